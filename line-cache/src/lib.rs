@@ -1,7 +1,15 @@
-use std::{ops::RangeBounds, sync::Arc};
+use std::{
+    ops::{Bound, RangeBounds},
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Arc,
+    },
+};
 
+use futures::{stream, Stream, StreamExt};
 use itertools::Itertools;
 use mini_moka::sync::Cache;
+use tokio::io::AsyncWrite;
 
 use line_index_reader::LineIndexReader;
 
@@ -9,38 +17,111 @@ pub type Index = u32;
 pub type Line = Arc<str>;
 pub type Lines = Box<[Line]>;
 
+/// Tells the lines of the files apart in a cache shared by them.
+pub type FileId = u64;
+
+/// A cache several [`LineCache`]s keep their lines in, see [`LineCache::shared`].
+pub type SharedCache = Arc<Cache<(FileId, Index), Line>>;
+
+static NEXT_FILE_ID: AtomicU64 = AtomicU64::new(0);
+
 pub struct LineCache {
     reader: Arc<LineIndexReader>,
-    cache: Arc<Cache<Index, Line>>,
+    id: FileId,
+    cache: SharedCache,
+    prefetch: PrefetchPolicy,
+    /// One past the last line ever cached, bounds the keys to evict.
+    cached_end: AtomicU32,
+}
+
+/// How many lines to read around a requested range on a cache miss.
+///
+/// The window on either side of the range is `min(range len * multiplier, max_extra)`
+/// lines, cut short by the first cached line. A zero `multiplier` disables prefetch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrefetchPolicy {
+    pub multiplier: u32,
+    pub max_extra: u32,
 }
 
-// TODO make cache capacity configurable.
+impl PrefetchPolicy {
+    /// Reads exactly the requested range.
+    pub const NONE: Self = Self {
+        multiplier: 0,
+        max_extra: 0,
+    };
+
+    #[must_use]
+    pub const fn window(&self, len: u32) -> u32 {
+        let window = len.saturating_mul(self.multiplier);
+        if window < self.max_extra {
+            window
+        } else {
+            self.max_extra
+        }
+    }
+}
+
+impl Default for PrefetchPolicy {
+    fn default() -> Self {
+        Self {
+            multiplier: 10,
+            max_extra: 2_048,
+        }
+    }
+}
+
+/// The capacity of a cache of its own, a shared one is sized by [`shared_cache`].
 const CACHE_MAX_CAPACITY: u64 = 256 * 1024 * 1024; // 256MB
 
+/// Lines fetched per step of [`LineCache::stream`].
+const STREAM_CHUNK: u32 = 1_024;
+
 impl LineCache {
     #[must_use]
     pub fn new(reader: Arc<LineIndexReader>) -> Self {
-        let cache = Arc::new(
-            Cache::builder()
-                .weigher(|_, value: &Line| {
-                    value
-                        .len()
-                        .try_into()
-                        .unwrap_or(u32::MAX)
-                        .clamp(1, u32::MAX)
-                })
-                .max_capacity(CACHE_MAX_CAPACITY)
-                .build(),
-        );
+        Self::with_prefetch_policy(reader, PrefetchPolicy::default())
+    }
 
-        Self { reader, cache }
+    #[must_use]
+    pub fn with_prefetch_policy(reader: Arc<LineIndexReader>, prefetch: PrefetchPolicy) -> Self {
+        Self {
+            reader,
+            id: NEXT_FILE_ID.fetch_add(1, Ordering::Relaxed),
+            cache: shared_cache(CACHE_MAX_CAPACITY),
+            prefetch,
+            cached_end: AtomicU32::new(0),
+        }
+    }
+
+    /// Keeps the lines in `cache` together with the lines of other files,
+    /// so its capacity bounds the memory taken by all of them.
+    #[must_use]
+    pub fn shared(reader: Arc<LineIndexReader>, cache: SharedCache) -> Self {
+        Self::shared_with_prefetch_policy(reader, cache, PrefetchPolicy::default())
+    }
+
+    /// See [`LineCache::shared`] and [`LineCache::with_prefetch_policy`].
+    #[must_use]
+    pub fn shared_with_prefetch_policy(
+        reader: Arc<LineIndexReader>,
+        cache: SharedCache,
+        prefetch: PrefetchPolicy,
+    ) -> Self {
+        Self {
+            reader,
+            id: NEXT_FILE_ID.fetch_add(1, Ordering::Relaxed),
+            cache,
+            prefetch,
+            cached_end: AtomicU32::new(0),
+        }
     }
 
     pub async fn line(&self, index: u32) -> Option<Line> {
-        if let Some(line) = self.cache.get(&index) {
+        if let Some(line) = self.cache.get(&(self.id, index)) {
             Some(line)
         } else {
-            self.lines(index..index).await.first().cloned()
+            self.lines(index..=index).await.first().cloned()
         }
     }
 
@@ -48,21 +129,12 @@ impl LineCache {
     where
         R: RangeBounds<u32> + Send,
     {
-        let start = match range.start_bound().cloned() {
-            std::ops::Bound::Included(i) => i,
-            std::ops::Bound::Excluded(i) => i + 1,
-            std::ops::Bound::Unbounded => 0,
-        };
-        let end = match range.end_bound().cloned() {
-            std::ops::Bound::Included(i) => i + 1,
-            std::ops::Bound::Excluded(i) => i,
-            std::ops::Bound::Unbounded => u32::MAX,
-        };
+        let (start, end) = bounds(&range);
 
         tracing::debug!("Fetching lines {start}:{end} from cache");
 
         let cached_lines = (start..end)
-            .map_while(|index| self.cache.get(&index))
+            .map_while(|index| self.cache.get(&(self.id, index)))
             .collect_vec();
 
         let len = cached_lines.len().try_into().unwrap_or(u32::MAX);
@@ -74,9 +146,19 @@ impl LineCache {
             return cached_lines.into_boxed_slice();
         }
 
-        let len = end - start;
-        // TODO pre-fetch lines before range if they are not in cache.
-        let prefetch = range.start..range.end.saturating_add(len.saturating_mul(10).min(2_048));
+        let window = self.prefetch.window(end - start);
+
+        // Pre-fetch lines around the range, up to the first cached line in either direction.
+        let prefetch_start = (range.start.saturating_sub(window)..range.start)
+            .rev()
+            .take_while(|&index| !self.cache.contains_key(&(self.id, index)))
+            .last()
+            .unwrap_or(range.start);
+        let prefetch_end = (range.end..range.end.saturating_add(window))
+            .take_while(|&index| !self.cache.contains_key(&(self.id, index)))
+            .last()
+            .map_or(range.end, |index| index + 1);
+        let prefetch = prefetch_start..prefetch_end;
 
         tracing::debug!("Fetching {}:{} from file", prefetch.start, prefetch.end);
 
@@ -89,41 +171,166 @@ impl LineCache {
             .map(Line::from)
             .collect_vec();
 
-        tracing::debug!(
-            "Read {}:{} from file",
-            prefetch.start,
-            prefetch.start + new_lines.len().try_into().unwrap_or(u32::MAX)
-        );
+        let read_end = prefetch.start + new_lines.len().try_into().unwrap_or(u32::MAX);
+        tracing::debug!("Read {}:{read_end} from file", prefetch.start);
 
         for (index, line) in prefetch.zip(&new_lines) {
-            self.cache.insert(index, line.clone());
+            self.cache.insert((self.id, index), line.clone());
         }
+        self.cached_end.fetch_max(read_end, Ordering::Relaxed);
 
         let mut lines = cached_lines;
-        lines.extend(new_lines.into_iter().take(range.len()));
+        lines.extend(
+            new_lines
+                .into_iter()
+                .skip((range.start - prefetch_start) as usize)
+                .take(range.len()),
+        );
         lines.into_boxed_slice()
     }
 
+    /// Yields the lines in `range`, fetching them in chunks like [`Self::lines`] does,
+    /// so the cached lines are reused and the read ones are cached with the prefetch policy.
+    pub fn stream<R>(&self, range: R) -> impl Stream<Item = Line> + Send + '_
+    where
+        R: RangeBounds<u32> + Send,
+    {
+        let (start, end) = bounds(&range);
+        let end = end.min(self.reader.len());
+
+        stream::unfold(start, move |start| async move {
+            if start >= end {
+                return None;
+            }
+
+            let lines = self
+                .lines(start..end.min(start.saturating_add(STREAM_CHUNK)))
+                .await;
+            if lines.is_empty() {
+                return None;
+            }
+
+            let next = start.saturating_add(lines.len().try_into().unwrap_or(u32::MAX));
+            Some((stream::iter(lines.into_vec()), next))
+        })
+        .flatten()
+    }
+
+    /// Copies the indexed content of the file to `writer` byte for byte,
+    /// line endings, delimiters, encoding and invalid text included.
+    /// The bytes are copied straight from the file, not through the cache,
+    /// so exporting a large file does not evict the lines on screen.
+    /// Returns the number of bytes written.
+    ///
+    /// # Errors
+    /// Fails if writing fails or the file can no longer be read to its indexed end.
+    pub async fn export_to<W>(&self, writer: &mut W) -> std::io::Result<u64>
+    where
+        W: AsyncWrite + Unpin + Send,
+    {
+        let len = self.reader.byte_len();
+        let written = self
+            .reader
+            .export(.., None, writer)
+            .await
+            .map_err(|error| match error {
+                line_index_reader::Error::IoError(error) => error,
+                error => std::io::Error::other(error),
+            })?;
+
+        if written < len {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                format!("exported {written} of {len} bytes"),
+            ));
+        }
+
+        Ok(written)
+    }
+
+    /// Reads the lines into the cache without returning them.
+    pub async fn warm<R>(&self, range: R)
+    where
+        R: RangeBounds<u32> + Send,
+    {
+        self.lines(range).await;
+    }
+
+    /// Evicts all cached lines of the file.
+    pub fn clear(&self) {
+        // Nobody else sees a cache that is not shared.
+        if Arc::strong_count(&self.cache) == 1 {
+            self.cache.invalidate_all();
+            self.cached_end.store(0, Ordering::Relaxed);
+        } else {
+            self.invalidate_from(0);
+        }
+    }
+
+    /// Evicts cached lines starting from `line`.
+    /// Call it after the reader was updated, the last line might have been appended to.
+    pub fn invalidate_from(&self, line: Index) {
+        let end = self.cached_end.fetch_min(line, Ordering::Relaxed);
+        for index in line..end {
+            self.cache.invalidate(&(self.id, index));
+        }
+    }
+
     pub fn lines_opt<R>(&self, range: R) -> Box<[Option<Line>]>
     where
         R: RangeBounds<u32> + Send,
     {
-        let start = match range.start_bound().cloned() {
-            std::ops::Bound::Included(i) => i,
-            std::ops::Bound::Excluded(i) => i + 1,
-            std::ops::Bound::Unbounded => 0,
-        };
-        let end = match range.end_bound().cloned() {
-            std::ops::Bound::Included(i) => i + 1,
-            std::ops::Bound::Excluded(i) => i,
-            std::ops::Bound::Unbounded => u32::MAX,
-        };
+        let (start, end) = bounds(&range);
 
         tracing::trace!("Fetching lines {start}:{end} from cache");
 
         (start..end)
-            .map(|index| self.cache.get(&index))
+            .map(|index| self.cache.get(&(self.id, index)))
             .collect_vec()
             .into_boxed_slice()
     }
 }
+
+impl Drop for LineCache {
+    /// The lines of a closed file are not kept in a shared cache.
+    fn drop(&mut self) {
+        if Arc::strong_count(&self.cache) > 1 {
+            self.invalidate_from(0);
+        }
+    }
+}
+
+/// Builds a cache to pass to [`LineCache::shared`], holding up to `max_capacity` bytes of lines.
+#[must_use]
+pub fn shared_cache(max_capacity: u64) -> SharedCache {
+    Arc::new(
+        Cache::builder()
+            .weigher(|_, value: &Line| {
+                value
+                    .len()
+                    .try_into()
+                    .unwrap_or(u32::MAX)
+                    .clamp(1, u32::MAX)
+            })
+            .max_capacity(max_capacity)
+            .build(),
+    )
+}
+
+/// Converts the range to `start..end`, an unbounded end is `u32::MAX`.
+fn bounds<R>(range: &R) -> (u32, u32)
+where
+    R: RangeBounds<u32>,
+{
+    let start = match range.start_bound().cloned() {
+        Bound::Included(i) => i,
+        Bound::Excluded(i) => i + 1,
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound().cloned() {
+        Bound::Included(i) => i + 1,
+        Bound::Excluded(i) => i,
+        Bound::Unbounded => u32::MAX,
+    };
+    (start, end)
+}
@@ -1,6 +1,8 @@
 use std::{io::Write, sync::Arc};
 
-use line_cache::LineCache;
+use futures::StreamExt;
+
+use line_cache::{shared_cache, LineCache, PrefetchPolicy};
 use line_index_reader::LineIndexReader;
 
 #[tokio::test]
@@ -45,3 +47,238 @@ async fn test_non_empty_file() {
     );
     assert!(cache.line(10).await.is_none());
 }
+
+#[tokio::test]
+async fn test_prefetch_before_range() {
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    for i in 0..1_000 {
+        file.write_all(format!("Line {i:03}\n").as_bytes()).unwrap();
+    }
+    file.flush().unwrap();
+
+    let reader = Arc::new(LineIndexReader::index(file.path()).await.unwrap());
+    let cache = LineCache::new(reader);
+
+    let lines = cache.lines(500..510).await;
+    assert_eq!(lines.len(), 10);
+    assert_eq!(lines[0].as_ref(), "Line 500");
+
+    assert!(cache.lines_opt(400..500).iter().all(Option::is_some));
+    assert!(cache.lines_opt(510..600).iter().all(Option::is_some));
+    assert!(cache.lines_opt(399..400).iter().all(Option::is_none));
+
+    // Scrolling up re-uses the cached lines after the range.
+    let lines = cache.lines(395..405).await;
+    assert_eq!(lines.len(), 10);
+    assert_eq!(lines[0].as_ref(), "Line 395");
+    assert_eq!(lines[9].as_ref(), "Line 404");
+
+    // No underflow at the beginning of the file.
+    assert_eq!(cache.lines(0..10).await.len(), 10);
+}
+
+#[tokio::test]
+async fn test_invalidate_from() {
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    file.write_all(b"Line 000\nLine 001").unwrap();
+    file.flush().unwrap();
+
+    let reader = Arc::new(LineIndexReader::index(file.path()).await.unwrap());
+    let cache = LineCache::new(reader.clone());
+
+    assert_eq!(cache.lines(..).await.len(), 2);
+
+    file.write_all(b" continued\nLine 002\n").unwrap();
+    file.flush().unwrap();
+
+    let old_len = reader.len();
+    reader.update().await.unwrap();
+
+    assert_eq!(cache.lines(1..2).await[0].as_ref(), "Line 001");
+
+    cache.invalidate_from(old_len - 1);
+
+    assert!(cache.lines_opt(0..1)[0].is_some());
+    assert!(cache.lines_opt(1..2)[0].is_none());
+
+    let lines = cache.lines(1..3).await;
+    assert_eq!(lines[0].as_ref(), "Line 001 continued");
+    assert_eq!(lines[1].as_ref(), "Line 002");
+}
+
+#[tokio::test]
+async fn test_line_on_cold_cache() {
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    for i in 0..10 {
+        file.write_all(format!("Line {i:03}\n").as_bytes()).unwrap();
+    }
+    file.flush().unwrap();
+
+    let reader = Arc::new(LineIndexReader::index(file.path()).await.unwrap());
+    let cache = LineCache::new(reader);
+
+    assert_eq!(cache.line(5).await.unwrap().as_ref(), "Line 005");
+}
+
+#[tokio::test]
+async fn test_warm_and_clear() {
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    for i in 0..100 {
+        file.write_all(format!("Line {i:03}\n").as_bytes()).unwrap();
+    }
+    file.flush().unwrap();
+
+    let reader = Arc::new(LineIndexReader::index(file.path()).await.unwrap());
+    let cache = LineCache::new(reader);
+
+    assert!(cache.lines_opt(10..20).iter().all(Option::is_none));
+
+    cache.warm(10..20).await;
+    assert!(cache.lines_opt(10..20).iter().all(Option::is_some));
+
+    cache.clear();
+    assert!(cache.lines_opt(..100).iter().all(Option::is_none));
+}
+
+#[tokio::test]
+async fn test_prefetch_disabled() {
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    for i in 0..100 {
+        file.write_all(format!("Line {i:03}\n").as_bytes()).unwrap();
+    }
+    file.flush().unwrap();
+
+    let reader = Arc::new(LineIndexReader::index(file.path()).await.unwrap());
+    let cache = LineCache::with_prefetch_policy(
+        reader,
+        PrefetchPolicy {
+            multiplier: 0,
+            max_extra: 2_048,
+        },
+    );
+
+    assert_eq!(cache.lines(40..50).await.len(), 10);
+
+    let cached = cache.lines_opt(..100);
+    assert!(cached[..40].iter().all(Option::is_none));
+    assert!(cached[40..50].iter().all(Option::is_some));
+    assert!(cached[50..].iter().all(Option::is_none));
+}
+
+#[tokio::test]
+async fn test_stream() {
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    for i in 0..10_000 {
+        file.write_all(format!("Line {i:05}\n").as_bytes()).unwrap();
+    }
+    file.flush().unwrap();
+
+    let reader = Arc::new(LineIndexReader::index(file.path()).await.unwrap());
+    let cache = LineCache::new(reader.clone());
+
+    // Some lines in the middle are cached already.
+    cache.warm(5_000..5_010).await;
+
+    let streamed = cache.stream(100..9_900).collect::<Vec<_>>().await;
+    assert_eq!(
+        streamed,
+        LineCache::new(reader.clone())
+            .lines(100..9_900)
+            .await
+            .into_vec()
+    );
+    assert!(cache.lines_opt(100..9_900).iter().all(Option::is_some));
+
+    // The end is clamped to the file.
+    assert_eq!(cache.stream(9_990..).count().await, 10);
+    assert_eq!(cache.stream(10_000..).count().await, 0);
+}
+
+#[rstest::rstest]
+#[case::eol(b"Line 1\n\nLine 3\n")]
+#[case::no_eol(b"Line 1\n\nLine 3")]
+#[case::empty(b"")]
+#[case::only_eol(b"\n")]
+#[case::crlf(b"Line 1\r\nLine 2\r\n")]
+#[case::invalid_utf8(b"ok 1\nbad \xFF\nok 3\n")]
+#[case::utf16(b"\xFF\xFEL\x001\x00\n\x00L\x002\x00")]
+#[tokio::test]
+async fn test_export_to(#[case] content: &[u8]) {
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    file.write_all(content).unwrap();
+    file.flush().unwrap();
+
+    let reader = Arc::new(LineIndexReader::index(file.path()).await.unwrap());
+    let cache = LineCache::new(reader);
+
+    let mut copy = vec![];
+    let written = cache.export_to(&mut copy).await.unwrap();
+
+    assert_eq!(content, copy);
+    assert_eq!(content.len() as u64, written);
+}
+
+#[tokio::test]
+async fn test_export_large_file() {
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    for i in 0..10_000 {
+        file.write_all(format!("Line {i:05}\n").as_bytes()).unwrap();
+    }
+    file.flush().unwrap();
+
+    let reader = Arc::new(LineIndexReader::index(file.path()).await.unwrap());
+    let cache = LineCache::new(reader);
+    cache.warm(5_000..5_010).await;
+
+    let mut copy = vec![];
+    cache.export_to(&mut copy).await.unwrap();
+
+    assert_eq!(std::fs::read(file.path()).unwrap(), copy);
+    // The copy does not go through the cache.
+    assert!(cache.lines_opt(..10).iter().all(Option::is_none));
+}
+
+#[tokio::test]
+async fn test_shared_cache() {
+    let mut files = vec![];
+    for name in ["a", "b"] {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        for i in 0..100 {
+            writeln!(file, "{name} {i:03}").unwrap();
+        }
+        file.flush().unwrap();
+        files.push(file);
+    }
+
+    let shared = shared_cache(1024 * 1024);
+    let a = LineCache::shared(
+        Arc::new(LineIndexReader::index(files[0].path()).await.unwrap()),
+        shared.clone(),
+    );
+    let b = LineCache::shared(
+        Arc::new(LineIndexReader::index(files[1].path()).await.unwrap()),
+        shared.clone(),
+    );
+
+    // The same line numbers of different files do not mix.
+    assert_eq!(a.line(10).await.unwrap().as_ref(), "a 010");
+    assert_eq!(b.line(10).await.unwrap().as_ref(), "b 010");
+
+    a.warm(..).await;
+    b.warm(..).await;
+    a.clear();
+    assert!(a.lines_opt(..100).iter().all(Option::is_none));
+    assert!(b.lines_opt(..100).iter().all(Option::is_some));
+
+    // A dropped file leaves nothing behind.
+    drop(b);
+    assert_eq!(shared.iter().count(), 0);
+
+    let c = LineCache::shared_with_prefetch_policy(
+        Arc::new(LineIndexReader::index(files[1].path()).await.unwrap()),
+        shared.clone(),
+        PrefetchPolicy::NONE,
+    );
+    assert_eq!(c.lines(40..50).await.len(), 10);
+    assert_eq!(shared.iter().count(), 10);
+}
@@ -1,24 +1,138 @@
 use std::{
-    io::{BufRead, Seek, SeekFrom},
-    ops::{Bound, RangeBounds},
+    io::{BufRead, Read, Seek, SeekFrom},
+    ops::{Bound, ControlFlow, RangeBounds},
     path::{Path, PathBuf},
-    sync::RwLock,
+    sync::{Arc, RwLock},
 };
 
+use futures::{Stream, StreamExt, TryStreamExt};
+use memmap2::Mmap;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use tempfile::TempPath;
 use tokio::{
     fs::File,
-    io::{AsyncReadExt, AsyncSeekExt, BufReader},
+    io::{
+        AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt,
+        BufReader,
+    },
     task::spawn_blocking,
 };
 
+mod persist;
+
 const READ_BUF_CAPACITY: usize = 8_192;
+const DEFAULT_DELIMITER: u8 = b'\n';
+const FINGERPRINT_LEN: usize = 1_024;
+const MIN_PARALLEL_CHUNK_LEN: u64 = 64 * 1024;
+const MAX_PARALLEL_CHUNK_LEN: u64 = 64 * 1024 * 1024;
+const DEFAULT_MAX_READ_BYTES: u64 = 64 * 1024 * 1024;
+/// Lines scanned at once by [`LineIndexReader::rfind`].
+const RFIND_CHUNK_LINES: u32 = 4_096;
+/// The progress of indexing is reported at most once per this many bytes scanned.
+const PROGRESS_STEP: u64 = 4 * 1024 * 1024;
 
 pub type Line = Box<str>;
 pub type Lines = Box<[Line]>;
 
+/// Text encoding of the file, detected when it is indexed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl Encoding {
+    /// Looks for a byte order mark, or for the zero bytes of ASCII text encoded in UTF-16.
+    fn detect(head: &[u8]) -> Self {
+        match head {
+            [0xFF, 0xFE, ..] => return Self::Utf16Le,
+            [0xFE, 0xFF, ..] => return Self::Utf16Be,
+            _ => {}
+        }
+
+        let units = head.len() / 2;
+        let zeros = |parity| {
+            head.iter()
+                .skip(parity)
+                .step_by(2)
+                .take(units)
+                .filter(|&&b| b == 0)
+                .count()
+        };
+        let (even, odd) = (zeros(0), zeros(1));
+
+        // Mostly ASCII text has every other byte zero.
+        if units == 0 {
+            Self::Utf8
+        } else if odd * 10 >= units * 3 && even * 10 < units {
+            Self::Utf16Le
+        } else if even * 10 >= units * 3 && odd * 10 < units {
+            Self::Utf16Be
+        } else {
+            Self::Utf8
+        }
+    }
+
+    /// The line ending, the `delimiter` as a code unit.
+    const fn eol(self, delimiter: u8) -> ([u8; 2], usize) {
+        match self {
+            Self::Utf8 => ([delimiter, 0], 1),
+            Self::Utf16Le => ([delimiter, 0], 2),
+            Self::Utf16Be => ([0, delimiter], 2),
+        }
+    }
+
+    const fn utf16(self) -> Option<&'static encoding_rs::Encoding> {
+        match self {
+            Self::Utf8 => None,
+            Self::Utf16Le => Some(encoding_rs::UTF_16LE),
+            Self::Utf16Be => Some(encoding_rs::UTF_16BE),
+        }
+    }
+}
+
+/// How [`LineIndexReader::find`] and friends match the needle.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SearchOptions {
+    /// Ignores the case, with Unicode simple case folding, so `ß` does not match `SS`.
+    pub case_insensitive: bool,
+    /// Matches only where the needle is not preceded or followed by a word character.
+    pub whole_word: bool,
+}
+
+impl SearchOptions {
+    /// Builds a regex matching `needle` literally, with the options applied.
+    #[must_use]
+    pub fn regex(self, needle: &str) -> regex::Regex {
+        let mut pattern = regex::escape(needle);
+        if self.whole_word {
+            pattern = format!(r"\b{{start-half}}{pattern}\b{{end-half}}");
+        }
+
+        regex::RegexBuilder::new(&pattern)
+            .case_insensitive(self.case_insensitive)
+            .build()
+            .expect("Escaped needle is a valid regex")
+    }
+}
+
 pub struct LineIndexReader {
     path: PathBuf,
-    offsets: RwLock<Vec<u64>>,
+    delimiter: u8,
+    /// Detected again while the head of the file fills up, see [`LineIndexReader::update`].
+    encoding: RwLock<Encoding>,
+    index: RwLock<Index>,
+    fingerprint: RwLock<Fingerprint>,
+    mmap: RwLock<Option<Mmap>>,
+    /// Opened by the first read and shared by the following ones, see [`read_at`].
+    /// Kept with its inode to notice the file replaced under the same path.
+    handle: RwLock<Option<(u64, Arc<std::fs::File>)>>,
+    /// A single read returns only the lines fitting in this many bytes.
+    max_read_bytes: u64,
+    /// The temporary file a stream is copied into, see [`LineIndexReader::index_reader`].
+    spool: Option<TempPath>,
 }
 
 /// Common interface
@@ -27,20 +141,145 @@ impl LineIndexReader {
     where
         P: AsRef<Path> + Clone + Send,
     {
-        let file = File::open(path.clone()).await?;
-        let offsets = spawn_blocking(move || index_lines(file)).await.unwrap()?;
+        Self::index_with_delimiter(path, DEFAULT_DELIMITER).await
+    }
+
+    /// Indexes records separated by an arbitrary `delimiter` byte instead of `\n`,
+    /// e.g. `\0` for `find -print0`-style logs.
+    pub async fn index_with_delimiter<P>(path: P, delimiter: u8) -> Result<Self, Error>
+    where
+        P: AsRef<Path> + Clone + Send,
+    {
+        Self::index_with(path, delimiter, |_, _| {}).await
+    }
+
+    /// Indexes the file like [`LineIndexReader::index`], calling `on_progress` with the bytes
+    /// scanned so far and the size of the file every few MiB, and once more at the end.
+    /// The callback runs on a blocking thread.
+    pub async fn index_with_progress<P, F>(path: P, on_progress: F) -> Result<Self, Error>
+    where
+        P: AsRef<Path> + Clone + Send,
+        F: Fn(u64, u64) + Send + 'static,
+    {
+        Self::index_with(path, DEFAULT_DELIMITER, on_progress).await
+    }
+
+    async fn index_with<P, F>(path: P, delimiter: u8, on_progress: F) -> Result<Self, Error>
+    where
+        P: AsRef<Path> + Clone + Send,
+        F: Fn(u64, u64) + Send + 'static,
+    {
+        let fingerprint = Fingerprint::read(path.as_ref()).await?;
+        let encoding = Encoding::detect(&fingerprint.head);
+
+        let file = File::open(path.clone()).await?.into_std().await;
+        let index =
+            spawn_blocking(move || index_lines_encoded(file, delimiter, encoding, on_progress))
+                .await
+                .unwrap()?;
 
         Ok(Self {
             path: path.as_ref().to_owned(),
-            offsets: RwLock::new(offsets),
+            delimiter,
+            encoding: RwLock::new(encoding),
+            index: RwLock::new(index),
+            fingerprint: RwLock::new(fingerprint),
+            mmap: RwLock::default(),
+            handle: RwLock::default(),
+            max_read_bytes: DEFAULT_MAX_READ_BYTES,
+            spool: None,
         })
     }
 
+    /// Indexes the file on the calling thread, for the callers without a tokio runtime.
+    /// The reader is used from a runtime afterwards as any other.
+    ///
+    /// It blocks the thread until the whole file is read, so it must not be called
+    /// from within an async context, use [`LineIndexReader::index`] there.
+    pub fn index_blocking<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let fingerprint = Fingerprint::read_blocking(path.as_ref())?;
+        let encoding = Encoding::detect(&fingerprint.head);
+
+        let file = std::fs::File::open(path.as_ref())?;
+        let index = index_lines_encoded(file, DEFAULT_DELIMITER, encoding, |_, _| {})?;
+
+        Ok(Self {
+            path: path.as_ref().to_owned(),
+            delimiter: DEFAULT_DELIMITER,
+            encoding: RwLock::new(encoding),
+            index: RwLock::new(index),
+            fingerprint: RwLock::new(fingerprint),
+            mmap: RwLock::default(),
+            handle: RwLock::default(),
+            max_read_bytes: DEFAULT_MAX_READ_BYTES,
+            spool: None,
+        })
+    }
+
+    /// Indexes the file and maps it into memory, so [`LineIndexReader::lines`] slices
+    /// the mapped memory instead of opening and reading the file on every call.
+    /// The mapping is refreshed by [`LineIndexReader::update`].
+    ///
+    /// The mapping assumes the file is only appended to. If another process truncates
+    /// the file while it is mapped, reading the truncated pages raises `SIGBUS`.
+    pub async fn index_mmap<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path> + Clone + Send,
+    {
+        let reader = Self::index(path).await?;
+        reader.remap()?;
+        Ok(reader)
+    }
+
+    /// Indexes a stream that cannot be re-opened or seeked, e.g. stdin fed by `tail -f`.
+    ///
+    /// The stream is copied into a temporary file in the background, the file is removed
+    /// with the reader. The copy only grows, so [`LineIndexReader::update`] indexes the data
+    /// piped since the last update the same way it indexes lines appended to a regular file.
+    /// The last line is not complete until the delimiter arrives, it is re-indexed as it grows.
+    /// The copy might be empty when it is indexed, the encoding is detected by the updates
+    /// as the first bytes arrive.
+    pub async fn index_reader<R>(source: R) -> Result<Self, Error>
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+    {
+        let (file, spool) = tempfile::NamedTempFile::new()?.into_parts();
+
+        tokio::spawn(async move {
+            if let Err(error) = pump(source, File::from_std(file)).await {
+                tracing::error!(%error, "Failed to copy the stream");
+            }
+        });
+
+        let mut reader = Self::index(&spool).await?;
+        reader.spool = Some(spool);
+        Ok(reader)
+    }
+
+    /// Caps the bytes read at once, so reading `..` of a huge file does not run out of memory.
+    /// Reads of larger ranges return the leading lines that fit, at least the first one.
+    /// 64 MiB by default.
+    #[must_use]
+    pub const fn with_max_read_bytes(mut self, max_read_bytes: u64) -> Self {
+        self.max_read_bytes = max_read_bytes;
+        self
+    }
+
+    /// The encoding detected from the byte order mark or the first bytes of the file.
+    #[must_use]
+    pub fn encoding(&self) -> Encoding {
+        *self.encoding.read().unwrap()
+    }
+
     #[must_use]
     pub fn len(&self) -> u32 {
-        self.offsets
+        self.index
             .read()
             .unwrap()
+            .offsets
             .len()
             .try_into()
             .unwrap_or(u32::MAX)
@@ -51,64 +290,577 @@ impl LineIndexReader {
         self.len() == 0
     }
 
+    /// Returns the offsets of the line starts as of the last update, without reading the file,
+    /// e.g. to compute the distribution of the line lengths.
+    /// The offsets are copied once and shared by the following calls until the index changes.
+    #[must_use]
+    pub fn offsets_snapshot(&self) -> Arc<[u64]> {
+        let cached = self.index.read().unwrap().snapshot.clone();
+        if let Some(snapshot) = cached {
+            return snapshot;
+        }
+
+        let mut index = self.index.write().unwrap();
+        let Index {
+            offsets, snapshot, ..
+        } = &mut *index;
+        let snapshot = snapshot
+            .get_or_insert_with(|| offsets.as_slice().into())
+            .clone();
+        drop(index);
+        snapshot
+    }
+
+    /// Returns the number of indexed bytes.
+    #[must_use]
+    pub fn byte_len(&self) -> u64 {
+        self.index.read().unwrap().end
+    }
+
+    /// Whether the indexed content ends with the delimiter, `false` for an empty file.
+    #[must_use]
+    pub fn ends_with_newline(&self) -> bool {
+        self.index.read().unwrap().terminated
+    }
+
+    /// Returns the `line` length in bytes without the delimiter.
+    /// A `\r` of a `\r\n` line ending is counted.
+    #[must_use]
+    pub fn line_len(&self, line: u32) -> Option<usize> {
+        let line = line as usize;
+        let (_, eol_len) = self.encoding().eol(self.delimiter);
+        let eol_len = eol_len as u64;
+
+        let len = {
+            let index = self.index.read().unwrap();
+            let start = *index.offsets.get(line)?;
+
+            match index.offsets.get(line + 1) {
+                Some(&next) => next - start - eol_len,
+                None if index.terminated => index.end - start - eol_len,
+                None => index.end - start,
+            }
+        };
+
+        usize::try_from(len).ok()
+    }
+
+    /// Returns the byte offset of the `line` start.
+    #[must_use]
+    pub fn offset_of(&self, line: u32) -> Option<u64> {
+        self.index
+            .read()
+            .unwrap()
+            .offsets
+            .get(line as usize)
+            .copied()
+    }
+
+    /// Returns the line containing the `byte` position.
+    #[must_use]
+    pub fn line_at_offset(&self, byte: u64) -> Option<u32> {
+        let index = self.index.read().unwrap();
+
+        if byte >= index.end {
+            return None;
+        }
+
+        index
+            .offsets
+            .partition_point(|&offset| offset <= byte)
+            .checked_sub(1)
+            .and_then(|line| line.try_into().ok())
+    }
+
+    /// Reads the lines overlapping `start_byte..end_byte`.
+    /// A range starting mid-line snaps to the start of that line.
+    #[must_use]
+    pub async fn lines_by_byte_range(&self, start_byte: u64, end_byte: u64) -> Lines {
+        if start_byte >= end_byte {
+            return Lines::default();
+        }
+
+        let Some(first) = self.line_at_offset(start_byte) else {
+            return Lines::default();
+        };
+        let end = self
+            .line_at_offset(end_byte - 1)
+            .map_or_else(|| self.len(), |last| last + 1);
+
+        self.lines(first..end).await
+    }
+
     #[must_use]
     pub async fn line(&self, line: u32) -> Option<Line> {
         self.lines(line..=line).await.first().cloned()
     }
 
+    /// Reads the lines in `range`, see [`LineIndexReader::try_lines`].
+    /// Errors are logged and yield no lines.
     #[must_use]
     pub async fn lines<R>(&self, range: R) -> Lines
     where
         R: RangeBounds<u32> + Send,
     {
-        let offset = {
-            let start = match range.start_bound().cloned() {
-                Bound::Included(x) => x,
-                Bound::Excluded(x) => x + 1,
-                Bound::Unbounded => 0,
-            } as usize;
+        self.try_lines(range).await.unwrap_or_else(|error| {
+            tracing::error!(%error, "Failed to read file {}", self.path.display());
+            Lines::default()
+        })
+    }
+
+    /// Reads the lines in `range`.
+    /// A range starting beyond the end of the index yields no lines,
+    /// failures to open or read the file are returned as errors.
+    pub async fn try_lines<R>(&self, range: R) -> Result<Lines, Error>
+    where
+        R: RangeBounds<u32> + Send,
+    {
+        self.read_lines(range, Decoding::Strict).await
+    }
 
-            let Some(&v) = self.offsets.read().unwrap().get(start) else {
-                return Lines::default();
+    /// Reads the lines in `range` replacing invalid UTF-8 sequences with `U+FFFD`,
+    /// so a malformed byte does not hide the lines around it.
+    /// Errors are logged and yield no lines.
+    #[must_use]
+    pub async fn lines_lossy<R>(&self, range: R) -> Lines
+    where
+        R: RangeBounds<u32> + Send,
+    {
+        self.read_lines(range, Decoding::Lossy)
+            .await
+            .unwrap_or_else(|error| {
+                tracing::error!(%error, "Failed to read file {}", self.path.display());
+                Lines::default()
+            })
+    }
+
+    async fn read_lines<R>(&self, range: R, decoding: Decoding) -> Result<Lines, Error>
+    where
+        R: RangeBounds<u32> + Send,
+    {
+        let (offset, limit, truncated) = {
+            let index = self.index.read().unwrap();
+
+            // An end past the last line reads up to the end of the indexed content,
+            // not the whole rest of the file.
+            let (start, end) = bounds(
+                &range,
+                u32::try_from(index.offsets.len()).unwrap_or(u32::MAX),
+            );
+            if end <= start {
+                return Ok(Lines::default());
+            }
+            let Some(&offset) = index.offsets.get(start as usize) else {
+                return Ok(Lines::default());
             };
 
-            v
-        };
+            let limit = index
+                .offsets
+                .get(end as usize)
+                .copied()
+                .unwrap_or(index.end)
+                .checked_sub(offset)
+                .and_then(|v| usize::try_from(v).ok());
 
-        let end = match range.end_bound().cloned() {
-            Bound::Included(x) => x + 1,
-            Bound::Excluded(x) => x,
-            Bound::Unbounded => u32::MAX,
-        } as usize;
+            let max = usize::try_from(self.max_read_bytes).unwrap_or(usize::MAX);
+            match limit {
+                Some(limit) if limit <= max => (offset, Some(limit), false),
+                _ => {
+                    // Up to the start of the first line not fitting,
+                    // the first line is read even if it does not fit.
+                    let cap = offset.saturating_add(self.max_read_bytes);
+                    let fits = index.offsets.partition_point(|&v| v <= cap);
+                    let first_end = index
+                        .offsets
+                        .get(start as usize + 1)
+                        .copied()
+                        .unwrap_or(index.end);
+                    (
+                        offset,
+                        usize::try_from(index.offsets[fits - 1].max(first_end) - offset).ok(),
+                        true,
+                    )
+                }
+            }
+        };
 
-        let limit = self
-            .offsets
-            .read()
-            .unwrap()
-            .get(end)
-            .and_then(|v| v.checked_sub(offset))
-            .and_then(|v| usize::try_from(v).ok());
+        if truncated {
+            tracing::warn!(
+                "Reading lines of {} truncated to {limit:?} bytes",
+                self.path.display()
+            );
+        }
 
         tracing::debug!("Reading lines {}:{offset}:{limit:?}", self.path.display());
 
+        if let Some(mmap) = self.mmap.read().unwrap().as_ref() {
+            return split_lines(
+                mapped_range(mmap, offset, limit),
+                self.delimiter,
+                self.encoding(),
+                decoding,
+            );
+        }
+
+        let buf = self.read_bytes(offset, limit).await?;
+
+        // Reading from the mem buf, no need for async.
+        split_lines(&buf, self.delimiter, self.encoding(), decoding)
+    }
+
+    /// Reads through the handle kept open, a read costs a `stat` and a `pread`
+    /// instead of `open`, `lseek`, `read` and `close`, and one trip to the blocking pool
+    /// instead of one per call. A removed file fails to read as if it was opened again.
+    #[cfg(unix)]
+    async fn read_bytes(&self, offset: u64, limit: Option<usize>) -> Result<Vec<u8>, Error> {
+        use std::os::unix::fs::MetadataExt;
+
+        let handle = self.handle.read().unwrap().clone();
+        let path = self.path.clone();
+
+        let (handle, buf) = spawn_blocking(move || {
+            let inode = std::fs::metadata(&path)?.ino();
+            let handle = match handle {
+                Some(handle) if handle.0 == inode => handle,
+                _ => (inode, Arc::new(std::fs::File::open(&path)?)),
+            };
+            let buf = read_at(&handle.1, offset, limit)?;
+            Ok::<_, Error>((handle, buf))
+        })
+        .await
+        .unwrap()?;
+
+        *self.handle.write().unwrap() = Some(handle);
+        Ok(buf)
+    }
+
+    #[cfg(not(unix))]
+    async fn read_bytes(&self, offset: u64, limit: Option<usize>) -> Result<Vec<u8>, Error> {
+        let file = File::open(&self.path).await?;
+        read_bytes(file, offset, limit).await
+    }
+
+    /// Reads the last `n` lines, or all lines if there are fewer, with a single seek.
+    #[must_use]
+    pub async fn tail(&self, n: u32) -> Lines {
+        let len = self.len();
+        self.lines(len.saturating_sub(n)..len).await
+    }
+
+    /// Reads the lines in `range` in descending order.
+    #[must_use]
+    pub async fn lines_rev<R>(&self, range: R) -> Lines
+    where
+        R: RangeBounds<u32> + Send,
+    {
+        let mut lines = self.lines(range).await;
+        lines.reverse();
+        lines
+    }
+
+    /// Streams the lines in `range`, reading them one by one from a single seek position.
+    /// Lines appended after the last update are not streamed.
+    pub fn stream<R>(&self, range: R) -> impl Stream<Item = Result<Line, Error>> + Send + 'static
+    where
+        R: RangeBounds<u32>,
+    {
+        let (start, end) = bounds(&range, self.len());
+        let count = end.saturating_sub(start) as usize;
+        let offset = self.offset_of(start).unwrap_or_default();
+        let path = self.path.clone();
+        let delimiter = self.delimiter;
+        let encoding = self.encoding();
+
+        tracing::debug!("Streaming lines {}:{start}:{end}", path.display());
+
+        futures::stream::once(async move {
+            let mut file = File::open(&path).await?;
+            file.seek(SeekFrom::Start(offset)).await?;
+
+            Ok::<_, Error>(futures::stream::try_unfold(
+                BufReader::new(file),
+                move |mut reader| async move {
+                    let mut buf = Vec::with_capacity(READ_BUF_CAPACITY);
+                    let read = read_record(&mut reader, delimiter, encoding, &mut buf).await?;
+                    Ok((read > 0).then_some((buf, reader)))
+                },
+            ))
+        })
+        .try_flatten()
+        .take(count)
+        .map(move |record| {
+            record.and_then(|record| {
+                Decoding::Strict.decode(trim_eol(&record, delimiter, encoding), encoding)
+            })
+        })
+    }
+
+    /// Returns indices of the lines in `range` matching `re`.
+    /// The file is read line by line, lines that are not valid UTF-8 are skipped.
+    pub async fn find_all_regex<R>(&self, re: &regex::Regex, range: R) -> Vec<u32>
+    where
+        R: RangeBounds<u32> + Send,
+    {
+        let mut matches = vec![];
+
+        self.scan(range, |index, line| {
+            if re.is_match(line) {
+                matches.push(index);
+            }
+            ControlFlow::Continue(())
+        })
+        .await;
+
+        matches
+    }
+
+    /// Returns the lines in `range` containing `needle`, with their indices.
+    pub async fn find_all<R>(
+        &self,
+        needle: &str,
+        options: SearchOptions,
+        range: R,
+    ) -> Vec<(u32, Line)>
+    where
+        R: RangeBounds<u32> + Send,
+    {
+        let re = options.regex(needle);
+        let mut matches = vec![];
+
+        self.scan(range, |index, line| {
+            if re.is_match(line) {
+                matches.push((index, line.into()));
+            }
+            ControlFlow::Continue(())
+        })
+        .await;
+
+        matches
+    }
+
+    /// Streams the indices of the lines in `range` containing `needle` as they are found,
+    /// so the matches of a large file can be counted without waiting for all of them.
+    /// Lines that are not valid text are skipped, the stream ends at the first read error.
+    pub fn find_stream<R>(
+        &self,
+        needle: &str,
+        options: SearchOptions,
+        range: R,
+    ) -> impl Stream<Item = u32> + Send + 'static
+    where
+        R: RangeBounds<u32>,
+    {
+        let (start, end) = bounds(&range, self.len());
+        let re = options.regex(needle);
+
+        self.stream(start..end)
+            .zip(futures::stream::iter(start..end))
+            .filter_map(move |(line, index)| {
+                let found = matches!(line, Ok(line) if re.is_match(&line));
+                std::future::ready(found.then_some(index))
+            })
+    }
+
+    /// Writes the lines in `range` to `writer` without holding them in memory,
+    /// returns the number of bytes written.
+    /// Without `re` the bytes are copied as they are in the file, line endings included.
+    /// Otherwise only the matching lines are written, each followed by `\n`,
+    /// lines that are not valid text are skipped.
+    pub async fn export<R, W>(
+        &self,
+        range: R,
+        re: Option<&regex::Regex>,
+        writer: &mut W,
+    ) -> Result<u64, Error>
+    where
+        R: RangeBounds<u32> + Send,
+        W: AsyncWrite + Unpin + Send,
+    {
+        let (start, end) = bounds(&range, self.len());
+        if start >= end {
+            return Ok(0);
+        }
+
+        let Some(re) = re else {
+            // The byte order mark goes with the first line.
+            let from = if start == 0 {
+                0
+            } else {
+                self.offset_of(start).unwrap_or_default()
+            };
+            let to = self.offset_of(end).unwrap_or_else(|| self.byte_len());
+
+            let mut file = File::open(&self.path).await?;
+            file.seek(SeekFrom::Start(from)).await?;
+
+            let written = tokio::io::copy(&mut file.take(to - from), writer).await?;
+            writer.flush().await?;
+            return Ok(written);
+        };
+
+        let mut written = 0;
+        let mut lines = std::pin::pin!(self.stream(start..end));
+
+        while let Some(line) = lines.next().await {
+            let line = match line {
+                Ok(line) => line,
+                Err(Error::Utf8Error(_) | Error::DecodeError(_)) => continue,
+                Err(error) => return Err(error),
+            };
+
+            if re.is_match(&line) {
+                writer.write_all(line.as_bytes()).await?;
+                writer.write_all(b"\n").await?;
+                written += line.len() as u64 + 1;
+            }
+        }
+
+        writer.flush().await?;
+        Ok(written)
+    }
+
+    /// Returns the index of the first line in `range` containing `needle`.
+    pub async fn find<R>(&self, needle: &str, options: SearchOptions, range: R) -> Option<u32>
+    where
+        R: RangeBounds<u32> + Send,
+    {
+        let re = options.regex(needle);
+        let mut found = None;
+
+        self.scan(range, |index, line| {
+            if re.is_match(line) {
+                found = Some(index);
+                return ControlFlow::Break(());
+            }
+            ControlFlow::Continue(())
+        })
+        .await;
+
+        found
+    }
+
+    /// Returns the index of the last line in `range` containing `needle`.
+    /// The range is scanned in chunks from its end, a match near the end reads only the last chunk.
+    pub async fn rfind<R>(&self, needle: &str, options: SearchOptions, range: R) -> Option<u32>
+    where
+        R: RangeBounds<u32> + Send,
+    {
+        let re = options.regex(needle);
+        let (start, mut end) = bounds(&range, self.len());
+
+        while end > start {
+            let chunk_start = end.saturating_sub(RFIND_CHUNK_LINES).max(start);
+            let mut found = None;
+
+            self.scan(chunk_start..end, |index, line| {
+                if re.is_match(line) {
+                    found = Some(index);
+                }
+                ControlFlow::Continue(())
+            })
+            .await;
+
+            if found.is_some() {
+                return found;
+            }
+            end = chunk_start;
+        }
+
+        None
+    }
+
+    /// Reads the lines in `range` one by one until `f` breaks.
+    /// Lines that are not valid UTF-8 are skipped.
+    async fn scan<R, F>(&self, range: R, mut f: F)
+    where
+        R: RangeBounds<u32> + Send,
+        F: FnMut(u32, &str) -> ControlFlow<()> + Send,
+    {
+        let (start, end) = bounds(&range, self.len());
+
+        let Some(&offset) = self.index.read().unwrap().offsets.get(start as usize) else {
+            return;
+        };
+
         let Ok(file) = File::open(&self.path).await else {
             tracing::error!("Failed to read file {}", self.path.display());
-            return Lines::default();
+            return;
         };
 
-        read_lines(file, offset, limit).await.unwrap_or_default()
+        let mut reader = BufReader::new(file);
+        if let Err(error) = reader.seek(SeekFrom::Start(offset)).await {
+            tracing::error!(%error, "Failed to seek file {}", self.path.display());
+            return;
+        }
+
+        let mut buf = Vec::with_capacity(READ_BUF_CAPACITY);
+        let encoding = self.encoding();
+
+        for index in start..end {
+            buf.clear();
+
+            match read_record(&mut reader, self.delimiter, encoding, &mut buf).await {
+                Ok(0) => break, // EOF
+                Ok(_) => {}
+                Err(error) => {
+                    tracing::error!(%error, "Failed to read file {}", self.path.display());
+                    break;
+                }
+            }
+
+            let line = trim_eol(&buf, self.delimiter, encoding);
+            if let Ok(line) = Decoding::Strict.decode(line, encoding) {
+                if f(index, &line).is_break() {
+                    break;
+                }
+            }
+        }
     }
 
+    /// Indexes the lines appended since the last update and returns the number of new lines.
+    /// If the file was rotated, truncated or replaced, it is re-indexed from scratch
+    /// and the new total number of lines is returned.
     pub async fn update(&self) -> Result<u32, Error> {
-        if let Ok(index) = self.consistency().await?.into_inconsistent() {
-            return Err(Error::InconsistentIndex(index));
+        let fingerprint = Fingerprint::read(&self.path).await?;
+
+        let is_same_file = self.fingerprint.read().unwrap().is_same_file(&fingerprint);
+
+        // Map the new content before the index refers to it.
+        if self.mmap.read().unwrap().is_some() {
+            self.remap()?;
+        }
+
+        // A file cut at a line start keeps its head and the lines before the cut.
+        let is_truncated =
+            tokio::fs::metadata(&self.path).await?.len() < self.index.read().unwrap().end;
+
+        // The head of a file indexed while empty or short, e.g. a spool of a stream,
+        // might tell another encoding once it is filled.
+        let is_reencoded = Encoding::detect(&fingerprint.head) != self.encoding();
+
+        // The lines before the last one were verified by the previous update.
+        let old_len = self.len();
+        if !is_same_file
+            || is_truncated
+            || is_reencoded
+            || self
+                .consistency_from(old_len.saturating_sub(1))
+                .await?
+                .is_inconsistent()
+        {
+            tracing::debug!("File {} was replaced, re-indexing", self.path.display());
+            return self.reindex(fingerprint).await;
         }
 
-        let old_len = self.offsets.read().unwrap().len();
+        // The head might have grown since the last update.
+        *self.fingerprint.write().unwrap() = fingerprint;
+
+        // The last line might have been partial, index it again from its start.
         let offset = self
-            .offsets
+            .index
             .read()
             .unwrap()
+            .offsets
             .last()
             .copied()
             .unwrap_or_default();
@@ -116,45 +868,174 @@ impl LineIndexReader {
         let mut file = File::open(&self.path).await?;
         let pos = file.seek(SeekFrom::Start(offset)).await?;
         assert_eq!(pos, offset);
+        let file = file.into_std().await;
 
-        let offsets = spawn_blocking(move || index_lines(file)).await.unwrap()?;
-        self.offsets.write().unwrap().extend(&offsets[1..]);
+        let (delimiter, encoding) = (self.delimiter, self.encoding());
+        let appended =
+            spawn_blocking(move || index_lines_encoded(file, delimiter, encoding, |_, _| {}))
+                .await
+                .unwrap()?;
+        self.index.write().unwrap().extend(&appended);
 
-        Ok(self
-            .offsets
-            .read()
-            .unwrap()
-            .len()
-            .checked_sub(old_len)
-            .map(|v| v.try_into().unwrap_or(u32::MAX))
-            .unwrap_or_default())
+        Ok(self.len().saturating_sub(old_len))
+    }
+
+    /// Indexes the file scanning its chunks in parallel, see [`index_lines_parallel`].
+    /// Faster than [`LineIndexReader::index`] for large files on multicore machines.
+    pub async fn index_parallel<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path> + Clone + Send,
+    {
+        let fingerprint = Fingerprint::read(path.as_ref()).await?;
+        let encoding = Encoding::detect(&fingerprint.head);
+        if encoding != Encoding::Utf8 {
+            // The chunks might split the UTF-16 code units.
+            return Self::index(path).await;
+        }
+
+        let owned_path = path.as_ref().to_owned();
+        let index = spawn_blocking(move || index_lines_parallel(&owned_path, DEFAULT_DELIMITER))
+            .await
+            .unwrap()?;
+
+        Ok(Self {
+            path: path.as_ref().to_owned(),
+            delimiter: DEFAULT_DELIMITER,
+            encoding: RwLock::new(encoding),
+            index: RwLock::new(index),
+            fingerprint: RwLock::new(fingerprint),
+            mmap: RwLock::default(),
+            handle: RwLock::default(),
+            max_read_bytes: DEFAULT_MAX_READ_BYTES,
+            spool: None,
+        })
+    }
+
+    fn remap(&self) -> Result<(), Error> {
+        let file = std::fs::File::open(&self.path)?;
+
+        // SAFETY: The file is expected to be append-only, see `index_mmap`.
+        let mmap = unsafe { Mmap::map(&file)? };
+
+        *self.mmap.write().unwrap() = Some(mmap);
+        Ok(())
+    }
+
+    async fn reindex(&self, fingerprint: Fingerprint) -> Result<u32, Error> {
+        let file = File::open(&self.path).await?.into_std().await;
+        let (delimiter, encoding) = (self.delimiter, Encoding::detect(&fingerprint.head));
+        let index =
+            spawn_blocking(move || index_lines_encoded(file, delimiter, encoding, |_, _| {}))
+                .await
+                .unwrap()?;
+
+        *self.index.write().unwrap() = index;
+        *self.encoding.write().unwrap() = encoding;
+        *self.fingerprint.write().unwrap() = fingerprint;
+
+        Ok(self.len())
     }
 
     /// Verifies that the index is consistent with the file.
     /// Return `true` if the index is consistent, `false` otherwise.
     pub async fn consistency(&self) -> Result<IndexConsistency, Error> {
+        self.consistency_from(0).await
+    }
+
+    /// Verifies the offsets of the lines starting from `start`,
+    /// the ones before are assumed to be consistent.
+    pub async fn consistency_from(&self, start: u32) -> Result<IndexConsistency, Error> {
+        // The first line has no delimiter before it.
+        let start = (start as usize).max(1);
+        let offsets = {
+            let index = self.index.read().unwrap();
+            index.offsets.get(start..).unwrap_or_default().to_vec()
+        };
+
+        self.verify_offsets((start..).zip(offsets)).await
+    }
+
+    /// Verifies the offsets of the second and the last lines and of `samples` random lines
+    /// in between, for the indexes too large to check in full.
+    /// The same `seed` picks the same lines, [`LineIndexReader::consistency`] checks all of them.
+    pub async fn consistency_sampled(
+        &self,
+        samples: usize,
+        seed: u64,
+    ) -> Result<IndexConsistency, Error> {
+        let len = self.len() as usize;
+        if len < 2 {
+            return Ok(IndexConsistency::Consistent);
+        }
+
+        let mut rng = fastrand::Rng::with_seed(seed);
+        let mut lines = [1, len - 1]
+            .into_iter()
+            .chain(std::iter::repeat_with(|| rng.usize(1..len)).take(samples))
+            .collect::<Vec<_>>();
+        // Seeking forward only, the first inconsistent line is found first.
+        lines.sort_unstable();
+        lines.dedup();
+
+        let offsets = {
+            let offsets = &self.index.read().unwrap().offsets;
+            lines
+                .into_iter()
+                .filter_map(|line| Some((line, *offsets.get(line)?)))
+                .collect::<Vec<_>>()
+        };
+
+        self.verify_offsets(offsets).await
+    }
+
+    /// Checks that the delimiter precedes each of the line offsets, in the given order.
+    async fn verify_offsets<I>(&self, offsets: I) -> Result<IndexConsistency, Error>
+    where
+        I: IntoIterator<Item = (usize, u64)> + Send,
+        I::IntoIter: Send,
+    {
         let mut file = File::open(&self.path).await?;
         let file_len = file.metadata().await?.len();
 
-        let offsets = self.offsets.read().unwrap().clone();
+        let (eol, eol_len) = self.encoding().eol(self.delimiter);
+        let eol = &eol[..eol_len];
 
-        for (index, &offset) in offsets.iter().enumerate().skip(1) {
-            assert!(offset > 0);
-            let offset = offset - 1;
+        for (index, offset) in offsets {
+            assert!(offset >= eol_len as u64);
+            let offset = offset - eol_len as u64;
 
             if offset > file_len {
-                dbg!(1);
+                tracing::warn!(
+                    line = index,
+                    offset,
+                    file_len,
+                    "Index is beyond the end of {}",
+                    self.path.display()
+                );
                 return Ok(IndexConsistency::Inconsistent(index));
             }
 
             if offset != file.seek(SeekFrom::Start(offset)).await? {
-                dbg!(2);
+                tracing::warn!(
+                    line = index,
+                    offset,
+                    "Failed to seek in {}",
+                    self.path.display()
+                );
                 return Ok(IndexConsistency::Inconsistent(index));
             }
 
-            let byte = file.read_u8().await?;
-            if b'\n' != byte {
-                dbg!(byte as char);
+            let mut bytes = [0; 2];
+            file.read_exact(&mut bytes[..eol_len]).await?;
+            if eol != &bytes[..eol_len] {
+                tracing::warn!(
+                    line = index,
+                    offset,
+                    ?bytes,
+                    expected = ?eol,
+                    "No delimiter before the line start in {}",
+                    self.path.display()
+                );
                 return Ok(IndexConsistency::Inconsistent(index));
             }
         }
@@ -163,20 +1044,138 @@ impl LineIndexReader {
     }
 }
 
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct Index {
+    /// Offsets of the line starts.
+    offsets: Vec<u64>,
+    /// Offset right after the last indexed byte.
+    end: u64,
+    /// Whether the last line ends with the delimiter.
+    terminated: bool,
+    /// The copy of the offsets handed out by [`LineIndexReader::offsets_snapshot`],
+    /// dropped when they change.
+    snapshot: Option<Arc<[u64]>>,
+}
+
+impl Index {
+    /// Appends the index of the content scanned from the start of the last line.
+    fn extend(&mut self, appended: &Self) {
+        // The last line is indexed again, skip its offset.
+        let skip = usize::from(!self.offsets.is_empty());
+        self.offsets.extend(appended.offsets.iter().skip(skip));
+        self.end = appended.end;
+        self.terminated = appended.terminated;
+        self.snapshot = None;
+    }
+}
+
 #[derive(Debug, Clone, Copy, enum_as_inner::EnumAsInner, PartialEq, Eq)]
 pub enum IndexConsistency {
     Consistent,
     Inconsistent(usize),
 }
 
-async fn read_lines(file: File, offset: u64, limit: Option<usize>) -> Result<Lines, Error> {
+/// Identifies the file the index was built for, to detect rotated or replaced files.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct Fingerprint {
+    inode: Option<u64>,
+    head: Box<[u8]>,
+}
+
+impl Fingerprint {
+    async fn read(path: &Path) -> Result<Self, Error> {
+        let file = File::open(path).await?;
+        let metadata = file.metadata().await?;
+
+        let mut head = Vec::with_capacity(FINGERPRINT_LEN);
+        file.take(FINGERPRINT_LEN as u64)
+            .read_to_end(&mut head)
+            .await?;
+
+        Ok(Self {
+            inode: inode(&metadata),
+            head: head.into_boxed_slice(),
+        })
+    }
+
+    fn read_blocking(path: &Path) -> Result<Self, Error> {
+        let file = std::fs::File::open(path)?;
+        let metadata = file.metadata()?;
+
+        let mut head = Vec::with_capacity(FINGERPRINT_LEN);
+        file.take(FINGERPRINT_LEN as u64).read_to_end(&mut head)?;
+
+        Ok(Self {
+            inode: inode(&metadata),
+            head: head.into_boxed_slice(),
+        })
+    }
+
+    /// Whether `current` is the same file, possibly grown since `self` was taken.
+    fn is_same_file(&self, current: &Self) -> bool {
+        self.inode == current.inode && current.head.starts_with(&self.head)
+    }
+}
+
+#[cfg(unix)]
+#[allow(clippy::unnecessary_wraps)] // Consistent with non-unix platforms.
+fn inode(metadata: &std::fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.ino())
+}
+
+#[cfg(not(unix))]
+const fn inode(_: &std::fs::Metadata) -> Option<u64> {
+    None
+}
+
+/// Converts `range` into `start..end` clamped to `len`.
+fn bounds<R>(range: &R, len: u32) -> (u32, u32)
+where
+    R: RangeBounds<u32>,
+{
+    let start = match range.start_bound().cloned() {
+        Bound::Included(x) => x,
+        Bound::Excluded(x) => x.saturating_add(1),
+        Bound::Unbounded => 0,
+    };
+
+    let end = match range.end_bound().cloned() {
+        Bound::Included(x) => x.saturating_add(1),
+        Bound::Excluded(x) => x,
+        Bound::Unbounded => len,
+    };
+
+    (start, end.min(len))
+}
+
+/// Copies `source` into `file` until EOF, every chunk is written through as it arrives.
+async fn pump<R>(mut source: R, mut file: File) -> Result<(), Error>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut buf = vec![0; READ_BUF_CAPACITY];
+
+    loop {
+        let read = source.read(&mut buf).await?;
+        if read == 0 {
+            return Ok(()); // EOF
+        }
+
+        file.write_all(&buf[..read]).await?;
+        file.flush().await?;
+    }
+}
+
+#[cfg(not(unix))]
+async fn read_bytes(file: File, offset: u64, limit: Option<usize>) -> Result<Vec<u8>, Error> {
     let mut reader = BufReader::new(file);
     let pos = reader.seek(SeekFrom::Start(offset)).await?;
     assert_eq!(pos, offset);
 
     let buf = if let Some(limit) = limit {
         let mut buf = Vec::with_capacity(limit);
-        reader.read_buf(&mut buf).await?;
+        reader.take(limit as u64).read_to_end(&mut buf).await?;
         buf
     } else {
         // Dangerous!!! Reading without the limit.
@@ -185,50 +1184,383 @@ async fn read_lines(file: File, offset: u64, limit: Option<usize>) -> Result<Lin
         buf
     };
 
-    // Reading from the mem buf, no need for async.
-    std::io::BufReader::new(std::io::Cursor::new(buf))
-        .lines()
-        .map(|line| line.map(Into::into))
+    Ok(buf)
+}
+
+/// Reads `limit` bytes at `offset`, or the rest of the file if no limit.
+/// The read does not move the file cursor, so the file is shared by concurrent reads.
+#[cfg(unix)]
+fn read_at(file: &std::fs::File, offset: u64, limit: Option<usize>) -> Result<Vec<u8>, Error> {
+    use std::os::unix::fs::FileExt;
+
+    let mut buf = vec![0; limit.unwrap_or(READ_BUF_CAPACITY)];
+    let mut filled = 0;
+
+    loop {
+        if filled == buf.len() {
+            if limit.is_some() {
+                break;
+            }
+            // Dangerous!!! Reading without the limit.
+            buf.resize(buf.len() * 2, 0);
+        }
+
+        match file.read_at(&mut buf[filled..], offset + filled as u64) {
+            Ok(0) => break, // EOF
+            Ok(read) => filled += read,
+            Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error.into()),
+        }
+    }
+
+    buf.truncate(filled);
+    Ok(buf)
+}
+
+/// Returns `limit` bytes of the mapped file starting at `offset`, or the rest if no limit.
+fn mapped_range(mmap: &Mmap, offset: u64, limit: Option<usize>) -> &[u8] {
+    let start = usize::try_from(offset)
+        .unwrap_or(usize::MAX)
+        .min(mmap.len());
+    let end = limit.map_or(mmap.len(), |limit| {
+        start.saturating_add(limit).min(mmap.len())
+    });
+
+    &mmap[start..end]
+}
+
+fn split_lines(
+    buf: &[u8],
+    delimiter: u8,
+    encoding: Encoding,
+    decoding: Decoding,
+) -> Result<Lines, Error> {
+    split_records(buf, delimiter, encoding)
+        .into_iter()
+        .map(|line| decoding.decode(trim_eol(line, delimiter, encoding), encoding))
         .collect::<Result<Vec<_>, _>>()
         .map(Vec::into_boxed_slice)
-        .map_err(Into::into)
 }
 
-fn index_lines(file: File) -> Result<Vec<u64>, Error> {
-    let mut file = file.try_into_std().unwrap();
+/// Splits `buf` after every line ending, a UTF-16 one is looked for at the code unit boundaries.
+fn split_records(buf: &[u8], delimiter: u8, encoding: Encoding) -> Vec<&[u8]> {
+    let (eol, len) = encoding.eol(delimiter);
+    if len == 1 {
+        return buf.split_inclusive(|&b| b == delimiter).collect();
+    }
+
+    // A trailing odd byte is not a part of any code unit.
+    let buf = &buf[..buf.len() - buf.len() % len];
+
+    let mut records = vec![];
+    let mut start = 0;
+    for (unit, pos) in buf.chunks_exact(len).zip((len..).step_by(len)) {
+        if unit == &eol[..len] {
+            records.push(&buf[start..pos]);
+            start = pos;
+        }
+    }
+    if start < buf.len() {
+        records.push(&buf[start..]);
+    }
+
+    records
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Decoding {
+    /// Fails on invalid UTF-8 or UTF-16.
+    Strict,
+    /// Replaces invalid sequences with `U+FFFD`.
+    Lossy,
+}
+
+impl Decoding {
+    fn decode(self, line: &[u8], encoding: Encoding) -> Result<Line, Error> {
+        let Some(utf16) = encoding.utf16() else {
+            return match self {
+                Self::Strict => std::str::from_utf8(line)
+                    .map(Into::into)
+                    .map_err(Into::into),
+                Self::Lossy => Ok(String::from_utf8_lossy(line).into()),
+            };
+        };
+
+        match self {
+            Self::Strict => utf16
+                .decode_without_bom_handling_and_without_replacement(line)
+                .map(Into::into)
+                .ok_or(Error::DecodeError(encoding)),
+            Self::Lossy => Ok(utf16.decode_without_bom_handling(line).0.into()),
+        }
+    }
+}
+
+/// Strips the line ending: the `delimiter`, or `\r\n` if the delimiter is `\n`.
+fn trim_eol(line: &[u8], delimiter: u8, encoding: Encoding) -> &[u8] {
+    let (eol, len) = encoding.eol(delimiter);
+    let line = line.strip_suffix(&eol[..len]).unwrap_or(line);
+
+    if delimiter == b'\n' {
+        let (cr, _) = encoding.eol(b'\r');
+        line.strip_suffix(&cr[..len]).unwrap_or(line)
+    } else {
+        line
+    }
+}
+
+/// Reads a line with its ending into `buf`, returns the number of bytes read, 0 at EOF.
+async fn read_record(
+    reader: &mut BufReader<File>,
+    delimiter: u8,
+    encoding: Encoding,
+    buf: &mut Vec<u8>,
+) -> std::io::Result<usize> {
+    let (eol, len) = encoding.eol(delimiter);
+    if len == 1 {
+        return reader.read_until(delimiter, buf).await;
+    }
+
+    let mut unit = [0; 2];
+    loop {
+        match reader.read_exact(&mut unit).await {
+            Ok(_) => buf.extend_from_slice(&unit),
+            Err(error) if error.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(error) => return Err(error),
+        }
+
+        if unit == eol {
+            break;
+        }
+    }
+
+    Ok(buf.len())
+}
 
+fn index_lines_encoded(
+    file: std::fs::File,
+    delimiter: u8,
+    encoding: Encoding,
+    on_progress: impl Fn(u64, u64),
+) -> Result<Index, Error> {
+    let progress = Progress::new(&file, on_progress)?;
+    match encoding {
+        Encoding::Utf8 => index_lines(file, delimiter, progress),
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            index_lines_utf16(file, encoding.eol(delimiter).0, progress)
+        }
+    }
+}
+
+/// Reports the offset reached by a scan, throttled to once per [`PROGRESS_STEP`].
+struct Progress<F> {
+    total: u64,
+    next: u64,
+    report: F,
+}
+
+impl<F: Fn(u64, u64)> Progress<F> {
+    fn new(file: &std::fs::File, on_progress: F) -> Result<Self, Error> {
+        Ok(Self {
+            total: file.metadata()?.len(),
+            next: PROGRESS_STEP,
+            report: on_progress,
+        })
+    }
+
+    fn advance(&mut self, offset: u64) {
+        if offset >= self.next {
+            (self.report)(offset, self.total.max(offset));
+            self.next = offset + PROGRESS_STEP;
+        }
+    }
+
+    /// The file might have grown during the scan.
+    fn finish(self, offset: u64) {
+        (self.report)(offset, self.total.max(offset));
+    }
+}
+
+/// Scans the file from its current position and collects offsets of the line starts.
+/// The file is scanned in chunks of the reader buffer size, so lines of any length
+/// are indexed without being buffered whole.
+///
+/// There is no hard limit on the line length: the memory taken by a line is bounded by
+/// the buffer anyway, and a limit would only drop the offsets of the lines after it.
+fn index_lines(
+    mut file: std::fs::File,
+    delimiter: u8,
+    mut progress: Progress<impl Fn(u64, u64)>,
+) -> Result<Index, Error> {
     let mut offsets = vec![];
 
     let mut offset = file.stream_position()?;
-    let mut buf = String::with_capacity(READ_BUF_CAPACITY);
-    let mut reader = std::io::BufReader::new(&file);
+    let mut reader = std::io::BufReader::with_capacity(READ_BUF_CAPACITY, &file);
+    let mut at_line_start = true;
 
-    // TODO handle very long lines: read in chunks until the hard limit.
-    while let Ok(read_bytes) = reader.read_line(&mut buf) {
-        if read_bytes == 0 {
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
             break; // EOF
         }
 
-        offsets.push(offset);
+        let mut pos = 0;
+        while pos < buf.len() {
+            if at_line_start {
+                offsets.push(offset + pos as u64);
+            }
 
-        if buf.chars().nth(read_bytes - 1) != Some('\n') {
-            // No EOL, we've reached the end of the file.
-            break;
+            if let Some(eol) = buf[pos..].iter().position(|&b| b == delimiter) {
+                pos += eol + 1;
+                at_line_start = true;
+            } else {
+                // The line continues in the next chunk.
+                pos = buf.len();
+                at_line_start = false;
+            }
+        }
+
+        reader.consume(pos);
+        offset += pos as u64;
+        progress.advance(offset);
+    }
+    progress.finish(offset);
+
+    Ok(Index {
+        terminated: !offsets.is_empty() && at_line_start,
+        offsets,
+        end: offset,
+        snapshot: None,
+    })
+}
+
+/// Scans the file from its current position by UTF-16 code units, `eol` ends the lines.
+/// The byte order mark is skipped, a trailing odd byte is not indexed.
+fn index_lines_utf16(
+    mut file: std::fs::File,
+    eol: [u8; 2],
+    mut progress: Progress<impl Fn(u64, u64)>,
+) -> Result<Index, Error> {
+    let mut offsets = vec![];
+
+    let mut offset = file.stream_position()?;
+    let mut reader = std::io::BufReader::with_capacity(READ_BUF_CAPACITY, &file);
+    let mut at_line_start = true;
+
+    if offset == 0 && matches!(reader.fill_buf()?, [0xFF, 0xFE, ..] | [0xFE, 0xFF, ..]) {
+        reader.consume(2);
+        offset = 2;
+    }
+
+    let mut unit = [0; 2];
+    loop {
+        match reader.read_exact(&mut unit) {
+            Ok(()) => {}
+            Err(error) if error.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(error) => return Err(error.into()),
+        }
+
+        if at_line_start {
+            offsets.push(offset);
+        }
+
+        offset += 2;
+        at_line_start = unit == eol;
+        progress.advance(offset);
+    }
+    progress.finish(offset);
+
+    Ok(Index {
+        terminated: !offsets.is_empty() && at_line_start,
+        offsets,
+        end: offset,
+        snapshot: None,
+    })
+}
+
+/// Splits the file into byte chunks, scans them for delimiters in parallel
+/// and merges the found positions into the line offsets.
+/// Every byte belongs to exactly one chunk, so a delimiter at a chunk boundary
+/// is counted once, and a line spanning several chunks gets a single offset.
+fn index_lines_parallel(path: &Path, delimiter: u8) -> Result<Index, Error> {
+    let len = std::fs::metadata(path)?.len();
+
+    let threads = rayon::current_num_threads() as u64;
+    let chunk_len = (len / threads).clamp(MIN_PARALLEL_CHUNK_LEN, MAX_PARALLEL_CHUNK_LEN);
+
+    let chunks = (0..len.div_ceil(chunk_len))
+        .into_par_iter()
+        .map(|chunk| {
+            let start = chunk * chunk_len;
+            scan_delimiters(path, start, chunk_len.min(len - start), delimiter)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let terminated = len > 0 && chunks.last().and_then(|chunk| chunk.last()) == Some(&(len - 1));
+
+    let mut offsets = Vec::with_capacity(chunks.iter().map(Vec::len).sum::<usize>() + 1);
+
+    if len > 0 {
+        offsets.push(0);
+    }
+
+    // A line starts after every delimiter, but the one at the end of the file.
+    offsets.extend(
+        chunks
+            .into_iter()
+            .flatten()
+            .map(|pos| pos + 1)
+            .filter(|&offset| offset < len),
+    );
+
+    Ok(Index {
+        offsets,
+        end: len,
+        terminated,
+        snapshot: None,
+    })
+}
+
+/// Returns positions of the delimiters in `len` bytes of the file starting at `start`.
+fn scan_delimiters(path: &Path, start: u64, len: u64, delimiter: u8) -> Result<Vec<u64>, Error> {
+    use std::io::Read;
+
+    let mut file = std::fs::File::open(path)?;
+    file.seek(SeekFrom::Start(start))?;
+
+    let mut reader = std::io::BufReader::with_capacity(READ_BUF_CAPACITY, file.take(len));
+    let mut positions = vec![];
+    let mut offset = start;
+
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            break; // End of the chunk
         }
-        buf.clear();
 
-        offset += read_bytes as u64;
+        positions.extend(
+            buf.iter()
+                .enumerate()
+                .filter(|(_, &b)| b == delimiter)
+                .map(|(pos, _)| offset + pos as u64),
+        );
 
-        assert_eq!(reader.stream_position()?, offset);
+        let read = buf.len();
+        reader.consume(read);
+        offset += read as u64;
     }
 
-    Ok(offsets)
+    Ok(positions)
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
+    #[error("UTF-8 error: {0}")]
+    Utf8Error(#[from] std::str::Utf8Error),
+    #[error("Invalid {0:?} text")]
+    DecodeError(Encoding),
     #[error("Inconsistent index at line {0}")]
     InconsistentIndex(usize),
 }
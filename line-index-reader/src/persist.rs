@@ -0,0 +1,198 @@
+use std::{
+    path::Path,
+    sync::RwLock,
+    time::{Duration, SystemTime},
+};
+
+use crate::{Encoding, Error, Fingerprint, Index, LineIndexReader, DEFAULT_MAX_READ_BYTES};
+
+const MAGIC: &[u8; 4] = b"LQIX";
+const VERSION: u8 = 1;
+
+/// Index persistence
+///
+/// The index file layout, all integers are little-endian:
+/// ```text
+/// [magic: 4][version: u8][delimiter: u8]
+/// [file len: u64][mtime secs: u64][mtime nanos: u32]
+/// [indexed end: u64][terminated: u8][offsets count: u64][offset deltas: LEB128 varints]
+/// ```
+/// Offsets are stored as deltas from the previous offset, i.e. line lengths,
+/// which mostly fit into one or two bytes.
+impl LineIndexReader {
+    /// Saves the offsets along with the source file length and modification time.
+    pub async fn save_index<P>(&self, dest: P) -> Result<(), Error>
+    where
+        P: AsRef<Path> + Send,
+    {
+        let metadata = tokio::fs::metadata(&self.path).await?;
+
+        let header = Header {
+            delimiter: self.delimiter,
+            file_len: metadata.len(),
+            modified: metadata.modified()?,
+        };
+
+        let buf = encode(&header, &self.index.read().unwrap());
+
+        tokio::fs::write(dest, buf).await.map_err(Into::into)
+    }
+
+    /// Loads the index saved by [`LineIndexReader::save_index`].
+    /// Falls back to a full index if the saved index is missing, malformed
+    /// or does not match the current file length and modification time.
+    pub async fn load_index<P, I>(path: P, index_path: I) -> Result<Self, Error>
+    where
+        P: AsRef<Path> + Clone + Send,
+        I: AsRef<Path> + Send + Sync,
+    {
+        let saved = tokio::fs::read(&index_path)
+            .await
+            .ok()
+            .and_then(|buf| decode(&buf));
+
+        let Some((header, index)) = saved else {
+            tracing::debug!(
+                "No valid saved index {}, re-indexing",
+                index_path.as_ref().display()
+            );
+            return Self::index(path).await;
+        };
+
+        let metadata = tokio::fs::metadata(path.as_ref()).await?;
+
+        if metadata.len() != header.file_len || metadata.modified()? != header.modified {
+            tracing::debug!(
+                "Saved index {} is stale, re-indexing",
+                index_path.as_ref().display()
+            );
+            return Self::index_with_delimiter(path, header.delimiter).await;
+        }
+
+        let fingerprint = Fingerprint::read(path.as_ref()).await?;
+
+        Ok(Self {
+            path: path.as_ref().to_owned(),
+            delimiter: header.delimiter,
+            encoding: RwLock::new(Encoding::detect(&fingerprint.head)),
+            index: RwLock::new(index),
+            fingerprint: RwLock::new(fingerprint),
+            mmap: RwLock::default(),
+            handle: RwLock::default(),
+            max_read_bytes: DEFAULT_MAX_READ_BYTES,
+            spool: None,
+        })
+    }
+}
+
+struct Header {
+    delimiter: u8,
+    file_len: u64,
+    modified: SystemTime,
+}
+
+fn encode(header: &Header, index: &Index) -> Vec<u8> {
+    let modified = header
+        .modified
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+
+    let offsets = &index.offsets;
+
+    let mut buf = Vec::with_capacity(43 + offsets.len() * 2);
+    buf.extend(MAGIC);
+    buf.push(VERSION);
+    buf.push(header.delimiter);
+    buf.extend(header.file_len.to_le_bytes());
+    buf.extend(modified.as_secs().to_le_bytes());
+    buf.extend(modified.subsec_nanos().to_le_bytes());
+    buf.extend(index.end.to_le_bytes());
+    buf.push(u8::from(index.terminated));
+    buf.extend((offsets.len() as u64).to_le_bytes());
+
+    let mut prev = 0;
+    for &offset in offsets {
+        write_varint(&mut buf, offset - prev);
+        prev = offset;
+    }
+
+    buf
+}
+
+fn decode(buf: &[u8]) -> Option<(Header, Index)> {
+    let mut cursor = buf.strip_prefix(MAGIC)?;
+
+    let [version, delimiter, rest @ ..] = cursor else {
+        return None;
+    };
+    if *version != VERSION {
+        return None;
+    }
+    cursor = rest;
+
+    let file_len = read_u64(&mut cursor)?;
+    let secs = read_u64(&mut cursor)?;
+    let nanos = u32::from_le_bytes(take(&mut cursor)?);
+    let end = read_u64(&mut cursor)?;
+    let [terminated] = take(&mut cursor)?;
+    let count = usize::try_from(read_u64(&mut cursor)?).ok()?;
+
+    let mut offsets = Vec::with_capacity(count.min(cursor.len()));
+    let mut offset = 0u64;
+    for _ in 0..count {
+        offset = offset.checked_add(read_varint(&mut cursor)?)?;
+        offsets.push(offset);
+    }
+
+    if !cursor.is_empty() {
+        return None;
+    }
+
+    let header = Header {
+        delimiter: *delimiter,
+        file_len,
+        modified: SystemTime::UNIX_EPOCH.checked_add(Duration::new(secs, nanos))?,
+    };
+
+    let index = Index {
+        offsets,
+        end,
+        terminated: terminated != 0,
+        snapshot: None,
+    };
+
+    Some((header, index))
+}
+
+fn take<const N: usize>(cursor: &mut &[u8]) -> Option<[u8; N]> {
+    let (head, rest) = cursor.split_first_chunk::<N>()?;
+    *cursor = rest;
+    Some(*head)
+}
+
+fn read_u64(cursor: &mut &[u8]) -> Option<u64> {
+    take(cursor).map(u64::from_le_bytes)
+}
+
+#[allow(clippy::cast_possible_truncation)] // Only the lowest 7 bits are taken.
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8 & 0x7F) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn read_varint(cursor: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+
+    for shift in (0..64).step_by(7) {
+        let [byte] = take(cursor)?;
+        value |= u64::from(byte & 0x7F) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+
+    None
+}
@@ -1,8 +1,14 @@
-use std::{io::Write, ops::RangeBounds};
+use std::{
+    io::{Seek, SeekFrom, Write},
+    ops::{Bound, Range, RangeBounds},
+};
+
+use futures::StreamExt;
+use tokio::io::AsyncWriteExt;
 
 use tempfile::NamedTempFile;
 
-use line_index_reader::LineIndexReader;
+use line_index_reader::{Encoding, LineIndexReader, SearchOptions};
 
 #[rstest::rstest]
 #[case::empty(empty(), 0)]
@@ -18,6 +24,81 @@ pub async fn index(#[case] file: NamedTempFile, #[case] expected_len: u32) {
     assert_eq!(index.len(), expected_len);
 }
 
+#[tokio::test]
+pub async fn index_very_long_line() {
+    const LONG_LINE_LEN: usize = 32 * 1024 * 1024;
+
+    let mut file = NamedTempFile::new().unwrap();
+    writeln!(file, "Line 000000").unwrap();
+    file.write_all(&vec![b'x'; LONG_LINE_LEN]).unwrap();
+    writeln!(file).unwrap();
+    writeln!(file, "Line 000002").unwrap();
+    write!(file, "Line 000003").unwrap();
+    file.flush().unwrap();
+
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+    assert_eq!(4, index.len());
+
+    assert_eq!(Some("Line 000000"), index.line(0).await.as_deref());
+    assert_eq!(
+        Some(LONG_LINE_LEN),
+        index.line(1).await.map(|line| line.len())
+    );
+    assert_eq!(Some("Line 000002"), index.line(2).await.as_deref());
+    assert_eq!(Some("Line 000003"), index.line(3).await.as_deref());
+}
+
+#[tokio::test]
+pub async fn index_with_progress() {
+    const LINES: u32 = 1_000_000;
+
+    let mut file = NamedTempFile::new().unwrap();
+    let mut content = vec![];
+    for i in 0..LINES {
+        writeln!(content, "Line {i:06}").unwrap();
+    }
+    file.write_all(&content).unwrap();
+    file.flush().unwrap();
+    let len = content.len() as u64;
+
+    let reports = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+    let index = LineIndexReader::index_with_progress(&file, {
+        let reports = reports.clone();
+        move |read, total| reports.lock().unwrap().push((read, total))
+    })
+    .await
+    .expect("LineIndex");
+    assert_eq!(index.len(), LINES);
+
+    let reports = reports.lock().unwrap();
+    assert!(reports.iter().all(|&(_, total)| total == len));
+    assert!(reports.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    assert_eq!(reports.last(), Some(&(len, len)));
+    // Throttled to one report per few MiB, not per buffer read.
+    assert!((2..=5).contains(&reports.len()), "{}", reports.len());
+}
+
+#[rstest::rstest]
+#[case::empty(empty())]
+#[case::one_line_with_eof(one_line_eol())]
+#[case::one_line_no_eof(one_line())]
+#[case::small_no_eof(small_file())]
+#[case::small_with_eof(small_file_eol())]
+#[case::large(large_with_eof())]
+#[tokio::test]
+pub async fn index_parallel(#[case] file: NamedTempFile) {
+    let sequential = LineIndexReader::index(&file).await.expect("LineIndex");
+    let parallel = LineIndexReader::index_parallel(&file)
+        .await
+        .expect("LineIndex");
+
+    assert_eq!(sequential.len(), parallel.len());
+
+    for line in 0..=sequential.len() {
+        assert_eq!(sequential.offset_of(line), parallel.offset_of(line));
+    }
+}
+
 #[rstest::rstest]
 #[case::first(0, "Line 000000".into())]
 #[case::middle(SMALL_FILE_LINES / 2, "Line 004782".into())]
@@ -56,6 +137,358 @@ where
     );
 }
 
+#[tokio::test]
+pub async fn crlf_line_endings() {
+    let mut file = NamedTempFile::new().unwrap();
+    file.write_all(b"Line 0\r\nLine 1\nLine 2\r\n\r\nLine 4\r")
+        .unwrap();
+    file.flush().unwrap();
+
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+    assert_eq!(5, index.len());
+
+    assert_eq!(
+        ["Line 0", "Line 1", "Line 2", "", "Line 4"].as_slice(),
+        index
+            .lines(..)
+            .await
+            .iter()
+            .map(AsRef::as_ref)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(Some("Line 2"), index.line(2).await.as_deref());
+
+    let re = regex::Regex::new(r"^Line \d$").unwrap();
+    assert_eq!(vec![0, 1, 2, 4], index.find_all_regex(&re, ..).await);
+
+    assert!(index
+        .consistency()
+        .await
+        .expect("Index consistency")
+        .is_consistent());
+}
+
+#[tokio::test]
+pub async fn null_delimiter() {
+    let mut file = NamedTempFile::new().unwrap();
+    file.write_all(b"Record 0\0Record\n1\0\0Record 3").unwrap();
+    file.flush().unwrap();
+
+    let index = LineIndexReader::index_with_delimiter(&file, b'\0')
+        .await
+        .expect("LineIndex");
+    assert_eq!(4, index.len());
+
+    assert_eq!(
+        ["Record 0", "Record\n1", "", "Record 3"].as_slice(),
+        index
+            .lines(..)
+            .await
+            .iter()
+            .map(AsRef::as_ref)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(Some("Record\n1"), index.line(1).await.as_deref());
+
+    let re = regex::Regex::new(r"^Record \d$").unwrap();
+    assert_eq!(vec![0, 3], index.find_all_regex(&re, ..).await);
+
+    assert!(index
+        .consistency()
+        .await
+        .expect("Index consistency")
+        .is_consistent());
+
+    file.write_all(b"\0Record 4\0").unwrap();
+    file.flush().unwrap();
+
+    assert_eq!(1, index.update().await.expect("Updated index"));
+    assert_eq!(Some("Record 4"), index.line(4).await.as_deref());
+}
+
+#[test]
+pub fn index_blocking() {
+    let file = small_file();
+    let index = LineIndexReader::index_blocking(&file).expect("LineIndex");
+    assert_eq!(SMALL_FILE_LINES, index.len());
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        assert_eq!(Some("Line 000000"), index.line(0).await.as_deref());
+        assert_eq!(10, index.lines(SMALL_FILE_LINES - 10..).await.len());
+    });
+}
+
+#[rstest::rstest]
+#[case::first(0, Some(0))]
+#[case::second(1, Some(12))]
+#[case::last(SMALL_FILE_LINES - 1, Some(12 * u64::from(SMALL_FILE_LINES - 1)))]
+#[case::eof(SMALL_FILE_LINES, None)]
+#[tokio::test]
+pub async fn offset_of(#[case] line: u32, #[case] expected: Option<u64>) {
+    let file = small_file();
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+
+    assert_eq!(expected, index.offset_of(line));
+}
+
+#[rstest::rstest]
+#[case::start(0, Some(0))]
+#[case::mid_first_line(5, Some(0))]
+#[case::first_eol(11, Some(0))]
+#[case::second_line(12, Some(1))]
+#[case::mid_line(12 * 4_782 + 7, Some(4_782))]
+#[case::last_line(12 * u64::from(SMALL_FILE_LINES - 1), Some(SMALL_FILE_LINES - 1))]
+#[case::last_byte(12 * u64::from(SMALL_FILE_LINES) - 2, Some(SMALL_FILE_LINES - 1))]
+#[case::eof(12 * u64::from(SMALL_FILE_LINES) - 1, None)]
+#[case::beyond_eof(u64::MAX, None)]
+#[tokio::test]
+pub async fn line_at_offset(#[case] byte: u64, #[case] expected: Option<u32>) {
+    let file = small_file();
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+
+    assert_eq!(expected, index.line_at_offset(byte));
+}
+
+#[rstest::rstest]
+#[case::whole_lines(0, 24, 0..2)]
+#[case::start_mid_line(5, 24, 0..2)]
+#[case::end_mid_line(12, 30, 1..3)]
+#[case::empty(10, 10, 0..0)]
+#[case::to_eof(12 * u64::from(SMALL_FILE_LINES - 1), u64::MAX, SMALL_FILE_LINES - 1..SMALL_FILE_LINES)]
+#[case::beyond_eof(u64::MAX - 1, u64::MAX, 0..0)]
+#[tokio::test]
+pub async fn lines_by_byte_range(
+    #[case] start_byte: u64,
+    #[case] end_byte: u64,
+    #[case] expected: Range<u32>,
+) {
+    let file = small_file();
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+
+    let expected = expected
+        .map(|i| format!("Line {i:06}").into())
+        .collect::<Vec<Box<str>>>();
+
+    assert_eq!(
+        expected,
+        index
+            .lines_by_byte_range(start_byte, end_byte)
+            .await
+            .into_vec()
+    );
+}
+
+#[tokio::test]
+pub async fn try_lines_on_removed_file() {
+    let file = temp_file(10);
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+
+    assert_eq!(10, index.try_lines(..).await.expect("Lines").len());
+    assert!(index.try_lines(10..).await.expect("Empty range").is_empty());
+
+    file.close().unwrap();
+
+    assert!(matches!(
+        index.try_lines(..).await,
+        Err(line_index_reader::Error::IoError(_))
+    ));
+    assert!(index.lines(..).await.is_empty());
+}
+
+#[rstest::rstest]
+#[case::all(.., LARGE_FILE_LINES)]
+#[case::beginning(..10, 10)]
+#[case::middle(1_000..=1_999, 1_000)]
+#[case::end(LARGE_FILE_LINES - 10.., 10)]
+#[case::beyond_eof(LARGE_FILE_LINES.., 0)]
+#[case::huge_end(LARGE_FILE_LINES - 10..u32::MAX, 10)]
+#[tokio::test]
+pub async fn stream<R>(#[case] lines: R, #[case] expected_len: u32)
+where
+    R: RangeBounds<u32> + Clone + Send,
+{
+    let file = large_with_eof();
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+
+    let first = match lines.start_bound() {
+        Bound::Included(&x) => x,
+        _ => 0,
+    };
+
+    let mut stream = std::pin::pin!(index.stream(lines));
+    let mut count = 0;
+
+    while let Some(line) = stream.next().await {
+        let line = line.expect("Line");
+        assert_eq!(format!("Line {:06}", first + count), line.as_ref());
+        count += 1;
+    }
+
+    assert_eq!(expected_len, count);
+}
+
+#[tokio::test]
+pub async fn mmap() {
+    let mut file = temp_file(10);
+
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+    let mapped = LineIndexReader::index_mmap(&file).await.expect("LineIndex");
+
+    assert_eq!(index.len(), mapped.len());
+    assert_eq!(index.lines(..).await, mapped.lines(..).await);
+    assert_eq!(index.lines(3..7).await, mapped.lines(3..7).await);
+    assert_eq!(Some("Line 000009"), mapped.line(9).await.as_deref());
+    assert!(mapped.line(10).await.is_none());
+
+    for i in 10..15 {
+        writeln!(file, "Line {i:06}").unwrap();
+    }
+    file.flush().unwrap();
+
+    assert_eq!(5, mapped.update().await.expect("Updated index"));
+    assert_eq!(Some("Line 000014"), mapped.line(14).await.as_deref());
+    assert_eq!(15, mapped.lines(..).await.len());
+}
+
+#[rstest::rstest]
+#[case::empty(empty(), 0)]
+#[case::one_line_with_eof(one_line_eol(), 12)]
+#[case::one_line_no_eof(one_line(), 11)]
+#[case::small_no_eof(small_file(), 12 * u64::from(SMALL_FILE_LINES) - 1)]
+#[case::small_with_eof(small_file_eol(), 12 * u64::from(SMALL_FILE_LINES))]
+#[tokio::test]
+pub async fn byte_len(#[case] file: NamedTempFile, #[case] expected: u64) {
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+    let parallel = LineIndexReader::index_parallel(&file)
+        .await
+        .expect("LineIndex");
+
+    assert_eq!(expected, index.byte_len());
+    assert_eq!(expected, parallel.byte_len());
+}
+
+#[rstest::rstest]
+#[case::empty(empty(), false)]
+#[case::one(one_line(), false)]
+#[case::one_eol(one_line_eol(), true)]
+#[case::small(small_file(), false)]
+#[case::small_eol(small_file_eol(), true)]
+#[tokio::test]
+pub async fn ends_with_newline(#[case] file: NamedTempFile, #[case] expected: bool) {
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+    assert_eq!(expected, index.ends_with_newline());
+}
+
+#[tokio::test]
+pub async fn ends_with_newline_after_update() {
+    let mut file = temp_file(0);
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+
+    write!(file, "Partial").unwrap();
+    file.flush().unwrap();
+    index.update().await.expect("Updated index");
+    assert!(!index.ends_with_newline());
+
+    writeln!(file).unwrap();
+    file.flush().unwrap();
+    index.update().await.expect("Updated index");
+    assert!(index.ends_with_newline());
+}
+
+#[tokio::test]
+pub async fn line_len() {
+    let mut file = NamedTempFile::new().unwrap();
+    write!(file, "Line 0\n\nLong line 2\r\nLast").unwrap();
+    file.flush().unwrap();
+
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+
+    assert_eq!(Some(6), index.line_len(0));
+    assert_eq!(Some(0), index.line_len(1));
+    assert_eq!(Some(12), index.line_len(2));
+    assert_eq!(Some(4), index.line_len(3));
+    assert_eq!(None, index.line_len(4));
+
+    writeln!(file).unwrap();
+    file.flush().unwrap();
+    index.update().await.expect("Updated index");
+
+    assert_eq!(Some(4), index.line_len(3));
+}
+
+#[rstest::rstest]
+#[case::none(0, 0)]
+#[case::one(1, 1)]
+#[case::some(10, 10)]
+#[case::all(SMALL_FILE_LINES, SMALL_FILE_LINES)]
+#[case::more_than_all(SMALL_FILE_LINES + 10, SMALL_FILE_LINES)]
+#[tokio::test]
+pub async fn tail(#[case] n: u32, #[case] expected_len: u32) {
+    let file = small_file();
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+
+    let lines = index.tail(n).await;
+
+    assert_eq!(expected_len as usize, lines.len());
+    assert_eq!(
+        lines.last().map(AsRef::as_ref),
+        (n > 0)
+            .then(|| format!("Line {:06}", SMALL_FILE_LINES - 1))
+            .as_deref()
+    );
+    assert_eq!(
+        lines.first().map(AsRef::as_ref),
+        (n > 0)
+            .then(|| format!("Line {:06}", SMALL_FILE_LINES - expected_len))
+            .as_deref()
+    );
+}
+
+#[tokio::test]
+pub async fn lines_rev() {
+    let file = temp_file(10);
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+
+    assert_eq!(
+        ["Line 000006", "Line 000005", "Line 000004"].as_slice(),
+        index
+            .lines_rev(4..7)
+            .await
+            .iter()
+            .map(AsRef::as_ref)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        Some("Line 000009"),
+        index.lines_rev(..).await.first().map(AsRef::as_ref)
+    );
+}
+
+#[tokio::test]
+pub async fn lines_lossy() {
+    let mut file = NamedTempFile::new().unwrap();
+    file.write_all(b"Line 0\nLine \xFF1\nLine 2\n").unwrap();
+    file.flush().unwrap();
+
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+    assert_eq!(3, index.len());
+
+    assert!(index.lines(..).await.is_empty());
+    assert_eq!(
+        ["Line 0", "Line \u{FFFD}1", "Line 2"].as_slice(),
+        index
+            .lines_lossy(..)
+            .await
+            .iter()
+            .map(AsRef::as_ref)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(Some("Line 2"), index.line(2).await.as_deref());
+}
+
 #[rstest::rstest]
 #[case::no_lines(0)]
 #[case::one_line(1)]
@@ -78,6 +511,151 @@ pub async fn update(#[case] new_lines: u32) {
     assert_eq!(1 + new_lines, index.len());
 }
 
+#[tokio::test]
+pub async fn update_empty() {
+    let mut file = NamedTempFile::new().unwrap();
+
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+    assert_eq!(0, index.update().await.expect("Updated index"));
+
+    writeln!(file, "Line 000000").unwrap();
+    file.flush().unwrap();
+
+    assert_eq!(1, index.update().await.expect("Updated index"));
+    assert_eq!(Some("Line 000000"), index.line(0).await.as_deref());
+}
+
+#[tokio::test]
+pub async fn update_partial_line() {
+    let mut file = NamedTempFile::new().unwrap();
+    write!(file, "First\nGhost").unwrap();
+    file.flush().unwrap();
+
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+    assert_eq!(2, index.len());
+    assert_eq!(Some("Ghost"), index.line(1).await.as_deref());
+
+    // The partial line is completed and more lines follow.
+    write!(file, " line\nMore\n").unwrap();
+    file.flush().unwrap();
+
+    assert_eq!(1, index.update().await.expect("Updated index"));
+    assert_eq!(
+        ["First", "Ghost line", "More"].as_slice(),
+        index
+            .lines(..)
+            .await
+            .iter()
+            .map(AsRef::as_ref)
+            .collect::<Vec<_>>()
+    );
+
+    write!(file, "Last").unwrap();
+    file.flush().unwrap();
+
+    assert_eq!(1, index.update().await.expect("Updated index"));
+    assert_eq!(4, index.len());
+    assert_eq!(Some("Last"), index.line(3).await.as_deref());
+    assert!(index
+        .consistency()
+        .await
+        .expect("Index consistency")
+        .is_consistent());
+}
+
+#[tokio::test]
+pub async fn update_replaced() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("app.log");
+
+    let old = temp_file(10);
+    std::fs::copy(&old, &path).unwrap();
+
+    let index = LineIndexReader::index(&path).await.expect("LineIndex");
+    assert_eq!(10, index.len());
+    // Keeps the old file open.
+    assert!(index.line(0).await.is_some());
+
+    // Rotate: a new, shorter file with different content takes the name.
+    let mut new = NamedTempFile::new_in(&dir).unwrap();
+    write!(new, "New 0\nNew 1\nNew 2").unwrap();
+    new.flush().unwrap();
+    new.persist(&path).unwrap();
+
+    assert_eq!(3, index.update().await.expect("Updated index"));
+    assert_eq!(3, index.len());
+    assert_eq!(Some("New 0"), index.line(0).await.as_deref());
+    assert_eq!(Some("New 2"), index.line(2).await.as_deref());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+pub async fn concurrent_reads() {
+    let file = small_file();
+    let index = std::sync::Arc::new(LineIndexReader::index(&file).await.expect("LineIndex"));
+
+    // The ranges overlap, the reads share the file handle.
+    let reads = (0..64u32).map(|task| {
+        let index = index.clone();
+        tokio::spawn(async move {
+            let start = task * 97;
+            for _ in 0..10 {
+                let lines = index.lines(start..start + 1_000).await;
+                assert_eq!(1_000, lines.len());
+                for (i, line) in (start..).zip(lines.iter()) {
+                    assert_eq!(format!("Line {i:06}"), line.as_ref());
+                }
+            }
+        })
+    });
+
+    for read in futures::future::join_all(reads).await {
+        read.unwrap();
+    }
+}
+
+#[tokio::test]
+pub async fn update_truncated() {
+    let mut file = temp_file(10);
+
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+    assert_eq!(10, index.len());
+
+    // Copy-truncate rotation keeps the same file.
+    file.as_file_mut().set_len(0).unwrap();
+    file.as_file_mut().seek(SeekFrom::Start(0)).unwrap();
+    writeln!(file, "New 0").unwrap();
+    writeln!(file, "New 1").unwrap();
+    file.flush().unwrap();
+
+    assert_eq!(2, index.update().await.expect("Updated index"));
+    assert_eq!(Some("New 1"), index.line(1).await.as_deref());
+
+    // Truncated to the same prefix.
+    file.as_file_mut().set_len(6).unwrap();
+
+    assert_eq!(1, index.update().await.expect("Updated index"));
+    assert_eq!(Some("New 0"), index.line(0).await.as_deref());
+}
+
+#[tokio::test]
+pub async fn update_truncated_to_last_line() {
+    // Longer than the fingerprint, the head stays the same.
+    let file = temp_file(200);
+
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+    assert_eq!(200, index.len());
+
+    // Cut right at the start of the last line.
+    file.as_file().set_len(199 * 12).unwrap();
+
+    assert_eq!(199, index.update().await.expect("Updated index"));
+    assert_eq!(199, index.len());
+    assert_eq!(199 * 12, index.byte_len());
+    assert!(index.ends_with_newline());
+    assert_eq!(Some("Line 000198"), index.line(198).await.as_deref());
+    assert_eq!(None, index.line(199).await);
+}
+
 #[rstest::rstest]
 #[case::empty(empty())]
 #[case::one(one_line())]
@@ -113,6 +691,88 @@ pub async fn consistency_on_truncated() {
     );
 }
 
+#[rstest::rstest]
+#[case::before(5, Some(8))]
+#[case::at(8, Some(8))]
+#[case::after(9, None)]
+#[tokio::test]
+pub async fn consistency_from(#[case] start: u32, #[case] expected: Option<usize>) {
+    let mut file = temp_file(10);
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+
+    // Overwrite the delimiter before the line 8.
+    file.seek(SeekFrom::Start(12 * 8 - 1)).unwrap();
+    file.write_all(b" ").unwrap();
+    file.flush().unwrap();
+
+    assert_eq!(
+        expected,
+        index
+            .consistency_from(start)
+            .await
+            .expect("Index consistency")
+            .into_inconsistent()
+            .ok()
+    );
+}
+
+#[rstest::rstest]
+#[case::intact(None, None)]
+#[case::second(Some(1), Some(1))]
+#[case::last(Some(SMALL_FILE_LINES - 1), Some(SMALL_FILE_LINES as usize - 1))]
+#[tokio::test]
+pub async fn consistency_sampled(#[case] corrupted: Option<u32>, #[case] expected: Option<usize>) {
+    let mut file = temp_file(SMALL_FILE_LINES);
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+
+    if let Some(line) = corrupted {
+        // Overwrite the delimiter before the line.
+        file.seek(SeekFrom::Start(12 * u64::from(line) - 1))
+            .unwrap();
+        file.write_all(b" ").unwrap();
+        file.flush().unwrap();
+    }
+
+    assert_eq!(
+        expected,
+        index
+            .consistency_sampled(100, 42)
+            .await
+            .expect("Index consistency")
+            .into_inconsistent()
+            .ok()
+    );
+}
+
+#[tokio::test]
+pub async fn consistency_sampled_is_reproducible() {
+    let mut file = temp_file(SMALL_FILE_LINES);
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+
+    // Every other line is broken, the first sampled one is reported.
+    for line in (2..SMALL_FILE_LINES).step_by(2) {
+        file.seek(SeekFrom::Start(12 * u64::from(line) - 1))
+            .unwrap();
+        file.write_all(b" ").unwrap();
+    }
+    file.flush().unwrap();
+
+    let first = index
+        .consistency_sampled(10, 7)
+        .await
+        .expect("Index consistency");
+    assert!(first.is_inconsistent());
+    for _ in 0..3 {
+        assert_eq!(
+            first,
+            index
+                .consistency_sampled(10, 7)
+                .await
+                .expect("Index consistency")
+        );
+    }
+}
+
 #[tokio::test]
 pub async fn consistency_on_appended() {
     let mut file = temp_file(10);
@@ -131,6 +791,407 @@ pub async fn consistency_on_appended() {
         .is_consistent());
 }
 
+#[rstest::rstest]
+#[case::all(.., vec![0, 1_000, 2_000, 3_000, 4_000, 5_000, 6_000, 7_000, 8_000, 9_000])]
+#[case::bounded(1_000..=3_000, vec![1_000, 2_000, 3_000])]
+#[case::exclusive_end(1_000..3_000, vec![1_000, 2_000])]
+#[case::unbounded_end(9_000.., vec![9_000])]
+#[case::beyond_eof(SMALL_FILE_LINES.., vec![])]
+#[case::huge_end(9_000..u32::MAX, vec![9_000])]
+#[tokio::test]
+pub async fn find_all_regex<R>(#[case] lines: R, #[case] expected: Vec<u32>)
+where
+    R: RangeBounds<u32> + Send,
+{
+    let file = small_file();
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+    let re = regex::Regex::new(r"^Line \d{3}000$").unwrap();
+
+    assert_eq!(expected, index.find_all_regex(&re, lines).await);
+}
+
+#[tokio::test]
+pub async fn find_all_regex_skips_invalid_utf8() {
+    let mut file = NamedTempFile::new().unwrap();
+    file.write_all(b"match 0\nmatch \xFF\nno match\nmatch 3")
+        .unwrap();
+    file.flush().unwrap();
+
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+    let re = regex::Regex::new(r"^match \S+$").unwrap();
+
+    assert_eq!(vec![0, 3], index.find_all_regex(&re, ..).await);
+}
+
+#[rstest::rstest]
+#[case::all(.., Some(1_000))]
+#[case::inside(1_500.., Some(1_500))]
+#[case::after(2_000.., None)]
+#[case::before(..1_000, None)]
+#[tokio::test]
+pub async fn find<R>(#[case] lines: R, #[case] expected: Option<u32>)
+where
+    R: RangeBounds<u32> + Send,
+{
+    let file = small_file();
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+
+    assert_eq!(
+        expected,
+        index
+            .find("Line 001", SearchOptions::default(), lines)
+            .await
+    );
+}
+
+#[rstest::rstest]
+#[case::all(.., Some(1_999))]
+#[case::inside(..1_500, Some(1_499))]
+#[case::after(2_000.., None)]
+#[case::before(..1_000, None)]
+#[case::chunks_away(1_500..9_000, Some(1_999))]
+#[tokio::test]
+pub async fn rfind<R>(#[case] lines: R, #[case] expected: Option<u32>)
+where
+    R: RangeBounds<u32> + Send,
+{
+    let file = small_file();
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+
+    assert_eq!(
+        expected,
+        index
+            .rfind("Line 001", SearchOptions::default(), lines)
+            .await
+    );
+}
+
+#[rstest::rstest]
+#[case::all(.., 1_000..2_000)]
+#[case::inside(1_500..1_600, 1_500..1_600)]
+#[case::overlap(1_900..2_100, 1_900..2_000)]
+#[case::outside(2_000.., 0..0)]
+#[tokio::test]
+pub async fn find_all<R>(#[case] lines: R, #[case] expected: Range<u32>)
+where
+    R: RangeBounds<u32> + Send,
+{
+    let file = small_file();
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+
+    let expected = expected
+        .map(|i| (i, format!("Line {i:06}").into()))
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        expected,
+        index
+            .find_all("Line 001", SearchOptions::default(), lines)
+            .await
+    );
+}
+
+#[rstest::rstest]
+#[case::case_sensitive("Σίσυφος", SearchOptions::default(), vec![0])]
+#[case::case_insensitive("σίσυφος", SearchOptions { case_insensitive: true, whole_word: false }, vec![0, 1])]
+#[case::simple_case_folding("straße", SearchOptions { case_insensitive: true, whole_word: false }, vec![2])]
+#[case::substring("push", SearchOptions::default(), vec![0, 4])]
+#[case::whole_word("push", SearchOptions { case_insensitive: false, whole_word: true }, vec![])]
+#[case::whole_word_match("pushed", SearchOptions { case_insensitive: false, whole_word: true }, vec![0])]
+#[case::both("PUSHED", SearchOptions { case_insensitive: true, whole_word: true }, vec![0, 1])]
+#[case::punctuation("(pushing)", SearchOptions { case_insensitive: false, whole_word: true }, vec![4])]
+#[tokio::test]
+pub async fn find_with_options(
+    #[case] needle: &str,
+    #[case] options: SearchOptions,
+    #[case] expected: Vec<u32>,
+) {
+    let mut file = NamedTempFile::new().unwrap();
+    file.write_all("Σίσυφος pushed\nΣΊΣΥΦΟΣ PUSHED\nstraße\nSTRASSE\nkeeps (pushing)".as_bytes())
+        .unwrap();
+    file.flush().unwrap();
+
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+
+    let found = index
+        .find_all(needle, options, ..)
+        .await
+        .into_iter()
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+
+    assert_eq!(expected, found);
+}
+
+#[rstest::rstest]
+#[case::fits(0..5, 60, 0..5)]
+#[case::unbounded(.., 120, 0..10)]
+#[case::partial_line_left_out(5.., 125, 5..15)]
+#[case::bounded(100..200, 60, 100..105)]
+#[case::first_line_only(.., 5, 0..1)]
+#[case::first_line_only_bounded(3..6, 4, 3..4)]
+#[tokio::test]
+pub async fn max_read_bytes<R>(#[case] lines: R, #[case] max: u64, #[case] expected: Range<u32>)
+where
+    R: RangeBounds<u32> + Send,
+{
+    let file = small_file();
+    let index = LineIndexReader::index(&file)
+        .await
+        .expect("LineIndex")
+        .with_max_read_bytes(max);
+
+    let expected = expected
+        .map(|i| format!("Line {i:06}").into())
+        .collect::<Vec<Box<str>>>();
+
+    assert_eq!(expected, index.lines(lines).await.into_vec());
+}
+
+#[rstest::rstest]
+#[case::exclusive(LARGE_FILE_LINES - 10..u32::MAX)]
+#[case::inclusive(LARGE_FILE_LINES - 10..=u32::MAX)]
+#[tokio::test]
+pub async fn lines_huge_end<R>(#[case] lines: R)
+where
+    R: RangeBounds<u32> + Clone + Send,
+{
+    let file = large_with_eof();
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+
+    let expected = (LARGE_FILE_LINES - 10..LARGE_FILE_LINES)
+        .map(|i| format!("Line {i:06}").into())
+        .collect::<Vec<Box<str>>>();
+    assert_eq!(expected, index.lines(lines.clone()).await.into_vec());
+
+    // The read is bounded by the lines in the index, not by the end given.
+    let index = index.with_max_read_bytes(12 * 4);
+    assert_eq!(expected[..4], index.lines(lines).await.into_vec());
+
+    // A reversed range has no lines.
+    let (start, end) = (LARGE_FILE_LINES - 5, LARGE_FILE_LINES - 10);
+    assert!(index.lines(start..end).await.is_empty());
+    assert!(index.lines(start..start).await.is_empty());
+}
+
+#[tokio::test]
+pub async fn lines_not_indexed_yet() {
+    let mut file = temp_file(10);
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+
+    writeln!(file, "Line {:06}", 10).unwrap();
+    file.flush().unwrap();
+
+    assert_eq!(2, index.lines(8..u32::MAX).await.len());
+
+    index.update().await.expect("Updated index");
+    assert_eq!(3, index.lines(8..u32::MAX).await.len());
+}
+
+#[tokio::test]
+pub async fn save_and_load_index() {
+    let file = temp_file(10);
+    let index_file = NamedTempFile::new().unwrap();
+
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+    index.save_index(&index_file).await.expect("Saved index");
+
+    let loaded = LineIndexReader::load_index(&file, &index_file)
+        .await
+        .expect("Loaded index");
+
+    assert_eq!(index.len(), loaded.len());
+    assert_eq!(index.lines(..).await, loaded.lines(..).await);
+}
+
+#[tokio::test]
+pub async fn load_index_uses_saved_offsets() {
+    let mut file = temp_file(10);
+    let index_file = NamedTempFile::new().unwrap();
+
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+    index.save_index(&index_file).await.expect("Saved index");
+
+    // Same length and mtime, but one line less: a full index would notice that.
+    let modified = file.as_file().metadata().unwrap().modified().unwrap();
+    file.as_file_mut().seek(SeekFrom::Start(10)).unwrap();
+    file.write_all(b" ").unwrap();
+    file.flush().unwrap();
+    file.as_file().set_modified(modified).unwrap();
+
+    let loaded = LineIndexReader::load_index(&file, &index_file)
+        .await
+        .expect("Loaded index");
+
+    assert_eq!(10, loaded.len());
+}
+
+#[tokio::test]
+pub async fn load_stale_index() {
+    let mut file = temp_file(10);
+    let index_file = NamedTempFile::new().unwrap();
+
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+    index.save_index(&index_file).await.expect("Saved index");
+
+    writeln!(file, "Line {:06}", 10).unwrap();
+    file.flush().unwrap();
+
+    let loaded = LineIndexReader::load_index(&file, &index_file)
+        .await
+        .expect("Loaded index");
+
+    assert_eq!(11, loaded.len());
+}
+
+#[tokio::test]
+pub async fn load_malformed_index() {
+    let file = temp_file(10);
+    let mut index_file = NamedTempFile::new().unwrap();
+    index_file.write_all(b"LQIX garbage").unwrap();
+    index_file.flush().unwrap();
+
+    let loaded = LineIndexReader::load_index(&file, &index_file)
+        .await
+        .expect("Loaded index");
+
+    assert_eq!(10, loaded.len());
+}
+
+#[rstest::rstest]
+#[case::utf8(Encoding::Utf8, None)]
+#[case::utf16le(Encoding::Utf16Le, Some(u16::to_le_bytes as fn(u16) -> [u8; 2]))]
+#[case::utf16be(Encoding::Utf16Be, Some(u16::to_be_bytes as fn(u16) -> [u8; 2]))]
+#[tokio::test]
+pub async fn encoding(#[case] expected: Encoding, #[case] to_bytes: Option<fn(u16) -> [u8; 2]>) {
+    const TEXT: &str = "Line 000000\r\nЛінія ✓\n\nLine 000003";
+
+    let mut file = NamedTempFile::new().unwrap();
+    match to_bytes {
+        // With the byte order mark.
+        Some(to_bytes) => "\u{FEFF}"
+            .encode_utf16()
+            .chain(TEXT.encode_utf16())
+            .for_each(|unit| file.write_all(&to_bytes(unit)).unwrap()),
+        None => file.write_all(TEXT.as_bytes()).unwrap(),
+    }
+    file.flush().unwrap();
+
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+    assert_eq!(expected, index.encoding());
+    assert_eq!(4, index.len());
+
+    let expected_lines: Vec<Box<str>> = ["Line 000000", "Лінія ✓", "", "Line 000003"]
+        .map(Into::into)
+        .into();
+    assert_eq!(expected_lines, index.lines(..).await.into_vec());
+    assert_eq!(
+        expected_lines,
+        index
+            .stream(..)
+            .map(|line| line.unwrap())
+            .collect::<Vec<_>>()
+            .await
+    );
+    assert_eq!(Some(1), index.find("✓", SearchOptions::default(), ..).await);
+    assert!(index.consistency().await.unwrap().is_consistent());
+}
+
+#[tokio::test]
+pub async fn index_reader() {
+    let (mut writer, source) = tokio::io::duplex(64);
+    let index = LineIndexReader::index_reader(source)
+        .await
+        .expect("LineIndex");
+
+    // Waits until the piped data is copied.
+    let update = |expected_len: u32| {
+        let index = &index;
+        async move {
+            for _ in 0..100 {
+                index.update().await.expect("Updated");
+                if index.len() == expected_len {
+                    return;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+            panic!("Expected {expected_len} lines, got {}", index.len());
+        }
+    };
+
+    writer.write_all(b"Line 000000\nLine 0000").await.unwrap();
+    update(2).await;
+    assert_eq!(Some("Line 0000"), index.line(1).await.as_deref());
+
+    writer.write_all(b"01\nLine 000002\n").await.unwrap();
+    update(3).await;
+    assert_eq!(
+        ["Line 000000", "Line 000001", "Line 000002"].map(Box::<str>::from),
+        *index.lines(..).await
+    );
+}
+
+#[tokio::test]
+pub async fn index_reader_utf16() {
+    let (mut writer, source) = tokio::io::duplex(64);
+    // Nothing is piped yet, the encoding is not known.
+    let index = LineIndexReader::index_reader(source)
+        .await
+        .expect("LineIndex");
+
+    let mut bytes = vec![0xFF, 0xFE];
+    bytes.extend("Line 0\nLine 1\n".encode_utf16().flat_map(u16::to_le_bytes));
+    writer.write_all(&bytes).await.unwrap();
+
+    for _ in 0..100 {
+        index.update().await.expect("Updated");
+        if index.len() == 2 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+
+    assert_eq!(Encoding::Utf16Le, index.encoding());
+    assert_eq!(
+        ["Line 0", "Line 1"].map(Box::<str>::from),
+        *index.lines(..).await
+    );
+}
+
+#[rstest::rstest]
+#[case::all(.., None, 0..usize::MAX)]
+#[case::middle(10..20, None, 12 * 10..12 * 20)]
+#[case::last_without_eol(SMALL_FILE_LINES - 1.., None, 12 * 9_564..usize::MAX)]
+#[case::matches(.., Some("Line 00000[13]"), 0..0)]
+#[tokio::test]
+pub async fn export(
+    #[case] range: impl RangeBounds<u32> + Send,
+    #[case] re: Option<&str>,
+    #[case] bytes: Range<usize>,
+) {
+    let file = small_file();
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+    let source = std::fs::read(&file).unwrap();
+    let re = re.map(|re| regex::Regex::new(re).unwrap());
+
+    let mut exported = vec![];
+    let written = index
+        .export(range, re.as_ref(), &mut exported)
+        .await
+        .expect("Exported");
+
+    let expected = if re.is_some() {
+        b"Line 000001\nLine 000003\n".to_vec()
+    } else {
+        source[bytes.start..bytes.end.min(source.len())].to_vec()
+    };
+    assert_eq!(
+        String::from_utf8_lossy(&exported),
+        String::from_utf8_lossy(&expected)
+    );
+    assert_eq!(written, exported.len() as u64);
+}
+
 // 11 bytes per line, so under 100K lines
 const SMALL_FILE_LINES: u32 = 9_565;
 // 11 bytes per line, so over 100K lines
@@ -203,3 +1264,61 @@ fn large_with_eof() -> NamedTempFile {
     f.flush().unwrap();
     f
 }
+
+#[rstest::rstest]
+#[case::all(.., 1_000..2_000)]
+#[case::overlap(1_900..2_100, 1_900..2_000)]
+#[case::outside(2_000.., 0..0)]
+#[tokio::test]
+pub async fn find_stream<R>(#[case] lines: R, #[case] expected: Range<u32>)
+where
+    R: RangeBounds<u32> + Send,
+{
+    let file = small_file();
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+
+    let found = index
+        .find_stream("Line 001", SearchOptions::default(), lines)
+        .collect::<Vec<_>>()
+        .await;
+
+    assert_eq!(expected.collect::<Vec<_>>(), found);
+}
+
+#[tokio::test]
+pub async fn find_stream_skips_invalid_utf8() {
+    let mut file = NamedTempFile::new().unwrap();
+    file.write_all(b"match\n\xff match\nno\nmatch\n").unwrap();
+    file.flush().unwrap();
+
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+    let found = index
+        .find_stream("match", SearchOptions::default(), ..)
+        .collect::<Vec<_>>()
+        .await;
+
+    assert_eq!(vec![0, 3], found);
+}
+
+#[tokio::test]
+pub async fn offsets_snapshot() {
+    let mut file = NamedTempFile::new().unwrap();
+    file.write_all(b"a\nbb\nccc").unwrap();
+    file.flush().unwrap();
+
+    let index = LineIndexReader::index(&file).await.expect("LineIndex");
+
+    let snapshot = index.offsets_snapshot();
+    assert_eq!([0, 2, 5], *snapshot);
+    assert!(
+        std::sync::Arc::ptr_eq(&snapshot, &index.offsets_snapshot()),
+        "shared until the index changes"
+    );
+
+    file.write_all(b"c\ndddd\n").unwrap();
+    file.flush().unwrap();
+    index.update().await.expect("Updated index");
+
+    assert_eq!([0, 2, 5, 10], *index.offsets_snapshot());
+    assert_eq!([0, 2, 5], *snapshot, "a taken snapshot does not change");
+}
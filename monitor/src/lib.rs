@@ -1,70 +1,364 @@
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+    time::SystemTime,
+};
 
 use enum_as_inner::EnumAsInner;
 use notify::Watcher;
-use tap::TapFallible;
-use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+mod links;
+mod queue;
+mod rename;
+mod sizes;
+
+pub use queue::Overflow;
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
-    #[error("Send error: {0}")]
-    SendFailure(#[from] tokio::sync::mpsc::error::SendError<Event>),
     #[error("Notify error: {0}")]
     NotifyError(#[from] notify::Error),
+    #[error("Pattern error: {0}")]
+    PatternError(#[from] glob::PatternError),
 }
 
 pub struct Monitor {
-    #[allow(dead_code)]
+    // Dropped first to release the watcher blocked on a full queue.
+    events: queue::Receiver,
     watcher: notify::RecommendedWatcher,
-    events: UnboundedReceiver<Event>,
+    /// The directories and external symlink targets given to the watcher.
+    watch_list: Vec<PathBuf>,
+}
+
+const DEFAULT_EXTENSIONS: &[&str] = &["log"];
+
+/// The default maximum number of pending events.
+pub const DEFAULT_CAPACITY: usize = 4_096;
+
+struct Options {
+    filter: Filter,
+    mode: notify::RecursiveMode,
+    capacity: usize,
+    overflow: Overflow,
+    min_size: u64,
+    max_size: u64,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            filter: Filter::extensions(DEFAULT_EXTENSIONS),
+            mode: notify::RecursiveMode::NonRecursive,
+            capacity: DEFAULT_CAPACITY,
+            overflow: Overflow::default(),
+            min_size: 0,
+            max_size: u64::MAX,
+        }
+    }
+}
+
+/// Combines the options of the `create_*` constructors, see [`Monitor::builder`].
+/// Watches `*.log` files in the given directories unless told otherwise.
+#[derive(Default)]
+pub struct Builder {
+    options: Options,
+}
+
+impl Builder {
+    /// Watches files with one of the extensions, see [`Monitor::create_with_extensions`].
+    #[must_use]
+    pub fn extensions(mut self, extensions: &[&str]) -> Self {
+        self.options.filter = Filter::extensions(extensions);
+        self
+    }
+
+    /// Watches files with the name matching the pattern, see [`Monitor::create_with_glob`].
+    pub fn glob(mut self, pattern: &str) -> Result<Self, Error> {
+        self.options.filter = Filter::Glob(glob::Pattern::new(pattern)?);
+        Ok(self)
+    }
+
+    /// Watches the subdirectories as well, see [`Monitor::create_recursive`].
+    #[must_use]
+    pub const fn recursive(mut self) -> Self {
+        self.options.mode = notify::RecursiveMode::Recursive;
+        self
+    }
+
+    /// Keeps at most `capacity` pending events, see [`Monitor::create_with_capacity`].
+    #[must_use]
+    pub const fn capacity(mut self, capacity: usize, overflow: Overflow) -> Self {
+        self.options.capacity = capacity;
+        self.options.overflow = overflow;
+        self
+    }
+
+    /// Watches files with a size within `min_size..=max_size` bytes.
+    /// A file growing into the limits is reported as created, a file leaving them as removed.
+    #[must_use]
+    pub const fn size_limits(mut self, min_size: u64, max_size: u64) -> Self {
+        self.options.min_size = min_size;
+        self.options.max_size = max_size;
+        self
+    }
+
+    /// Starts watching the directories, see [`Monitor::create_many`].
+    pub fn watch<P>(self, paths: &[P]) -> Result<Monitor, Error>
+    where
+        P: AsRef<Path> + Send,
+    {
+        Monitor::watch(paths, self.options)
+    }
 }
 
 impl Monitor {
+    /// Watches `*.log` files in the directory.
     pub fn create<P>(path: &P) -> Result<Self, Error>
     where
         P: AsRef<Path> + Send,
     {
-        // TODO bound
-        let (tx, rx) = unbounded_channel();
+        Self::create_with_extensions(path, DEFAULT_EXTENSIONS)
+    }
+
+    /// Watches files with one of the extensions in the directory.
+    /// Extensions are given without the leading dot and compared case-insensitively,
+    /// an empty list accepts all files.
+    pub fn create_with_extensions<P>(path: &P, extensions: &[&str]) -> Result<Self, Error>
+    where
+        P: AsRef<Path> + Send,
+    {
+        Self::watch(
+            &[path],
+            Options {
+                filter: Filter::extensions(extensions),
+                ..Options::default()
+            },
+        )
+    }
 
-        for event in list_files_in_directory(path)? {
-            tx.send(event).tap_err(|error| {
-                tracing::error!(path = %path.as_ref().display(), %error, "Failed to send initial list of files");
-            })?;
+    /// Watches files with the name matching the glob pattern in the directory, e.g. `access-*.log`.
+    pub fn create_with_glob<P>(path: &P, pattern: &str) -> Result<Self, Error>
+    where
+        P: AsRef<Path> + Send,
+    {
+        Self::watch(
+            &[path],
+            Options {
+                filter: Filter::Glob(glob::Pattern::new(pattern)?),
+                ..Options::default()
+            },
+        )
+    }
+
+    /// Watches `*.log` files in the directory and all its subdirectories, at any depth.
+    /// Subdirectories created later are watched as well, symlinked directories are not followed.
+    pub fn create_recursive<P>(path: &P) -> Result<Self, Error>
+    where
+        P: AsRef<Path> + Send,
+    {
+        Self::watch(
+            &[path],
+            Options {
+                mode: notify::RecursiveMode::Recursive,
+                ..Options::default()
+            },
+        )
+    }
+
+    /// Watches `*.log` files in the directory keeping at most `capacity` pending events.
+    /// A full queue either stalls the watcher or drops the oldest event, see [`Overflow`].
+    /// The files found on start are always queued, regardless of the capacity.
+    pub fn create_with_capacity<P>(
+        path: &P,
+        capacity: usize,
+        overflow: Overflow,
+    ) -> Result<Self, Error>
+    where
+        P: AsRef<Path> + Send,
+    {
+        Self::watch(
+            &[path],
+            Options {
+                capacity,
+                overflow,
+                ..Options::default()
+            },
+        )
+    }
+
+    /// Configures the watcher with several options at once, e.g. a glob pattern and size limits.
+    #[must_use]
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
+    /// Watches `*.log` files in all the directories, merging their events.
+    /// A directory that cannot be watched is reported and skipped,
+    /// it is an error only if none of them can be watched.
+    pub fn create_many<P>(paths: &[P]) -> Result<Self, Error>
+    where
+        P: AsRef<Path> + Send,
+    {
+        Self::watch(paths, Options::default())
+    }
+
+    fn watch<P>(paths: &[P], options: Options) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let Options {
+            filter,
+            mode,
+            capacity,
+            overflow,
+            min_size,
+            max_size,
+        } = options;
+
+        // Events are keyed by path, make them unique regardless of the working directory.
+        let mut dirs: Vec<PathBuf> = vec![];
+        for path in paths {
+            let path = std::path::absolute(path)?;
+            if !dirs.contains(&path) {
+                dirs.push(path);
+            }
         }
 
+        let (tx, rx) = queue::channel(capacity, overflow);
+        let tx = Arc::new(tx);
+        let filter = Arc::new(filter);
+
+        let links = Arc::new(RwLock::new(links::Links::default()));
+
+        let mut renames = rename::Renames::default();
+
+        // Shared with the initial scan, a file found there might be modified before it is sent.
+        let sizes = Arc::new(Mutex::new(sizes::Sizes::new(min_size, max_size)));
+
         let mut watcher = notify::recommended_watcher({
+            let tx = tx.clone();
+            let filter = filter.clone();
+            let links = links.clone();
+            let sizes = sizes.clone();
             move |res: notify::Result<notify::Event>| {
                 let event = res.expect("Notify event");
-                for ev in event
-                    .paths
-                    .iter()
-                    .filter_map(|path| event_handler(path.to_owned(), event.kind))
-                {
-                    let path = ev.path.clone();
-                    _ = tx.send(ev).tap_err(|error| {
-                        tracing::error!(path = %path.display(), event_kind = ?event.kind, %error, "Failed to send an event");
-                    });
+                // Collected first, sending may block while the links are being added.
+                let events = {
+                    let links = links.read().unwrap();
+                    renames
+                        .handle(&event, &filter)
+                        .into_iter()
+                        .chain(
+                            event
+                                .paths
+                                .iter()
+                                .flat_map(|path| links.resolve(path))
+                                .filter(|path| filter.matches(path))
+                                .filter_map(|path| event_handler(path, event.kind)),
+                        )
+                        .chain(files_in_created_dir(&event, &filter, mode))
+                        .collect::<Vec<_>>()
+                };
+                let events = sizes.lock().unwrap().handle_all(events);
+                for ev in events {
+                    tx.send(ev);
                 }
             }
         })?;
-        watcher.watch(path.as_ref(), notify::RecursiveMode::NonRecursive)?;
+
+        let mut failure = None;
+        let mut watch_list = vec![];
+        let mut watched_dirs = vec![];
+        let mut symlinks = vec![];
+
+        for dir in &dirs {
+            let result = list_files_in_directory(dir, &filter, mode).and_then(|events| {
+                watcher
+                    .watch(dir, mode)
+                    .map(|()| events)
+                    .map_err(Into::into)
+            });
+
+            match result {
+                Ok(events) => {
+                    symlinks.extend(events.iter().filter_map(|event| {
+                        links::resolve_symlink(&event.path)
+                            .map(|target| (event.path.clone(), target))
+                    }));
+                    // The consumer has not started yet, a full queue would stall it forever.
+                    let events = sizes.lock().unwrap().handle_all(events);
+                    for event in events {
+                        tx.force_send(event);
+                    }
+                    watch_list.push(dir.clone());
+                    watched_dirs.extend(std::fs::canonicalize(dir));
+                }
+                Err(error) => {
+                    tracing::error!(path = %dir.display(), %error, "Failed to watch directory");
+                    failure = Some(error);
+                }
+            }
+        }
+
+        if let Some(error) = failure.filter(|_| watched_dirs.is_empty()) {
+            return Err(error);
+        }
+
+        watch_list.extend(follow_symlinks(
+            &mut watcher,
+            symlinks,
+            &watched_dirs,
+            mode,
+            &links,
+        ));
 
         Ok(Self {
-            watcher,
             events: rx,
+            watcher,
+            watch_list,
         })
     }
 
+    /// Stops watching and returns the events not consumed yet.
+    /// Events raised while shutting down are discarded.
+    pub fn shutdown(self) -> Vec<Event> {
+        let Self {
+            events,
+            mut watcher,
+            watch_list,
+        } = self;
+
+        // Releases the watcher blocked on a full queue, it could not be stopped otherwise.
+        events.close();
+
+        for path in &watch_list {
+            if let Err(error) = watcher.unwatch(path) {
+                tracing::warn!(path = %path.display(), %error, "Failed to unwatch");
+            }
+        }
+        drop(watcher);
+
+        std::iter::from_fn(|| events.try_recv()).collect()
+    }
+
+    /// Returns the next event if there is one, without waiting.
     pub fn try_next_message(&mut self) -> Option<Event> {
-        self.events.try_recv().ok()
+        self.events.try_recv()
     }
 
+    /// Waits for the next event.
+    /// Returns `None` once the watcher is gone and all events are consumed.
     pub async fn next_message(&mut self) -> Option<Event> {
         self.events.recv().await
     }
+
+    /// Returns the number of events waiting to be consumed.
+    #[must_use]
+    pub fn pending(&self) -> usize {
+        self.events.len()
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, EnumAsInner)]
@@ -72,32 +366,104 @@ pub enum EventKind {
     Created,
     Modified,
     Removed,
+    /// The file was renamed within the watched files, [`Event::path`] is the new path.
+    Renamed {
+        from: PathBuf,
+        to: PathBuf,
+    },
 }
 
 #[derive(Debug, Clone)]
 pub struct Event {
     pub path: PathBuf,
     pub kind: EventKind,
+    /// The file metadata at the time of the event, `None` if it could not be read,
+    /// e.g. the file was removed.
+    pub metadata: Option<FileMeta>,
 }
 
-fn event_handler(path: PathBuf, event_kind: notify::EventKind) -> Option<Event> {
-    match event_kind {
-        notify::EventKind::Access(_) => None, /* Access events are ignored */
-        notify::EventKind::Create(notify::event::CreateKind::File) => Event {
+impl Event {
+    fn new(path: PathBuf, kind: EventKind) -> Self {
+        let metadata = FileMeta::read(&path);
+        Self {
             path,
-            kind: EventKind::Created,
+            kind,
+            metadata,
         }
-        .into(),
-        notify::EventKind::Modify(_) => Event {
-            path,
-            kind: EventKind::Modified,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileMeta {
+    pub len: u64,
+    pub modified: SystemTime,
+}
+
+impl FileMeta {
+    fn read(path: &Path) -> Option<Self> {
+        let metadata = std::fs::metadata(path).ok()?;
+        Some(Self {
+            len: metadata.len(),
+            modified: metadata.modified().ok()?,
+        })
+    }
+}
+
+/// Watches the targets of the symlinks outside the watched directories,
+/// returns the targets being watched.
+fn follow_symlinks(
+    watcher: &mut notify::RecommendedWatcher,
+    symlinks: Vec<(PathBuf, std::io::Result<PathBuf>)>,
+    watched_dirs: &[PathBuf],
+    mode: notify::RecursiveMode,
+    links: &RwLock<links::Links>,
+) -> Vec<PathBuf> {
+    let mut targets = vec![];
+
+    for (link, target) in symlinks {
+        let target = match target {
+            Ok(target) => target,
+            Err(error) => {
+                tracing::warn!(path = %link.display(), %error, "Failed to resolve symlink, it will not be followed");
+                continue;
+            }
+        };
+
+        let external = !watched_dirs.iter().any(|dir| match mode {
+            notify::RecursiveMode::Recursive => target.starts_with(dir),
+            notify::RecursiveMode::NonRecursive => target.parent() == Some(dir),
+        });
+
+        if external {
+            if let Err(error) = watcher.watch(&target, notify::RecursiveMode::NonRecursive) {
+                tracing::warn!(path = %link.display(), target = %target.display(), %error, "Failed to watch symlink target, it will not be followed");
+                continue;
+            }
+            if !targets.contains(&target) {
+                targets.push(target.clone());
+            }
         }
-        .into(),
-        notify::EventKind::Remove(notify::event::RemoveKind::File) => Event {
-            path,
-            kind: EventKind::Removed,
+
+        links.write().unwrap().insert(target, link, external);
+    }
+
+    targets
+}
+
+fn event_handler(path: PathBuf, event_kind: notify::EventKind) -> Option<Event> {
+    match event_kind {
+        /* Access events are ignored, renames are paired in `Renames`,
+         * the files of new directories are listed by `files_in_created_dir` */
+        notify::EventKind::Access(_)
+        | notify::EventKind::Modify(notify::event::ModifyKind::Name(_))
+        | notify::EventKind::Create(notify::event::CreateKind::Folder) => None,
+        notify::EventKind::Create(notify::event::CreateKind::File) => {
+            Event::new(path, EventKind::Created).into()
+        }
+        notify::EventKind::Modify(_) => Event::new(path, EventKind::Modified).into(),
+        notify::EventKind::Remove(notify::event::RemoveKind::File) => {
+            Event::new(path, EventKind::Removed).into()
         }
-        .into(),
         kind => {
             tracing::warn!("Unsupported event {kind:?} for file {}", path.display());
             None
@@ -105,20 +471,86 @@ fn event_handler(path: PathBuf, event_kind: notify::EventKind) -> Option<Event>
     }
 }
 
-fn list_files_in_directory<P>(path: &P) -> Result<Vec<Event>, Error>
-where
-    P: AsRef<Path>,
-{
-    std::fs::read_dir(path)
-        .map(|res| {
-            res.map(|entry| entry.map(|entry| entry.path()))
-                .filter_map(Result::ok)
-                .filter(|path| path.is_file())
-                .map(|path| Event {
-                    path,
-                    kind: EventKind::Created,
-                })
-                .collect()
+/// Lists the files in a directory created under a recursive watch.
+/// The watcher adds the directory by itself, but the files written into it
+/// before that raise no events. A file written right after might be reported twice.
+fn files_in_created_dir(
+    event: &notify::Event,
+    filter: &Filter,
+    mode: notify::RecursiveMode,
+) -> Vec<Event> {
+    if mode != notify::RecursiveMode::Recursive
+        || event.kind != notify::EventKind::Create(notify::event::CreateKind::Folder)
+    {
+        return vec![];
+    }
+
+    event
+        .paths
+        .iter()
+        .flat_map(|path| {
+            list_files_in_directory(path, filter, mode).unwrap_or_else(|error| {
+                tracing::warn!(path = %path.display(), %error, "Failed to list files");
+                vec![]
+            })
         })
-        .map_err(Into::into)
+        .collect()
+}
+
+enum Filter {
+    Extensions(Box<[String]>),
+    /// Matches the file name.
+    Glob(glob::Pattern),
+}
+
+impl Filter {
+    fn extensions(extensions: &[&str]) -> Self {
+        Self::Extensions(extensions.iter().map(ToString::to_string).collect())
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        match self {
+            Self::Extensions(extensions) => {
+                extensions.is_empty()
+                    || path.extension().is_some_and(|ext| {
+                        extensions
+                            .iter()
+                            .any(|allowed| ext.eq_ignore_ascii_case(allowed))
+                    })
+            }
+            Self::Glob(pattern) => path
+                .file_name()
+                .is_some_and(|name| pattern.matches(&name.to_string_lossy())),
+        }
+    }
+}
+
+fn list_files_in_directory(
+    path: &Path,
+    filter: &Filter,
+    mode: notify::RecursiveMode,
+) -> Result<Vec<Event>, Error> {
+    let mut events = vec![];
+
+    for entry in std::fs::read_dir(path)?.filter_map(Result::ok) {
+        let path = entry.path();
+
+        if path.is_file() {
+            if filter.matches(&path) {
+                events.push(Event::new(path, EventKind::Created));
+            }
+        } else if mode == notify::RecursiveMode::Recursive
+            // `DirEntry::file_type` does not follow symlinks, which could make a loop.
+            && entry.file_type().is_ok_and(|file_type| file_type.is_dir())
+        {
+            match list_files_in_directory(&path, filter, mode) {
+                Ok(nested) => events.extend(nested),
+                Err(error) => {
+                    tracing::warn!(path = %path.display(), %error, "Failed to list files");
+                }
+            }
+        }
+    }
+
+    Ok(events)
 }
@@ -0,0 +1,54 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+/// Symlinked files found on start.
+///
+/// The watcher reports changes under the real path of a file,
+/// the links map them back to the paths of the symlinks.
+/// Targets outside the watched directories are watched separately.
+#[derive(Default)]
+pub struct Links {
+    by_target: HashMap<PathBuf, Target>,
+}
+
+struct Target {
+    links: Vec<PathBuf>,
+    /// The target is not inside the watched directories, its own events are not reported.
+    external: bool,
+}
+
+impl Links {
+    pub fn insert(&mut self, target: PathBuf, link: PathBuf, external: bool) {
+        self.by_target
+            .entry(target)
+            .or_insert_with(|| Target {
+                links: vec![],
+                external,
+            })
+            .links
+            .push(link);
+    }
+
+    /// Returns the paths to report an event for `path` under.
+    pub fn resolve(&self, path: &Path) -> Vec<PathBuf> {
+        match self.by_target.get(path) {
+            Some(target) if target.external => target.links.clone(),
+            Some(target) => target
+                .links
+                .iter()
+                .cloned()
+                .chain([path.to_owned()])
+                .collect(),
+            None => vec![path.to_owned()],
+        }
+    }
+}
+
+/// Returns the real path of a symlink, `None` if the path is not a symlink.
+pub fn resolve_symlink(path: &Path) -> Option<std::io::Result<PathBuf>> {
+    std::fs::symlink_metadata(path)
+        .is_ok_and(|metadata| metadata.is_symlink())
+        .then(|| std::fs::canonicalize(path))
+}
@@ -0,0 +1,153 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Condvar, Mutex},
+};
+
+use tokio::sync::Notify;
+
+use crate::Event;
+
+/// What to do with a new event when the queue is full.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Overflow {
+    /// Wait until the consumer takes an event, stalling the watcher.
+    #[default]
+    Block,
+    /// Drop the oldest queued event.
+    DropOldest,
+}
+
+struct State {
+    events: VecDeque<Event>,
+    sender_alive: bool,
+    receiver_alive: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    capacity: usize,
+    overflow: Overflow,
+    /// Signals the sender that there is space in the queue.
+    space: Condvar,
+    /// Signals the receiver that there are events in the queue.
+    ready: Notify,
+}
+
+/// Creates a queue holding at most `capacity` events, at least one.
+pub fn channel(capacity: usize, overflow: Overflow) -> (Sender, Receiver) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            events: VecDeque::new(),
+            sender_alive: true,
+            receiver_alive: true,
+        }),
+        capacity: capacity.max(1),
+        overflow,
+        space: Condvar::new(),
+        ready: Notify::new(),
+    });
+
+    (Sender(shared.clone()), Receiver(shared))
+}
+
+pub struct Sender(Arc<Shared>);
+
+impl Sender {
+    /// Queues the event ignoring the capacity.
+    pub fn force_send(&self, event: Event) {
+        self.0.state.lock().unwrap().events.push_back(event);
+        self.0.ready.notify_one();
+    }
+
+    /// Queues the event, handling a full queue according to the overflow policy.
+    /// The event is discarded if the receiver is gone.
+    pub fn send(&self, event: Event) {
+        let shared = &self.0;
+        let mut state = shared.state.lock().unwrap();
+
+        match shared.overflow {
+            Overflow::Block => {
+                state = shared
+                    .space
+                    .wait_while(state, |state| {
+                        state.receiver_alive && state.events.len() >= shared.capacity
+                    })
+                    .unwrap();
+            }
+            Overflow::DropOldest => {
+                while state.events.len() >= shared.capacity {
+                    if let Some(dropped) = state.events.pop_front() {
+                        tracing::warn!(path = %dropped.path.display(), kind = ?dropped.kind, "Event queue is full, dropped an event");
+                    }
+                }
+            }
+        }
+
+        if !state.receiver_alive {
+            return;
+        }
+
+        state.events.push_back(event);
+        drop(state);
+
+        shared.ready.notify_one();
+    }
+}
+
+impl Drop for Sender {
+    fn drop(&mut self) {
+        self.0.state.lock().unwrap().sender_alive = false;
+        self.0.ready.notify_one();
+    }
+}
+
+pub struct Receiver(Arc<Shared>);
+
+impl Receiver {
+    pub fn try_recv(&self) -> Option<Event> {
+        let event = self.0.state.lock().unwrap().events.pop_front();
+        if event.is_some() {
+            self.0.space.notify_one();
+        }
+        event
+    }
+
+    /// Waits for the next event, returns `None` when the sender is gone and the queue is empty.
+    pub async fn recv(&self) -> Option<Event> {
+        loop {
+            // A notification sent in between is kept as a permit.
+            let ready = self.0.ready.notified();
+
+            {
+                let mut state = self.0.state.lock().unwrap();
+                if let Some(event) = state.events.pop_front() {
+                    drop(state);
+                    self.0.space.notify_one();
+                    return Some(event);
+                }
+                if !state.sender_alive {
+                    return None;
+                }
+            }
+
+            ready.await;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.state.lock().unwrap().events.len()
+    }
+
+    /// Makes the sender discard new events, the queued ones can still be received.
+    pub fn close(&self) {
+        self.0.state.lock().unwrap().receiver_alive = false;
+        // Release the sender if it waits for space.
+        self.0.space.notify_all();
+    }
+}
+
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
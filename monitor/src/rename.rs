@@ -0,0 +1,97 @@
+use std::path::PathBuf;
+
+use notify::event::RenameMode;
+
+use crate::{Event, EventKind, Filter};
+
+/// Pairs the halves of rename events.
+///
+/// Backends may report a rename as separate `From` and `To` events sharing a tracker,
+/// inotify additionally follows them with a `Both` event carrying both paths.
+/// A `From` half without a `To` half, i.e. a file moved out of the watched directory,
+/// is reported as removed when the next event arrives.
+#[derive(Default)]
+pub struct Renames {
+    /// The `From` half waiting for its `To` half.
+    from: Option<(Option<usize>, PathBuf)>,
+    /// The tracker of the last paired rename, the `Both` event repeating it is skipped.
+    paired: Option<usize>,
+}
+
+type Move = (Option<PathBuf>, Option<PathBuf>);
+
+impl Renames {
+    pub fn handle(&mut self, event: &notify::Event, filter: &Filter) -> Vec<Event> {
+        let notify::EventKind::Modify(notify::event::ModifyKind::Name(mode)) = event.kind else {
+            return self.flush(filter).into_iter().collect();
+        };
+
+        let tracker = event.attrs.tracker();
+        let mut moves: Vec<Move> = vec![];
+
+        match (mode, event.paths.as_slice()) {
+            (RenameMode::From, [from]) => {
+                moves.extend(self.from.take().map(|(_, from)| (Some(from), None)));
+                self.from = Some((tracker, from.clone()));
+            }
+            (RenameMode::To, [to]) => match self.from.take() {
+                Some((from_tracker, from)) if tracker.is_some() && from_tracker == tracker => {
+                    self.paired = tracker;
+                    moves.push((Some(from), Some(to.clone())));
+                }
+                pending => {
+                    moves.extend(pending.map(|(_, from)| (Some(from), None)));
+                    moves.push((None, Some(to.clone())));
+                }
+            },
+            (RenameMode::Both, [from, to]) => {
+                if tracker.is_some() && self.paired == tracker {
+                    self.paired = None;
+                } else {
+                    match self.from.take() {
+                        Some((from_tracker, _)) if from_tracker == tracker => {}
+                        pending => moves.extend(pending.map(|(_, from)| (Some(from), None))),
+                    }
+                    moves.push((Some(from.clone()), Some(to.clone())));
+                }
+            }
+            // The backend does not tell which side it is, check if the file is still there.
+            (_, paths) => {
+                moves.extend(self.from.take().map(|(_, from)| (Some(from), None)));
+                moves.extend(paths.iter().map(|path| {
+                    if path.exists() {
+                        (None, Some(path.clone()))
+                    } else {
+                        (Some(path.clone()), None)
+                    }
+                }));
+            }
+        }
+
+        moves
+            .into_iter()
+            .filter_map(|(from, to)| moved(from, to, filter))
+            .collect()
+    }
+
+    /// Reports the pending `From` half as removed.
+    fn flush(&mut self, filter: &Filter) -> Option<Event> {
+        self.from
+            .take()
+            .and_then(|(_, from)| moved(Some(from), None, filter))
+    }
+}
+
+/// Turns a move into an event, as seen through the filter:
+/// moving a file out of the filter removes it, moving a file into the filter creates it.
+fn moved(from: Option<PathBuf>, to: Option<PathBuf>, filter: &Filter) -> Option<Event> {
+    let from = from.filter(|path| filter.matches(path));
+    let to = to.filter(|path| filter.matches(path));
+
+    match (from, to) {
+        (Some(from), Some(to)) => Event::new(to.clone(), EventKind::Renamed { from, to }).into(),
+        (Some(path), None) => Event::new(path, EventKind::Removed).into(),
+        (None, Some(path)) => Event::new(path, EventKind::Created).into(),
+        (None, None) => None,
+    }
+}
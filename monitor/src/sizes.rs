@@ -0,0 +1,80 @@
+use std::{collections::HashSet, path::PathBuf};
+
+use crate::{Event, EventKind};
+
+/// Reports only the files with a size within the limits.
+///
+/// A file growing into the limits is reported as created, a file leaving them as removed,
+/// so the consumer sees the files come and go as if they were created and removed.
+/// The events of the files not reported are dropped.
+pub struct Sizes {
+    min: u64,
+    max: u64,
+    /// The files reported as created and not removed since.
+    reported: HashSet<PathBuf>,
+}
+
+impl Sizes {
+    pub fn new(min: u64, max: u64) -> Self {
+        Self {
+            min,
+            max,
+            reported: HashSet::new(),
+        }
+    }
+
+    const fn is_unlimited(&self) -> bool {
+        self.min == 0 && self.max == u64::MAX
+    }
+
+    /// A file without metadata is gone or unreadable, it is not within the limits.
+    fn fits(&self, event: &Event) -> bool {
+        event
+            .metadata
+            .is_some_and(|metadata| (self.min..=self.max).contains(&metadata.len))
+    }
+
+    pub fn handle_all(&mut self, events: Vec<Event>) -> Vec<Event> {
+        events
+            .into_iter()
+            .filter_map(|event| self.handle(event))
+            .collect()
+    }
+
+    fn handle(&mut self, event: Event) -> Option<Event> {
+        if self.is_unlimited() {
+            return Some(event);
+        }
+
+        let fits = self.fits(&event);
+
+        let known = match &event.kind {
+            EventKind::Created | EventKind::Modified => self.reported.remove(&event.path),
+            EventKind::Removed => return self.reported.remove(&event.path).then_some(event),
+            EventKind::Renamed { from, .. } => self.reported.remove(from),
+        };
+        if fits {
+            self.reported.insert(event.path.clone());
+        }
+
+        match (known, fits) {
+            (true, true) => Some(event),
+            (false, true) => Some(Event {
+                kind: EventKind::Created,
+                ..event
+            }),
+            (true, false) => Some(match event.kind {
+                EventKind::Renamed { from, .. } => Event {
+                    path: from,
+                    kind: EventKind::Removed,
+                    metadata: None,
+                },
+                _ => Event {
+                    kind: EventKind::Removed,
+                    ..event
+                },
+            }),
+            (false, false) => None,
+        }
+    }
+}
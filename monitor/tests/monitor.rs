@@ -2,13 +2,20 @@ use std::io::Write;
 
 use monitor::EventKind;
 
+fn log_file(dir: &tempfile::TempDir) -> tempfile::NamedTempFile {
+    tempfile::Builder::new()
+        .suffix(".log")
+        .tempfile_in(dir)
+        .unwrap()
+}
+
 #[test]
 pub fn test_monitor_new_files() {
     let temp_dir = tempfile::tempdir().unwrap();
 
     let mut m = monitor::Monitor::create(&temp_dir).unwrap();
 
-    let mut temp_file_a = tempfile::NamedTempFile::new_in(&temp_dir).unwrap();
+    let mut temp_file_a = log_file(&temp_dir);
 
     temp_file_a.write_all(b"First line\n").unwrap();
 
@@ -32,10 +39,10 @@ pub fn test_monitor_new_files() {
 pub fn test_monitor_existing_files() {
     let temp_dir = tempfile::tempdir().unwrap();
 
-    let mut file_a = tempfile::NamedTempFile::new_in(&temp_dir).unwrap();
+    let mut file_a = log_file(&temp_dir);
     file_a.write_all(b"Line A\n").unwrap();
 
-    let mut file_b = tempfile::NamedTempFile::new_in(&temp_dir).unwrap();
+    let mut file_b = log_file(&temp_dir);
     file_b.write_all(b"Line C\n").unwrap();
 
     let mut m = monitor::Monitor::create(&temp_dir).unwrap();
@@ -59,3 +66,442 @@ pub fn test_monitor_existing_files() {
         ],
     );
 }
+
+#[tokio::test]
+pub async fn test_monitor_next_message() {
+    let temp_dir = tempfile::tempdir().unwrap();
+
+    let mut m = monitor::Monitor::create(&temp_dir).unwrap();
+
+    let mut file = log_file(&temp_dir);
+    file.write_all(b"Line A\n").unwrap();
+
+    let timeout = std::time::Duration::from_secs(1);
+
+    let event = tokio::time::timeout(timeout, m.next_message())
+        .await
+        .expect("should receive an event")
+        .unwrap();
+    assert_eq!(event.kind, EventKind::Created);
+    assert_eq!(event.path, file.path());
+}
+
+#[test]
+pub fn test_monitor_extensions() {
+    let temp_dir = tempfile::tempdir().unwrap();
+
+    let new_file = |suffix| {
+        tempfile::Builder::new()
+            .suffix(suffix)
+            .tempfile_in(&temp_dir)
+            .unwrap()
+    };
+
+    let _existing_txt = new_file(".txt");
+    let _existing_bin = new_file(".bin");
+
+    let mut m = monitor::Monitor::create_with_extensions(&temp_dir, &["txt", "ndjson"]).unwrap();
+
+    let mut bin = new_file(".bin");
+    let mut ndjson = new_file(".NDJSON");
+    bin.write_all(b"Line A\n").unwrap();
+    ndjson.write_all(b"Line A\n").unwrap();
+
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let events = std::iter::from_fn(|| m.try_next_message()).collect::<Vec<_>>();
+
+    assert!(!events.is_empty());
+    assert!(events.iter().all(|ev| {
+        let ext = ev.path.extension().unwrap();
+        ext == "txt" || ext == "NDJSON"
+    }));
+    assert_eq!(events[0].kind, EventKind::Created);
+    assert_eq!(events[0].path.extension().unwrap(), "txt");
+}
+
+#[test]
+pub fn test_monitor_recursive() {
+    let temp_dir = tempfile::tempdir().unwrap();
+
+    let nested = temp_dir.path().join("service").join("nested");
+    std::fs::create_dir_all(&nested).unwrap();
+    let existing = nested.join("existing.log");
+    std::fs::write(&existing, b"Line A\n").unwrap();
+
+    let mut m = monitor::Monitor::create_recursive(&temp_dir).unwrap();
+
+    let event = m.try_next_message().unwrap();
+    assert_eq!(event.kind, EventKind::Created);
+    assert_eq!(event.path, existing);
+    assert!(event.path.is_absolute());
+
+    let created = temp_dir.path().join("service").join("created.log");
+    std::fs::write(&created, b"Line A\n").unwrap();
+
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let event = m.try_next_message().unwrap();
+    assert_eq!(event.kind, EventKind::Created);
+    assert_eq!(event.path, created);
+}
+
+#[test]
+pub fn test_monitor_created_directory() {
+    let temp_dir = tempfile::tempdir().unwrap();
+
+    let mut m = monitor::Monitor::create_recursive(&temp_dir).unwrap();
+
+    // Written before the watcher adds the new directories.
+    let nested = temp_dir.path().join("service").join("nested");
+    std::fs::create_dir_all(&nested).unwrap();
+    let first = nested.join("first.log");
+    std::fs::write(&first, b"Line A\n").unwrap();
+    std::fs::write(nested.join("ignored.txt"), b"Line A\n").unwrap();
+
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let created = std::iter::from_fn(|| m.try_next_message())
+        .filter(|event| event.kind == EventKind::Created)
+        .map(|event| event.path)
+        .collect::<Vec<_>>();
+    assert!(created.contains(&first));
+    assert!(created.iter().all(|path| path == &first));
+
+    // The new directory is watched.
+    let second = nested.join("second.log");
+    std::fs::write(&second, b"Line A\n").unwrap();
+
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let event = m.try_next_message().unwrap();
+    assert_eq!(event.kind, EventKind::Created);
+    assert_eq!(event.path, second);
+}
+
+#[test]
+pub fn test_monitor_drop_oldest() {
+    let temp_dir = tempfile::tempdir().unwrap();
+
+    let mut m =
+        monitor::Monitor::create_with_capacity(&temp_dir, 16, monitor::Overflow::DropOldest)
+            .unwrap();
+
+    let mut files = (0..4).map(|_| log_file(&temp_dir)).collect::<Vec<_>>();
+    for _ in 0..250 {
+        for file in &mut files {
+            file.write_all(b"Line\n").unwrap();
+        }
+    }
+    let last = log_file(&temp_dir);
+
+    std::thread::sleep(std::time::Duration::from_millis(200));
+
+    assert_eq!(m.pending(), 16);
+
+    let events = std::iter::from_fn(|| m.try_next_message()).collect::<Vec<_>>();
+    assert_eq!(events.len(), 16);
+    assert_eq!(events.last().unwrap().kind, EventKind::Created);
+    assert_eq!(events.last().unwrap().path, last.path());
+}
+
+#[tokio::test]
+pub async fn test_monitor_block() {
+    let temp_dir = tempfile::tempdir().unwrap();
+
+    let mut m =
+        monitor::Monitor::create_with_capacity(&temp_dir, 2, monitor::Overflow::Block).unwrap();
+
+    let files = (0..8).map(|_| log_file(&temp_dir)).collect::<Vec<_>>();
+
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    assert_eq!(m.pending(), 2);
+
+    let timeout = std::time::Duration::from_secs(1);
+    for file in &files {
+        let event = tokio::time::timeout(timeout, m.next_message())
+            .await
+            .expect("should receive an event")
+            .unwrap();
+        assert_eq!(event.kind, EventKind::Created);
+        assert_eq!(event.path, file.path());
+    }
+}
+
+#[test]
+pub fn test_monitor_rename() {
+    let temp_dir = tempfile::tempdir().unwrap();
+
+    let app = temp_dir.path().join("app.log");
+    let renamed = temp_dir.path().join("app-1.log");
+    let rotated = temp_dir.path().join("app.log.1");
+    std::fs::write(&app, b"Line A\n").unwrap();
+
+    let mut m = monitor::Monitor::create(&temp_dir).unwrap();
+    assert_eq!(m.try_next_message().unwrap().kind, EventKind::Created);
+
+    std::fs::rename(&app, &renamed).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let event = m.try_next_message().unwrap();
+    assert_eq!(
+        event.kind,
+        EventKind::Renamed {
+            from: app,
+            to: renamed.clone()
+        }
+    );
+    assert_eq!(event.path, renamed);
+    assert!(m.try_next_message().is_none());
+
+    // The rotated file does not match the filter anymore.
+    std::fs::rename(&renamed, &rotated).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let event = m.try_next_message().unwrap();
+    assert_eq!(event.kind, EventKind::Removed);
+    assert_eq!(event.path, renamed);
+    assert!(m.try_next_message().is_none());
+}
+
+#[test]
+pub fn test_monitor_metadata() {
+    let temp_dir = tempfile::tempdir().unwrap();
+
+    let mut file = log_file(&temp_dir);
+    file.write_all(b"Line A\n").unwrap();
+
+    let mut m = monitor::Monitor::create(&temp_dir).unwrap();
+
+    let event = m.try_next_message().unwrap();
+    assert_eq!(event.kind, EventKind::Created);
+    let metadata = event.metadata.unwrap();
+    assert_eq!(metadata.len, 7);
+    assert_eq!(
+        metadata.modified,
+        file.as_file().metadata().unwrap().modified().unwrap()
+    );
+
+    file.write_all(b"Line B\n").unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let event = m.try_next_message().unwrap();
+    assert_eq!(event.kind, EventKind::Modified);
+    assert_eq!(event.metadata.unwrap().len, 14);
+
+    drop(file);
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let event = m.try_next_message().unwrap();
+    assert_eq!(event.kind, EventKind::Removed);
+    assert!(event.metadata.is_none());
+}
+
+#[test]
+pub fn test_monitor_many() {
+    let app_dir = tempfile::tempdir().unwrap();
+    let nginx_dir = tempfile::tempdir().unwrap();
+    let missing_dir = app_dir.path().join("missing");
+
+    let app = log_file(&app_dir);
+
+    let mut m = monitor::Monitor::create_many(&[
+        app_dir.path(),
+        nginx_dir.path(),
+        app_dir.path(),
+        &missing_dir,
+    ])
+    .unwrap();
+
+    let event = m.try_next_message().unwrap();
+    assert_eq!(event.kind, EventKind::Created);
+    assert_eq!(event.path, app.path());
+    assert!(m.try_next_message().is_none());
+
+    let nginx = log_file(&nginx_dir);
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let event = m.try_next_message().unwrap();
+    assert_eq!(event.kind, EventKind::Created);
+    assert_eq!(event.path, nginx.path());
+    assert!(m.try_next_message().is_none());
+
+    assert!(monitor::Monitor::create_many(&[&missing_dir]).is_err());
+}
+
+#[cfg(unix)]
+#[test]
+pub fn test_monitor_symlink() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let elsewhere = tempfile::tempdir().unwrap();
+
+    let target = elsewhere.path().join("real.txt");
+    std::fs::write(&target, b"Line A\n").unwrap();
+
+    let link = temp_dir.path().join("linked.log");
+    std::os::unix::fs::symlink(&target, &link).unwrap();
+
+    let mut m = monitor::Monitor::create(&temp_dir).unwrap();
+
+    let event = m.try_next_message().unwrap();
+    assert_eq!(event.kind, EventKind::Created);
+    assert_eq!(event.path, link);
+
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(&target)
+        .unwrap()
+        .write_all(b"Line B\n")
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let event = m.try_next_message().unwrap();
+    assert_eq!(event.kind, EventKind::Modified);
+    assert_eq!(event.path, link);
+    assert_eq!(event.metadata.unwrap().len, 14);
+}
+
+#[test]
+pub fn test_monitor_glob() {
+    let temp_dir = tempfile::tempdir().unwrap();
+
+    for name in ["app-1.log", "other.log", "app-2.txt"] {
+        std::fs::write(temp_dir.path().join(name), b"Line A\n").unwrap();
+    }
+
+    let names = |pattern| {
+        let mut m = monitor::Monitor::create_with_glob(&temp_dir, pattern).unwrap();
+        let mut names = std::iter::from_fn(|| m.try_next_message())
+            .map(|ev| ev.path.file_name().unwrap().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    };
+
+    assert_eq!(names("*.log"), ["app-1.log", "other.log"]);
+    assert_eq!(names("app-*.log"), ["app-1.log"]);
+
+    let mut m = monitor::Monitor::create_with_glob(&temp_dir, "app-*.log").unwrap();
+    assert!(m.try_next_message().is_some());
+
+    std::fs::write(temp_dir.path().join("other-2.log"), b"Line A\n").unwrap();
+    std::fs::write(temp_dir.path().join("app-3.log"), b"Line A\n").unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let event = m.try_next_message().unwrap();
+    assert_eq!(event.kind, EventKind::Created);
+    assert_eq!(event.path, temp_dir.path().join("app-3.log"));
+
+    assert!(monitor::Monitor::create_with_glob(&temp_dir, "[").is_err());
+}
+
+#[test]
+pub fn test_monitor_shutdown() {
+    let temp_dir = tempfile::tempdir().unwrap();
+
+    let mut file = log_file(&temp_dir);
+
+    for _ in 0..5 {
+        let m = monitor::Monitor::create(&temp_dir).unwrap();
+        let pending = m.shutdown();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].kind, EventKind::Created);
+    }
+
+    let mut m = monitor::Monitor::create(&temp_dir).unwrap();
+    assert_eq!(m.try_next_message().unwrap().kind, EventKind::Created);
+
+    // The watchers of the monitors shut down do not report anything anymore.
+    file.write_all(b"Line A\n").unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let event = m.try_next_message().unwrap();
+    assert_eq!(event.kind, EventKind::Modified);
+    assert_eq!(event.path, file.path());
+    assert!(m.try_next_message().is_none());
+
+    file.write_all(b"Line B\n").unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let pending = m.shutdown();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].kind, EventKind::Modified);
+}
+
+#[test]
+pub fn test_monitor_size_limits() {
+    let temp_dir = tempfile::tempdir().unwrap();
+
+    let mut small = log_file(&temp_dir);
+    small.write_all(b"Line A\n").unwrap();
+
+    let mut large = log_file(&temp_dir);
+    large.write_all(&[b'x'; 100]).unwrap();
+
+    let mut m = monitor::Monitor::builder()
+        .size_limits(10, 1_000)
+        .watch(&[&temp_dir])
+        .unwrap();
+
+    let event = m.try_next_message().unwrap();
+    assert_eq!(event.kind, EventKind::Created);
+    assert_eq!(event.path, large.path());
+    assert!(m.try_next_message().is_none());
+
+    // Grows past the minimum.
+    small.write_all(b"Line B\n").unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    small.write_all(b"Line C\n").unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    // Grows past the maximum.
+    large.write_all(&[b'x'; 1_000]).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    large.write_all(b"Line D\n").unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let events = std::iter::from_fn(|| m.try_next_message())
+        .map(|ev| (ev.kind, ev.path))
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        events,
+        [
+            (EventKind::Created, small.path().to_path_buf()),
+            (EventKind::Modified, small.path().to_path_buf()),
+            (EventKind::Removed, large.path().to_path_buf()),
+        ]
+    );
+}
+
+#[test]
+pub fn test_monitor_builder() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(temp_dir.path().join("sub")).unwrap();
+
+    for (name, content) in [
+        ("app-1.log", "Line A\n"),
+        ("app-2.log", ""),
+        ("other.log", "Line A\n"),
+        ("sub/app-3.log", "Line A\n"),
+    ] {
+        std::fs::write(temp_dir.path().join(name), content).unwrap();
+    }
+
+    let mut m = monitor::Monitor::builder()
+        .glob("app-*.log")
+        .unwrap()
+        .recursive()
+        .size_limits(1, 1_000)
+        .watch(&[&temp_dir])
+        .unwrap();
+
+    let mut names = std::iter::from_fn(|| m.try_next_message())
+        .map(|ev| ev.path.file_name().unwrap().to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    names.sort();
+
+    assert_eq!(names, ["app-1.log", "app-3.log"]);
+}
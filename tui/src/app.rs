@@ -1,11 +1,22 @@
-use std::{io::Stdout, path::Path};
+use std::{
+    io::Stdout,
+    time::{Duration, Instant},
+};
 
 use crossterm::event::{self};
+use ratatui::layout::Rect;
+use time::UtcOffset;
 
 use crate::{
-    repository::Repository,
+    repository::{Age, Repository, RepositoryError, Source},
+    session,
+    theme::Theme,
+    timestamp,
     utils::KeyEventExt,
-    widgets::{FileList, FileListState, FileView, FileViewState, KeyEventHandler},
+    widgets::{
+        FileList, FileListSort, FileListState, FileView, FileViewState, Help, KeyEventHandler,
+        MouseEventHandler, NumberFormat, TailOnOpen,
+    },
 };
 
 type Terminal = ratatui::Terminal<ratatui::backend::CrosstermBackend<Stdout>>;
@@ -14,23 +25,75 @@ pub struct App;
 
 type Continue = bool;
 
+/// The ages in the file list change every second, even if nothing else does.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Following all files switches to another file at most this often.
+const FOLLOW_ALL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Focuses the file modified last, see [`AppState::follow_all`].
+struct FollowAll {
+    switched_at: Option<Instant>,
+    /// The file to switch to once the interval since the last switch passes.
+    next: Option<String>,
+}
+
 impl App {
-    pub fn run(terminal: &mut Terminal, target_dir: &Path) -> std::io::Result<()> {
-        let mut state = AppState::new(target_dir);
+    /// Starts with the file named `open`, if given, instead of the file list.
+    /// The ages of the files are the times of their last lines read by `age_parser`.
+    /// The removed files stay in the file list with `keep_removed`.
+    /// The merged files are interleaved by the timestamps `merge_parser` reads.
+    #[allow(clippy::too_many_arguments)] // The options given on the command line.
+    pub fn run(
+        terminal: &mut Terminal,
+        source: &Source,
+        open: Option<String>,
+        tail_on_open: TailOnOpen,
+        number_format: NumberFormat,
+        theme: Theme,
+        utc_offset: UtcOffset,
+        poll_interval: Duration,
+        age_parser: Option<timestamp::Parser>,
+        merge_parser: timestamp::Parser,
+        keep_removed: bool,
+    ) -> std::io::Result<()> {
+        let age = Age {
+            parser: age_parser,
+            utc_offset,
+        };
+        let files = FileViewState::with_tail_on_open(tail_on_open)
+            .number_format(number_format)
+            .merge_format(merge_parser, utc_offset);
+        let mut state = AppState::new(source, open, files, theme, age, keep_removed)
+            .map_err(std::io::Error::other)?;
 
-        while Self::handle_key_events(&mut state)? {
-            state.update();
+        while Self::handle_key_events(&mut state, poll_interval)? {
+            if state.update() {
+                terminal.draw(|f| state.draw(f))?;
+            }
+        }
 
-            terminal.draw(|f| state.draw(f))?;
+        if let Some(target_dir) = source.dir() {
+            if let Err(error) = session::save(target_dir, &state.files.session()) {
+                tracing::warn!(%error, "Failed to save the session");
+            }
         }
 
         Ok(())
     }
 
-    fn handle_key_events(state: &mut AppState) -> std::io::Result<Continue> {
-        if event::poll(std::time::Duration::from_millis(16))? {
-            if let event::Event::Key(key) = event::read()? {
-                return Ok(state.handle_key_event(&key));
+    fn handle_key_events(
+        state: &mut AppState,
+        poll_interval: Duration,
+    ) -> std::io::Result<Continue> {
+        if event::poll(poll_interval)? {
+            // Resizing needs a redraw as well.
+            state.dirty = true;
+
+            match event::read()? {
+                event::Event::Key(key) => return Ok(state.handle_key_event(&key)),
+                event::Event::Mouse(mouse) => state.handle_mouse_event(mouse),
+                _ => {}
             }
         }
         Ok(true)
@@ -40,33 +103,138 @@ impl App {
 pub struct AppState {
     repo: Repository,
     file_list: Option<FileListState>,
+    /// The sort of the file list, it is kept when the list is closed.
+    file_list_sort: FileListSort,
     files: FileViewState,
+    /// The help popup is shown over everything else.
+    show_help: bool,
+    theme: Theme,
+    /// Offset the times are shown in.
+    utc_offset: UtcOffset,
+    /// Switches to the file modified last, toggled by `A`.
+    follow_all: Option<FollowAll>,
+    /// An event was handled since the last draw.
+    dirty: bool,
+    /// The repository generation shown by the last draw.
+    generation: u64,
+    drawn_at: Instant,
+    /// The frame size of the last draw, the views learn their height from it.
+    size: Rect,
 }
 
 impl AppState {
-    fn new(target_dir: &Path) -> Self {
-        Self {
-            repo: Repository::new(target_dir.to_owned()),
-            file_list: Option::default(),
-            files: FileViewState::default(),
+    fn new(
+        source: &Source,
+        open: Option<String>,
+        mut files: FileViewState,
+        theme: Theme,
+        age: Age,
+        keep_removed: bool,
+    ) -> Result<Self, RepositoryError> {
+        for tab in source.dir().map(session::load).unwrap_or_default() {
+            files.restore(tab);
         }
+        if let Some(name) = open {
+            files.open_name(name);
+        }
+
+        Ok(Self {
+            repo: Repository::new(source, age, keep_removed)?,
+            file_list: Option::default(),
+            file_list_sort: FileListSort::default(),
+            files,
+            show_help: false,
+            theme,
+            utc_offset: age.utc_offset,
+            follow_all: None,
+            dirty: true,
+            generation: 0,
+            drawn_at: Instant::now(),
+            size: Rect::default(),
+        })
     }
 
     fn draw(&mut self, frame: &mut ratatui::Frame) {
-        frame.render_stateful_widget(FileView {}, frame.size(), &mut self.files);
+        self.drawn_at = Instant::now();
+
+        // The lines are read for the old height, read them again.
+        if self.size != frame.size() {
+            self.size = frame.size();
+            self.dirty = true;
+        }
+
+        frame.render_stateful_widget(
+            FileView { theme: self.theme },
+            frame.size(),
+            &mut self.files,
+        );
 
         if let Some(state) = self.file_list.as_mut() {
-            frame.render_stateful_widget(FileList {}, frame.size(), state);
+            frame.render_stateful_widget(
+                FileList {
+                    theme: self.theme,
+                    utc_offset: self.utc_offset,
+                },
+                frame.size(),
+                state,
+            );
+        }
+
+        if self.show_help {
+            frame.render_widget(Help { theme: self.theme }, frame.size());
         }
     }
 
     fn handle_key_event(&mut self, event: &event::KeyEvent) -> Continue {
+        // Typed text is not a command.
+        if let Some(state) = self.file_list.as_mut().filter(|state| state.is_prompting()) {
+            state.handle_key_event(event);
+            return true;
+        }
+        if self.file_list.is_none() && self.files.is_prompting() {
+            self.files.handle_key_event(event);
+            return true;
+        }
+
         if event.has_pressed('q') {
             return false;
         }
 
+        if self.show_help {
+            if event.has_pressed('?')
+                || (event::KeyEventKind::Press, event::KeyCode::Esc) == (event.kind, event.code)
+            {
+                self.show_help = false;
+            }
+            return true;
+        }
+
+        if event.has_pressed('?') {
+            self.show_help = true;
+            return true;
+        }
+
+        if event.has_pressed('A') && self.file_list.is_none() {
+            self.follow_all = match self.follow_all {
+                Some(_) => None,
+                None => Some(FollowAll {
+                    switched_at: None,
+                    next: None,
+                }),
+            };
+            self.files.show_message(
+                if self.follow_all.is_some() {
+                    "Following the file modified last"
+                } else {
+                    "Stopped following the file modified last"
+                }
+                .to_string(),
+            );
+            return true;
+        }
+
         if event.has_pressed('o') && self.file_list.is_none() {
-            self.file_list = FileListState::default().into();
+            self.file_list = FileListState::new(self.file_list_sort).into();
         } else if (event::KeyEventKind::Press, event::KeyCode::Esc) == (event.kind, event.code)
             && self.file_list.is_some()
             && !self.files.is_empty()
@@ -75,8 +243,10 @@ impl AppState {
         }
 
         if let Some(state) = self.file_list.as_mut() {
-            if let Some(info) = state.handle_key_event(event) {
-                self.files.push(info);
+            let action = state.handle_key_event(event);
+            self.file_list_sort = state.sort();
+            if let Some((info, mode)) = action {
+                self.files.open(info, mode);
                 self.file_list = None;
             }
         } else {
@@ -86,17 +256,66 @@ impl AppState {
         true
     }
 
-    fn update(&mut self) {
+    fn handle_mouse_event(&mut self, event: event::MouseEvent) {
+        if self.show_help {
+            return;
+        }
+
+        if let Some(state) = self.file_list.as_mut() {
+            state.handle_mouse_event(event);
+        } else {
+            self.files.handle_mouse_event(event);
+        }
+    }
+
+    /// Switches to the file modified last, unless it switched less than an interval ago,
+    /// so files modified in turns do not switch back and forth on every line.
+    fn follow_all(&mut self) {
+        let updated = self.repo.take_updates().pop();
+        let Some(follow) = self.follow_all.as_mut() else {
+            return;
+        };
+
+        if updated.is_some() {
+            follow.next = updated;
+        }
+        if follow
+            .switched_at
+            .is_some_and(|at| at.elapsed() < FOLLOW_ALL_INTERVAL)
+        {
+            return;
+        }
+
+        if let Some(name) = follow.next.take() {
+            follow.switched_at = Some(Instant::now());
+            self.files.follow(name);
+        }
+    }
+
+    /// Returns `false` if nothing changed since the last draw.
+    fn update(&mut self) -> bool {
+        let generation = self.repo.generation();
+        if !self.dirty
+            && generation == self.generation
+            && self.drawn_at.elapsed() < REFRESH_INTERVAL
+        {
+            return false;
+        }
+        self.dirty = false;
+        self.generation = generation;
+
+        self.follow_all();
+
         if self.file_list.is_none() && self.files.is_empty() {
-            self.file_list = FileListState::default().into();
+            self.file_list = FileListState::new(self.file_list_sort).into();
         }
 
         if let Some(state) = self.file_list.as_mut() {
             state.update(&self.repo);
-        };
+        }
 
         self.files.update(&self.repo);
 
-        // TODO Updated file is not rendered
+        true
     }
 }
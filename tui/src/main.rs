@@ -3,28 +3,51 @@ use std::{
     ffi::OsStr,
     io::{stdout, Result, Stdout},
     path::{Path, PathBuf},
+    time::Duration,
 };
 
 use crossterm::{
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    event::{
+        DisableMouseCapture, EnableMouseCapture, KeyboardEnhancementFlags,
+        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
+    terminal::{
+        disable_raw_mode, enable_raw_mode, supports_keyboard_enhancement, EnterAlternateScreen,
+        LeaveAlternateScreen,
+    },
     ExecutableCommand,
 };
 use ratatui::prelude::{CrosstermBackend, Terminal};
+use time::{macros::format_description, UtcOffset};
 use tracing_subscriber::util::SubscriberInitExt;
 
 mod app;
 mod repository;
+mod session;
+mod theme;
+mod timestamp;
 mod utils;
 mod widgets;
 
-use crate::app::App;
+use crate::{
+    app::App,
+    repository::Source,
+    theme::Theme,
+    widgets::{NumberFormat, TailOnOpen},
+};
 
-fn main() -> Result<()> {
-    let Some(target_dir) = target_dir_from_args() else {
+fn main() {
+    let Some(args) = Args::parse(args().skip(1)) else {
         print_usage();
-        return Ok(());
+        return;
     };
 
+    // The local offset is only available while the process is single-threaded.
+    let utc_offset = args
+        .utc_offset
+        .or_else(|| UtcOffset::current_local_offset().ok())
+        .unwrap_or(UtcOffset::UTC);
+
     tracing_subscriber::fmt()
         .with_writer(std::io::stderr)
         .with_max_level(tracing::Level::DEBUG)
@@ -32,7 +55,27 @@ fn main() -> Result<()> {
         .finish()
         .init();
 
-    with_terminal(|terminal| App::run(terminal, &target_dir))
+    let theme = args.theme.as_deref().map(Theme::load).unwrap_or_default();
+
+    // Printed once the terminal is restored.
+    if let Err(error) = with_terminal(|terminal| {
+        App::run(
+            terminal,
+            &args.source,
+            args.open,
+            args.tail_on_open,
+            args.number_format,
+            theme,
+            utc_offset,
+            args.poll_interval,
+            args.age,
+            args.merge_format,
+            args.keep_removed,
+        )
+    }) {
+        eprintln!("{error}");
+        std::process::exit(1);
+    }
 }
 
 fn with_terminal<F>(f: F) -> Result<()>
@@ -40,29 +83,155 @@ where
     F: FnOnce(&mut Terminal<CrosstermBackend<Stdout>>) -> Result<()>,
 {
     stdout().execute(EnterAlternateScreen)?;
+    stdout().execute(EnableMouseCapture)?;
     enable_raw_mode()?;
+
+    // Lets Enter be told apart from Shift+Enter and Ctrl+Enter.
+    let enhanced_keyboard = supports_keyboard_enhancement().unwrap_or(false);
+    if enhanced_keyboard {
+        stdout().execute(PushKeyboardEnhancementFlags(
+            KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES,
+        ))?;
+    }
+
     let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
     terminal.clear()?;
 
     let result = f(&mut terminal);
 
+    if enhanced_keyboard {
+        stdout().execute(PopKeyboardEnhancementFlags)?;
+    }
+    stdout().execute(DisableMouseCapture)?;
     stdout().execute(LeaveAlternateScreen)?;
     disable_raw_mode()?;
 
     result
 }
 
-fn target_dir_from_args() -> Option<PathBuf> {
-    args()
-        .nth(1)
-        .map(PathBuf::from)
-        .filter(|p| p.exists())
-        .filter(|p| p.is_dir())
+/// About 60 frames per second.
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(16);
+
+struct Args {
+    source: Source,
+    /// The file to open instead of showing the file list.
+    open: Option<String>,
+    /// Where the opened files start, at the top by default.
+    tail_on_open: TailOnOpen,
+    /// How the line numbers are written, plain decimal by default.
+    number_format: NumberFormat,
+    theme: Option<PathBuf>,
+    /// Offset to show the times in, the local one by default.
+    utc_offset: Option<UtcOffset>,
+    /// How long to wait for input before checking the files again.
+    poll_interval: Duration,
+    /// Reads the ages of the files from their last lines, the modification times without it.
+    age: Option<timestamp::Parser>,
+    /// Reads the timestamps the merged files are interleaved by, any known format by default.
+    merge_format: timestamp::Parser,
+    /// Lists the removed files until they are created again.
+    keep_removed: bool,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Option<Self> {
+        let mut source = None;
+        let mut open = None;
+        let mut tail_on_open = TailOnOpen::Off;
+        let mut number_format = NumberFormat::Plain;
+        let mut theme = None;
+        let mut utc_offset = None;
+        let mut poll_interval = DEFAULT_POLL_INTERVAL;
+        let mut age = Some(timestamp::any as timestamp::Parser);
+        let mut merge_format = timestamp::any as timestamp::Parser;
+        let mut keep_removed = false;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--open" => open = Some(args.next()?),
+                "--tail-on-open" => tail_on_open = TailOnOpen::Page,
+                arg if arg.starts_with("--tail-on-open=") => {
+                    tail_on_open = TailOnOpen::Lines(arg["--tail-on-open=".len()..].parse().ok()?);
+                }
+                "--number-format" => {
+                    number_format = match args.next()?.as_str() {
+                        "plain" => NumberFormat::Plain,
+                        "grouped" => NumberFormat::Grouped,
+                        "offset" => NumberFormat::Offset,
+                        _ => return None,
+                    };
+                }
+                "--age" => {
+                    age = match args.next()?.as_str() {
+                        "mtime" => None,
+                        name => Some(timestamp::parser(name)?),
+                    };
+                }
+                "--merge-format" => merge_format = timestamp::parser(&args.next()?)?,
+                "--keep-removed" => keep_removed = true,
+                "--theme" => theme = Some(PathBuf::from(args.next()?)),
+                "--utc-offset" => utc_offset = Some(parse_utc_offset(&args.next()?)?),
+                "--poll-interval" => {
+                    poll_interval = Duration::from_millis(args.next()?.parse().ok()?);
+                }
+                "-" => source = Some(Source::Stdin),
+                _ => source = Some(Source::Dir(PathBuf::from(arg))),
+            }
+        }
+
+        // A file is opened in the directory it is in.
+        if let Some(Source::Dir(path)) = &mut source {
+            if path.is_file() {
+                // The monitor watches only `*.log` files, any other would never be listed.
+                if !path
+                    .extension()
+                    .is_some_and(|extension| extension.eq_ignore_ascii_case("log"))
+                {
+                    return None;
+                }
+                open = Some(utils::file_name(path)?);
+                path.pop();
+                if path.as_os_str().is_empty() {
+                    path.push(".");
+                }
+            }
+        }
+
+        source
+            .filter(|source| source.dir().is_none_or(Path::is_dir))
+            .filter(|source| source.dir().is_some() || open.is_none())
+            .map(|source| Self {
+                source,
+                open,
+                tail_on_open,
+                number_format,
+                theme,
+                utc_offset,
+                poll_interval,
+                age,
+                merge_format,
+                keep_removed,
+            })
+    }
+}
+
+/// Accepts `+07`, `-03:30` and `Z`.
+fn parse_utc_offset(s: &str) -> Option<UtcOffset> {
+    if s.eq_ignore_ascii_case("z") || s.eq_ignore_ascii_case("utc") {
+        return Some(UtcOffset::UTC);
+    }
+
+    UtcOffset::parse(
+        s,
+        format_description!("[offset_hour sign:mandatory]:[offset_minute]"),
+    )
+    .or_else(|_| UtcOffset::parse(s, format_description!("[offset_hour sign:mandatory]")))
+    .ok()
 }
 
 fn print_usage() {
     eprintln!(
-        "Usage: {} <target-dir>",
+        "Usage: {} [--theme <theme.toml>] [--utc-offset <+HH:MM>] [--poll-interval <ms>] [--open <file-name>] [--tail-on-open[=<lines>]] [--number-format <plain | grouped | offset>] [--age <log | iso8601 | syslog | mtime>] [--merge-format <log | iso8601 | syslog>] [--keep-removed] <target-dir | file.log | ->",
         current_exe()
             .ok()
             .as_deref()
@@ -71,3 +240,48 @@ fn print_usage() {
             .unwrap_or("<app>")
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use time::macros::offset;
+
+    use super::{parse_utc_offset, Args};
+
+    fn parse(args: &[&str]) -> Option<Args> {
+        Args::parse(args.iter().map(ToString::to_string))
+    }
+
+    #[test]
+    fn utc_offsets() {
+        assert_eq!(parse_utc_offset("+07"), Some(offset!(+07)));
+        assert_eq!(parse_utc_offset("-03:30"), Some(offset!(-03:30)));
+        assert_eq!(parse_utc_offset("Z"), Some(offset!(UTC)));
+        assert_eq!(parse_utc_offset("7"), None);
+    }
+
+    #[test]
+    fn open_a_log_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("app.LOG");
+        let text = dir.path().join("notes.txt");
+        std::fs::write(&log, "").unwrap();
+        std::fs::write(&text, "").unwrap();
+
+        let args = parse(&[log.to_str().unwrap()]).unwrap();
+        assert_eq!(args.open.as_deref(), Some("app.LOG"));
+        assert_eq!(args.source.dir(), Some(dir.path()));
+
+        // It would never be listed.
+        assert!(parse(&[text.to_str().unwrap()]).is_none());
+    }
+
+    #[test]
+    fn merge_formats() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().to_str().unwrap();
+
+        assert!(parse(&["--merge-format", "syslog", dir]).is_some());
+        assert!(parse(&["--merge-format", "[hour]:[minute]", dir]).is_none());
+        assert!(parse(&["--merge-format", "mtime", dir]).is_none());
+    }
+}
@@ -1,136 +1,890 @@
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    fmt::{Display, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
 
 use dashmap::{mapref::multiple::RefMulti, DashMap};
-use itertools::Itertools;
-use time::OffsetDateTime;
+use futures::StreamExt;
+use time::{OffsetDateTime, UtcOffset};
 use tokio::sync::{
     mpsc,
     oneshot::{self},
 };
 
-use line_cache::LineCache;
+use line_cache::{LineCache, SharedCache};
 use line_index_reader::LineIndexReader;
+pub use line_index_reader::SearchOptions;
 use monitor::Monitor;
 
-use crate::utils::{self, file_name};
+use crate::{
+    timestamp,
+    utils::{self, file_name},
+};
 
 struct Entry {
+    path: PathBuf,
     reader: Arc<LineIndexReader>,
     line_cache: Arc<LineCache>,
     updated: OffsetDateTime,
+    /// Changes when the file is indexed anew, see [`RepoLines::index_version`].
+    index_version: u64,
 }
 
-impl From<LineIndexReader> for Entry {
-    fn from(reader: LineIndexReader) -> Self {
+static NEXT_INDEX_VERSION: AtomicU64 = AtomicU64::new(0);
+
+impl Entry {
+    fn new(path: PathBuf, reader: LineIndexReader, cache: &SharedCache) -> Self {
         let reader = Arc::new(reader);
-        let line_cache = Arc::new(LineCache::new(reader.clone()));
+        let line_cache = Arc::new(LineCache::shared(reader.clone(), cache.clone()));
         Self {
+            path,
             reader,
             line_cache,
             updated: utils::now(),
+            index_version: NEXT_INDEX_VERSION.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    fn info(&self, name: String) -> FileInfo {
+        FileInfo {
+            name,
+            path: self.path.clone(),
+            last_update: self.updated,
+            number_of_lines: self.reader.len(),
+            present: true,
+        }
+    }
+}
+
+/// How the age of a file is found.
+#[derive(Debug, Clone, Copy)]
+pub struct Age {
+    /// Reads the time of the last line, the modification time is taken without it.
+    pub parser: Option<timestamp::Parser>,
+    /// The offset of the times logged without one.
+    pub utc_offset: UtcOffset,
+}
+
+impl Age {
+    /// The time of the last line, or `modified` if it has none.
+    async fn of(self, reader: &LineIndexReader, modified: Option<SystemTime>) -> OffsetDateTime {
+        let logged = match (self.parser, reader.len().checked_sub(1)) {
+            (Some(parser), Some(last)) => reader
+                .line(last)
+                .await
+                .and_then(|line| parser(&line, self.utc_offset)),
+            _ => None,
+        };
+
+        logged
+            .or_else(|| modified.map(OffsetDateTime::from))
+            .unwrap_or_else(utils::now)
+    }
+}
+
+/// Where the files come from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// The files in the directory, watched for changes.
+    Dir(PathBuf),
+    /// A single file piped into stdin, given as `-`.
+    Stdin,
+}
+
+impl Source {
+    /// The directory the session is kept in, a piped stream has none.
+    pub fn dir(&self) -> Option<&Path> {
+        match self {
+            Self::Dir(dir) => Some(dir),
+            Self::Stdin => None,
         }
     }
 }
 
+const STDIN_NAME: &str = "<stdin>";
+
+/// The lines of all files are cached together in this many bytes.
+const LINE_CACHE_CAPACITY: u64 = 512 * 1024 * 1024;
+
+/// Piped data comes without file events, the copy is checked for new lines this often.
+const STDIN_UPDATE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The most match indices sent at once while counting, fewer if the next ones are not read yet.
+const COUNT_BATCH_LEN: usize = 4_096;
+
 type LinesRequest = (Arc<LineCache>, u32, u32);
 
+enum SearchRequest {
+    Find {
+        reader: Arc<LineIndexReader>,
+        needle: String,
+        options: SearchOptions,
+        from: u32,
+        direction: SearchDirection,
+        reply: oneshot::Sender<Option<u32>>,
+    },
+    Filter {
+        reader: Arc<LineIndexReader>,
+        needle: String,
+        options: SearchOptions,
+        from: u32,
+        to: u32,
+        reply: oneshot::Sender<Matches>,
+    },
+    Count {
+        reader: Arc<LineIndexReader>,
+        needle: String,
+        options: SearchOptions,
+        from: u32,
+        to: u32,
+        reply: mpsc::UnboundedSender<Vec<u32>>,
+    },
+    Export {
+        reader: Arc<LineIndexReader>,
+        from: u32,
+        to: u32,
+        matching: Option<(String, SearchOptions)>,
+        path: PathBuf,
+        reply: oneshot::Sender<Result<u64, String>>,
+    },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RepositoryError {
+    #[error("Failed to watch the target directory: {0}")]
+    Monitor(#[from] monitor::Error),
+    #[error("Failed to start the worker: {0}")]
+    Runtime(#[from] std::io::Error),
+}
+
 pub struct Repository {
+    /// The watched directory, none for a piped stream.
+    dir: Option<PathBuf>,
     entries: Arc<DashMap<String, Entry>>,
+    /// The files removed after they had been found, until they are created again.
+    removed: Arc<DashMap<String, FileInfo>>,
+    /// The removed files are listed along with the present ones.
+    keep_removed: bool,
+    /// The bytes scanned and the sizes of the files being indexed.
+    indexing: Arc<DashMap<String, (u64, u64)>>,
+    /// Bumped by the worker whenever the content or a search result changes.
+    generation: Arc<AtomicU64>,
+    /// Bumped by the worker whenever a file is listed, updated or removed.
+    list_version: Arc<AtomicU64>,
     lines_sender: mpsc::Sender<LinesRequest>,
-    #[allow(dead_code)]
-    watcher: oneshot::Sender<()>,
+    search_sender: mpsc::UnboundedSender<SearchRequest>,
+    /// Names of the files with new lines, in the order they were modified.
+    updated_files: mpsc::UnboundedReceiver<String>,
+    /// Tells the worker to stop, taken on drop.
+    stop: Option<oneshot::Sender<()>>,
+    worker: Option<std::thread::JoinHandle<()>>,
 }
 
 impl Repository {
-    pub fn new(target_dir: PathBuf) -> Self {
+    /// Lists the removed files until they are created again if `keep_removed` is set.
+    pub fn new(source: &Source, age: Age, keep_removed: bool) -> Result<Self, RepositoryError> {
+        let monitor = source.dir().map(|dir| Monitor::create(&dir)).transpose()?;
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_io()
+            .enable_time()
+            .build()?;
+
         let entries = Arc::new(DashMap::new());
         let entries_clone = entries.clone();
+        let removed = Arc::new(DashMap::new());
+        let removed_clone = removed.clone();
+        let indexing = Arc::new(DashMap::new());
+        let indexing_clone = indexing.clone();
+        let generation = Arc::new(AtomicU64::new(0));
+        let generation_clone = generation.clone();
+        let list_version = Arc::new(AtomicU64::new(0));
+        let list_version_clone = list_version.clone();
 
-        let (watcher, is_dead) = oneshot::channel::<()>();
+        let (stop, is_dead) = oneshot::channel::<()>();
         let (lines_request_sender, lines_request_receiver) = mpsc::channel::<LinesRequest>(1024);
+        let (search_request_sender, search_request_receiver) = mpsc::unbounded_channel();
+        let (updated_files_sender, updated_files) = mpsc::unbounded_channel();
 
-        std::thread::spawn(move || {
-            tokio::runtime::Builder::new_current_thread()
-                .enable_io()
-                .build()
-                .unwrap()
-                .block_on(async move {
-                    Self::worker(target_dir, is_dead, entries_clone, lines_request_receiver).await;
-                });
+        let worker = std::thread::spawn(move || {
+            runtime.block_on(async move {
+                Self::worker(
+                    monitor,
+                    age,
+                    is_dead,
+                    entries_clone,
+                    removed_clone,
+                    indexing_clone,
+                    generation_clone,
+                    list_version_clone,
+                    lines_request_receiver,
+                    search_request_receiver,
+                    updated_files_sender,
+                )
+                .await;
+            });
+            // Reading stdin blocks a thread until the next input, do not wait for it.
+            runtime.shutdown_background();
         });
 
-        Self {
+        Ok(Self {
+            dir: source.dir().map(Path::to_path_buf),
             entries,
+            removed,
+            keep_removed,
+            indexing,
+            generation,
+            list_version,
             lines_sender: lines_request_sender,
-            watcher,
-        }
+            search_sender: search_request_sender,
+            updated_files,
+            stop: Some(stop),
+            worker: Some(worker),
+        })
     }
 
+    #[allow(clippy::too_many_arguments)] // Everything the worker shares with the handle.
     async fn worker(
-        target_dir: PathBuf,
+        mut monitor: Option<Monitor>,
+        age: Age,
         mut is_dead: oneshot::Receiver<()>,
         file_entries: Arc<DashMap<String, Entry>>,
+        removed: Arc<DashMap<String, FileInfo>>,
+        indexing: Arc<DashMap<String, (u64, u64)>>,
+        generation: Arc<AtomicU64>,
+        list_version: Arc<AtomicU64>,
         mut lines_request: mpsc::Receiver<LinesRequest>,
+        mut search_request: mpsc::UnboundedReceiver<SearchRequest>,
+        updated_files: mpsc::UnboundedSender<String>,
     ) {
-        let mut monitor = Monitor::create(&target_dir).unwrap();
+        let cache = line_cache::shared_cache(LINE_CACHE_CAPACITY);
+        let piped = monitor.is_none();
+        if piped {
+            Self::index_stdin(&file_entries, &cache, age).await;
+            list_version.fetch_add(1, Ordering::Relaxed);
+        }
+        let mut stdin_updates = tokio::time::interval(STDIN_UPDATE_INTERVAL);
 
         loop {
             tokio::select! {
                     _ = &mut is_dead => {
                         break;
                     }
-                    Some(event) = monitor.next_message() => {
-                        Self::handle_event(event, &file_entries).await;
+                    Some(event) = next_event(&mut monitor) => {
+                        // Indexing a large file takes a while, do not make the drop wait for it.
+                        tokio::select! {
+                            _ = &mut is_dead => {
+                                break;
+                            }
+                            () = Self::handle_event(
+                                event,
+                                &file_entries,
+                                &removed,
+                                &indexing,
+                                &cache,
+                                age,
+                                &updated_files,
+                            ) => {}
+                        }
+                        list_version.fetch_add(1, Ordering::Relaxed);
+                        generation.fetch_add(1, Ordering::Relaxed);
+                    }
+                    _ = stdin_updates.tick(), if piped => {
+                        if Self::update_entry(&file_entries, STDIN_NAME, age, None).await {
+                            list_version.fetch_add(1, Ordering::Relaxed);
+                            generation.fetch_add(1, Ordering::Relaxed);
+                        }
                     }
                     Some((line_cache, from, to)) = lines_request.recv() => {
-                        line_cache.lines(from..to).await;
+                        line_cache.warm(from..to).await;
+                        generation.fetch_add(1, Ordering::Relaxed);
                     }
+                    Some(request) = search_request.recv() => {
+                        // Searching a large file takes a while, keep handling other requests.
+                        tokio::spawn(Self::search(request, generation.clone()));
+                    }
+            }
+        }
+
+        if let Some(monitor) = monitor {
+            monitor.shutdown();
+        }
+    }
+
+    async fn search(request: SearchRequest, generation: Arc<AtomicU64>) {
+        match request {
+            SearchRequest::Find {
+                reader,
+                needle,
+                options,
+                from,
+                direction,
+                reply,
+            } => {
+                let found = match direction {
+                    SearchDirection::Forward => match reader.find(&needle, options, from..).await {
+                        Some(index) => Some(index),
+                        None => reader.find(&needle, options, ..from).await,
+                    },
+                    SearchDirection::Backward => match reader.rfind(&needle, options, ..from).await
+                    {
+                        Some(index) => Some(index),
+                        None => reader.rfind(&needle, options, from..).await,
+                    },
+                };
+
+                _ = reply.send(found);
+            }
+            SearchRequest::Filter {
+                reader,
+                needle,
+                options,
+                from,
+                to,
+                reply,
+            } => {
+                let matches = reader
+                    .find_all(&needle, options, from..to)
+                    .await
+                    .into_iter()
+                    .map(|(index, line)| (index, line.into()))
+                    .collect();
+
+                _ = reply.send(matches);
+            }
+            SearchRequest::Count {
+                reader,
+                needle,
+                options,
+                from,
+                to,
+                reply,
+            } => {
+                let mut found = std::pin::pin!(reader
+                    .find_stream(&needle, options, from..to)
+                    .ready_chunks(COUNT_BATCH_LEN));
+
+                while let Some(batch) = found.next().await {
+                    // The search was replaced or closed, stop counting for it.
+                    if reply.send(batch).is_err() {
+                        return;
+                    }
+                    generation.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            SearchRequest::Export {
+                reader,
+                from,
+                to,
+                matching,
+                path,
+                reply,
+            } => {
+                let re = matching.map(|(needle, options)| options.regex(&needle));
+                let exported = async {
+                    // An existing file is not overwritten, it might be the one being exported.
+                    let file = tokio::fs::OpenOptions::new()
+                        .write(true)
+                        .create_new(true)
+                        .open(&path)
+                        .await?;
+                    let mut writer = tokio::io::BufWriter::new(file);
+                    reader.export(from..to, re.as_ref(), &mut writer).await
+                }
+                .await;
+
+                _ = reply.send(exported.map_err(|error| error.to_string()));
+            }
+        }
+
+        generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Takes the names of the files modified since the last call, the latest last.
+    pub fn take_updates(&mut self) -> Vec<String> {
+        std::iter::from_fn(|| self.updated_files.try_recv().ok()).collect()
+    }
+
+    /// Changes whenever the files, their lines or a search result change,
+    /// nothing new is shown while it stays the same.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Relaxed)
+    }
+
+    /// Indexes the lines appended to the file `name`, returns `false` if nothing changed.
+    /// The file was `modified` at the time, if known.
+    ///
+    /// The entry is not locked while the file is read, the UI keeps reading the other entries.
+    async fn update_entry(
+        entries: &DashMap<String, Entry>,
+        name: &str,
+        age: Age,
+        modified: Option<SystemTime>,
+    ) -> bool {
+        let Some((reader, line_cache)) = entries
+            .get(name)
+            .map(|entry| (entry.reader.clone(), entry.line_cache.clone()))
+        else {
+            return false;
+        };
+
+        let old_len = reader.len();
+        let old_byte_len = reader.byte_len();
+
+        let Ok(added) = reader.update().await else {
+            return false;
+        };
+
+        if (old_len, old_byte_len) == (reader.len(), reader.byte_len()) && added == 0 {
+            return false;
+        }
+
+        // A re-indexed file does not keep the old lines.
+        let appended = old_len.saturating_add(added) == reader.len();
+        line_cache.invalidate_from(if appended {
+            old_len.saturating_sub(1)
+        } else {
+            0
+        });
+        let updated = age.of(&reader, modified).await;
+
+        // The file might have been replaced meanwhile, its entry is not ours to update.
+        if let Some(mut entry) = entries.get_mut(name) {
+            if Arc::ptr_eq(&entry.reader, &reader) {
+                entry.updated = updated;
+                if !appended {
+                    entry.index_version = NEXT_INDEX_VERSION.fetch_add(1, Ordering::Relaxed);
+                }
             }
         }
+
+        true
+    }
+
+    /// Piped data is copied into a temporary file, see [`LineIndexReader::index_reader`].
+    async fn index_stdin(entries: &DashMap<String, Entry>, cache: &SharedCache, age: Age) {
+        match LineIndexReader::index_reader(tokio::io::stdin()).await {
+            Ok(reader) => {
+                let mut entry = Entry::new("-".into(), reader, cache);
+                entry.updated = age.of(&entry.reader, None).await;
+                entries.insert(STDIN_NAME.to_owned(), entry);
+            }
+            Err(error) => tracing::error!(%error, "Failed to read stdin"),
+        }
+    }
+
+    /// Indexes the file, listing its progress in `indexing` meanwhile.
+    async fn index(
+        path: &Path,
+        name: &str,
+        indexing: &Arc<DashMap<String, (u64, u64)>>,
+    ) -> Result<LineIndexReader, line_index_reader::Error> {
+        let progress = indexing.clone();
+        let key = name.to_owned();
+        let reader = LineIndexReader::index_with_progress(path, move |read, total| {
+            progress.insert(key.clone(), (read, total));
+        })
+        .await;
+
+        indexing.remove(name);
+        reader
     }
 
-    async fn handle_event(event: monitor::Event, entries: &Arc<DashMap<String, Entry>>) {
+    async fn handle_event(
+        event: monitor::Event,
+        entries: &Arc<DashMap<String, Entry>>,
+        removed: &DashMap<String, FileInfo>,
+        indexing: &Arc<DashMap<String, (u64, u64)>>,
+        cache: &SharedCache,
+        age: Age,
+        updated_files: &mpsc::UnboundedSender<String>,
+    ) {
         let Some(name) = file_name(&event.path) else {
             return;
         };
+        let modified = event.metadata.map(|metadata| metadata.modified);
 
         match event.kind {
             monitor::EventKind::Created => {
-                if let Ok(reader) = LineIndexReader::index(&event.path).await {
-                    entries.insert(name, reader.into());
+                if let Ok(reader) = Self::index(&event.path, &name, indexing).await {
+                    let mut entry = Entry::new(event.path, reader, cache);
+                    entry.updated = age.of(&entry.reader, modified).await;
+                    removed.remove(&name);
+                    entries.insert(name, entry);
                 };
             }
             monitor::EventKind::Modified => {
-                if let Some(mut entry) = entries.get_mut(&name) {
-                    if entry.reader.update().await.is_ok() {
-                        entry.updated = utils::now();
-                    }
+                if Self::update_entry(entries, &name, age, modified).await {
+                    _ = updated_files.send(name);
                 }
             }
             monitor::EventKind::Removed => {
-                entries.remove(&name);
+                let info = entries.remove(&name).map_or_else(
+                    || FileInfo {
+                        name: name.clone(),
+                        path: event.path,
+                        last_update: utils::now(),
+                        number_of_lines: 0,
+                        present: false,
+                    },
+                    |(name, entry)| entry.info(name),
+                );
+                removed.insert(
+                    name,
+                    FileInfo {
+                        present: false,
+                        ..info
+                    },
+                );
+            }
+            monitor::EventKind::Renamed { from, .. } => {
+                let updated =
+                    file_name(&from)
+                        .and_then(|from| entries.remove(&from))
+                        .map(|(from, entry)| {
+                            let updated = entry.updated;
+                            let info = entry.info(from.clone());
+                            removed.insert(
+                                from,
+                                FileInfo {
+                                    present: false,
+                                    ..info
+                                },
+                            );
+                            updated
+                        });
+
+                // The reader is bound to the old path, re-open it under the new name.
+                if let Ok(reader) = Self::index(&event.path, &name, indexing).await {
+                    let mut entry = Entry::new(event.path, reader, cache);
+                    entry.updated = updated.unwrap_or(entry.updated);
+                    removed.remove(&name);
+                    entries.insert(name, entry);
+                }
             }
         }
     }
 }
 
+impl Drop for Repository {
+    fn drop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            _ = stop.send(());
+        }
+        if let Some(worker) = self.worker.take() {
+            if worker.join().is_err() {
+                tracing::error!("The worker panicked");
+            }
+        }
+    }
+}
+
+/// Waits for the next file event, forever if no directory is watched.
+async fn next_event(monitor: &mut Option<Monitor>) -> Option<monitor::Event> {
+    match monitor {
+        Some(monitor) => monitor.next_message().await,
+        None => std::future::pending().await,
+    }
+}
+
 pub trait RepoList {
     fn list(&self) -> Vec<FileInfo>;
+    /// Changes whenever [`Self::list`] might, the list stays the same while it does.
+    fn list_version(&self) -> u64;
+    /// Totals over all files, taken from the indices without reading the files.
+    fn summary(&self) -> RepoSummary;
+    /// The files being indexed, they are listed once they are indexed.
+    fn indexing(&self) -> Vec<IndexProgress>;
+    /// The directory the files are found in, none for a piped stream.
+    fn dir(&self) -> Option<&Path>;
 }
 
 impl RepoList for Repository {
     fn list(&self) -> Vec<FileInfo> {
-        self.entries.iter().map(Into::into).collect()
+        self.entries
+            .iter()
+            .map(Into::into)
+            .chain(
+                self.removed
+                    .iter()
+                    .filter(|_| self.keep_removed)
+                    .map(|removed| removed.value().clone()),
+            )
+            .collect()
+    }
+
+    fn list_version(&self) -> u64 {
+        self.list_version.load(Ordering::Relaxed)
+    }
+
+    fn summary(&self) -> RepoSummary {
+        self.entries
+            .iter()
+            .fold(RepoSummary::default(), |summary, entry| RepoSummary {
+                files: summary.files + 1,
+                lines: summary.lines + u64::from(entry.value().reader.len()),
+                bytes: summary.bytes + entry.value().reader.byte_len(),
+            })
+    }
+
+    fn indexing(&self) -> Vec<IndexProgress> {
+        self.indexing
+            .iter()
+            .map(|entry| {
+                let (read, total) = *entry.value();
+                IndexProgress {
+                    name: entry.key().clone(),
+                    read,
+                    total,
+                }
+            })
+            .collect()
+    }
+
+    fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+}
+
+/// A file being indexed, `read` of its `total` bytes are scanned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexProgress {
+    pub name: String,
+    pub read: u64,
+    pub total: u64,
+}
+
+impl Display for IndexProgress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {}%",
+            self.name,
+            self.read.saturating_mul(100) / self.total.max(1)
+        )
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RepoSummary {
+    pub files: usize,
+    pub lines: u64,
+    pub bytes: u64,
+}
+
+impl Display for RepoSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} files, {} lines, {}",
+            self.files,
+            self.lines,
+            ByteSize(self.bytes)
+        )
+    }
+}
+
+/// Shows a number of bytes in the largest binary unit, e.g. `5.5 MiB`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteSize(pub u64);
+
+impl Display for ByteSize {
+    #[allow(clippy::cast_precision_loss)] // Shown with one decimal.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+        if self.0 < 1024 {
+            return write!(f, "{} B", self.0);
+        }
+
+        let mut size = self.0 as f64 / 1024.0;
+        let mut unit = UNITS[0];
+        for next in &UNITS[1..] {
+            if size < 1024.0 {
+                break;
+            }
+            size /= 1024.0;
+            unit = next;
+        }
+
+        write!(f, "{size:.1} {unit}")
+    }
+}
+
+/// Lines with their indices.
+pub type Matches = Vec<(u32, Arc<str>)>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchDirection {
+    Forward,
+    Backward,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FileInfoSortKey {
+    #[default]
+    Name,
+    LineCount,
+    /// Ascending puts the most recently updated files first.
+    LastUpdate,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SortDirection {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+impl From<SortDirection> for char {
+    fn from(direction: SortDirection) -> Self {
+        match direction {
+            SortDirection::Ascending => '▼',
+            SortDirection::Descending => '▲',
+        }
+    }
+}
+
+impl Display for SortDirection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_char(char::from(*self))
+    }
+}
+
+/// A line of a file, or its place while it is read in the background.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LineState {
+    Loaded(Arc<str>),
+    Pending,
+}
+
+impl LineState {
+    pub fn loaded(self) -> Option<Arc<str>> {
+        match self {
+            Self::Loaded(line) => Some(line),
+            Self::Pending => None,
+        }
+    }
+}
+
+/// Consecutive equal lines, a pending line is a run of its own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Run {
+    /// Index of the first line.
+    pub start: u32,
+    pub len: u32,
+    pub line: LineState,
+}
+
+impl Run {
+    pub const fn end(&self) -> u32 {
+        self.start.saturating_add(self.len)
+    }
+
+    /// Appends `next` if it continues the run with the same line, returns `false` otherwise.
+    pub fn extend(&mut self, next: &Self) -> bool {
+        let continues =
+            self.end() == next.start && self.line == next.line && self.line != LineState::Pending;
+        if continues {
+            self.len = self.len.saturating_add(next.len);
+        }
+        continues
+    }
+}
+
+/// Appends the runs to `runs`, merging the first one into the last of `runs` if it continues it.
+pub fn join_runs(runs: &mut Vec<Run>, next: impl IntoIterator<Item = Run>) {
+    for run in next {
+        if !runs.last_mut().is_some_and(|last| last.extend(&run)) {
+            runs.push(run);
+        }
     }
 }
 
 pub trait RepoLines {
-    fn lines(&self, name: &str, from: u32, to: u32) -> Box<[Arc<str>]>;
+    /// The lines in `from..to`, the ones not read yet are `Pending` until a later call.
+    fn lines(&self, name: &str, from: u32, to: u32) -> Box<[LineState]>;
+    /// The leading loaded lines of [`Self::lines`], up to the first pending one.
+    fn loaded_lines(&self, name: &str, from: u32, to: u32) -> Box<[Arc<str>]> {
+        self.lines(name, from, to)
+            .into_vec()
+            .into_iter()
+            .map_while(LineState::loaded)
+            .collect()
+    }
+    /// The lines of [`Self::lines`] with the consecutive equal ones collapsed into runs.
+    fn runs(&self, name: &str, from: u32, to: u32) -> Box<[Run]> {
+        let mut runs = vec![];
+        join_runs(
+            &mut runs,
+            (from..)
+                .zip(self.lines(name, from, to).into_vec())
+                .map(|(start, line)| Run {
+                    start,
+                    len: 1,
+                    line,
+                }),
+        );
+        runs.into_boxed_slice()
+    }
     fn total(&self, name: &str) -> u32;
+    /// The file was removed after it had been found, a file created with the same name replaces it.
+    fn is_removed(&self, name: &str) -> bool;
+    /// Changes when the file is indexed anew, the lines found in it before belong to the old content.
+    fn index_version(&self, name: &str) -> u64;
+    /// The bytes in the lines `from..to` without their endings, taken from the index.
+    fn byte_len(&self, name: &str, from: u32, to: u32) -> u64;
+    /// The position of the line in the file, taken from the index.
+    fn offset_of(&self, name: &str, line: u32) -> Option<u64>;
+    /// Looks for the nearest line containing `needle` in the background.
+    /// Forward search starts at `from`, backward search starts right before `from`,
+    /// both wrap around the end of the file.
+    fn find(
+        &self,
+        name: &str,
+        needle: &str,
+        options: SearchOptions,
+        from: u32,
+        direction: SearchDirection,
+    ) -> oneshot::Receiver<Option<u32>>;
+    /// Collects the lines in `from..to` containing `needle` in the background.
+    fn filter(
+        &self,
+        name: &str,
+        needle: &str,
+        options: SearchOptions,
+        from: u32,
+        to: u32,
+    ) -> oneshot::Receiver<Matches>;
+    /// Counts the lines in `from..to` containing `needle` in the background,
+    /// sending their indices in batches as they are found. The channel closes once all are sent.
+    fn count_matches(
+        &self,
+        name: &str,
+        needle: &str,
+        options: SearchOptions,
+        from: u32,
+        to: u32,
+    ) -> mpsc::UnboundedReceiver<Vec<u32>>;
+    /// Writes the lines in `from..to` to `path` in the background, only the ones containing
+    /// the needle if there is one. Replies with the number of bytes written.
+    /// Fails if `path` exists, the file being exported included.
+    fn export(
+        &self,
+        name: &str,
+        from: u32,
+        to: u32,
+        matching: Option<(&str, SearchOptions)>,
+        path: PathBuf,
+    ) -> oneshot::Receiver<Result<u64, String>>;
 }
 
 impl RepoLines for Repository {
-    fn lines(&self, name: &str, from: u32, to: u32) -> Box<[Arc<str>]> {
+    fn lines(&self, name: &str, from: u32, to: u32) -> Box<[LineState]> {
         let Some(entry) = self.entries.get(name) else {
             return Box::default();
         };
@@ -138,16 +892,20 @@ impl RepoLines for Repository {
         let lines = entry.value().line_cache.lines_opt(from..to);
 
         if lines.iter().any(Option::is_none) {
-            self.lines_sender
-                .try_send((entry.value().line_cache.clone(), from, to))
-                .unwrap();
+            // The missing lines are requested again on the next update.
+            if let Err(error) =
+                self.lines_sender
+                    .try_send((entry.value().line_cache.clone(), from, to))
+            {
+                tracing::warn!(%error, "Dropped the lines request");
+            }
         }
 
         lines
-            .iter()
-            .map_while(Clone::clone)
-            .collect_vec()
-            .into_boxed_slice()
+            .into_vec()
+            .into_iter()
+            .map(|line| line.map_or(LineState::Pending, LineState::Loaded))
+            .collect()
     }
 
     fn total(&self, name: &str) -> u32 {
@@ -156,21 +914,213 @@ impl RepoLines for Repository {
             .map(|entry| entry.value().reader.len())
             .unwrap_or_default()
     }
+
+    fn is_removed(&self, name: &str) -> bool {
+        self.removed.contains_key(name)
+    }
+
+    fn index_version(&self, name: &str) -> u64 {
+        self.entries
+            .get(name)
+            .map(|entry| entry.value().index_version)
+            .unwrap_or_default()
+    }
+
+    fn byte_len(&self, name: &str, from: u32, to: u32) -> u64 {
+        self.entries
+            .get(name)
+            .map(|entry| {
+                (from..to)
+                    .map_while(|line| entry.value().reader.line_len(line))
+                    .map(|len| len as u64)
+                    .sum()
+            })
+            .unwrap_or_default()
+    }
+
+    fn offset_of(&self, name: &str, line: u32) -> Option<u64> {
+        self.entries
+            .get(name)
+            .and_then(|entry| entry.value().reader.offset_of(line))
+    }
+
+    fn find(
+        &self,
+        name: &str,
+        needle: &str,
+        options: SearchOptions,
+        from: u32,
+        direction: SearchDirection,
+    ) -> oneshot::Receiver<Option<u32>> {
+        let (reply, receiver) = oneshot::channel();
+
+        if let Some(entry) = self.entries.get(name) {
+            // The reply is dropped if the worker is gone, the receiver sees it as closed.
+            _ = self.search_sender.send(SearchRequest::Find {
+                reader: entry.value().reader.clone(),
+                needle: needle.to_owned(),
+                options,
+                from,
+                direction,
+                reply,
+            });
+        }
+
+        receiver
+    }
+
+    fn filter(
+        &self,
+        name: &str,
+        needle: &str,
+        options: SearchOptions,
+        from: u32,
+        to: u32,
+    ) -> oneshot::Receiver<Matches> {
+        let (reply, receiver) = oneshot::channel();
+
+        if let Some(entry) = self.entries.get(name) {
+            _ = self.search_sender.send(SearchRequest::Filter {
+                reader: entry.value().reader.clone(),
+                needle: needle.to_owned(),
+                options,
+                from,
+                to,
+                reply,
+            });
+        }
+
+        receiver
+    }
+
+    fn count_matches(
+        &self,
+        name: &str,
+        needle: &str,
+        options: SearchOptions,
+        from: u32,
+        to: u32,
+    ) -> mpsc::UnboundedReceiver<Vec<u32>> {
+        let (reply, receiver) = mpsc::unbounded_channel();
+
+        if let Some(entry) = self.entries.get(name) {
+            _ = self.search_sender.send(SearchRequest::Count {
+                reader: entry.value().reader.clone(),
+                needle: needle.to_owned(),
+                options,
+                from,
+                to,
+                reply,
+            });
+        }
+
+        receiver
+    }
+
+    fn export(
+        &self,
+        name: &str,
+        from: u32,
+        to: u32,
+        matching: Option<(&str, SearchOptions)>,
+        path: PathBuf,
+    ) -> oneshot::Receiver<Result<u64, String>> {
+        let (reply, receiver) = oneshot::channel();
+
+        if let Some(entry) = self.entries.get(name) {
+            _ = self.search_sender.send(SearchRequest::Export {
+                reader: entry.value().reader.clone(),
+                from,
+                to,
+                matching: matching.map(|(needle, options)| (needle.to_owned(), options)),
+                path,
+                reply,
+            });
+        }
+
+        receiver
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct FileInfo {
     pub name: String,
+    pub path: PathBuf,
     pub last_update: OffsetDateTime,
     pub number_of_lines: u32,
+    /// `false` for a removed file, listed until it is created again.
+    pub present: bool,
 }
 
 impl From<RefMulti<'_, String, Entry>> for FileInfo {
     fn from(entry: RefMulti<String, Entry>) -> Self {
-        Self {
-            name: entry.key().clone(),
-            last_update: entry.value().updated,
-            number_of_lines: entry.value().reader.len(),
+        entry.value().info(entry.key().clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use time::UtcOffset;
+
+    use super::{Age, RepoLines, RepoList, Repository, Source};
+
+    fn age() -> Age {
+        Age {
+            parser: None,
+            utc_offset: UtcOffset::UTC,
         }
     }
+
+    #[test]
+    fn drop_while_indexing() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("large.log"),
+            "line\n".repeat(16 * 1024 * 1024),
+        )
+        .unwrap();
+
+        let repo = Repository::new(&Source::Dir(dir.path().to_path_buf()), age(), false).unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while repo.indexing().is_empty() {
+            assert!(Instant::now() < deadline, "The file is not being indexed");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        let indexing = repo.indexing.clone();
+        drop(repo);
+
+        // The indexing was stopped before the file was read through.
+        assert!(!indexing.is_empty());
+    }
+
+    #[test]
+    fn export_to_a_new_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("app.log");
+        let existing = dir.path().join("existing.txt");
+        std::fs::write(&source, "a\nb\n").unwrap();
+        std::fs::write(&existing, "kept").unwrap();
+
+        let repo = Repository::new(&Source::Dir(dir.path().to_path_buf()), age(), false).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while repo.list().is_empty() {
+            assert!(Instant::now() < deadline, "The file is not listed");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        let export = |path| repo.export("app.log", 0, 2, None, path).blocking_recv();
+
+        assert!(export(source.clone()).unwrap().is_err());
+        assert!(export(existing.clone()).unwrap().is_err());
+        assert_eq!(std::fs::read_to_string(&source).unwrap(), "a\nb\n");
+        assert_eq!(std::fs::read_to_string(&existing).unwrap(), "kept");
+
+        let new = dir.path().join("new.txt");
+        assert_eq!(export(new.clone()).unwrap(), Ok(4));
+        assert_eq!(std::fs::read_to_string(&new).unwrap(), "a\nb\n");
+    }
 }
@@ -0,0 +1,229 @@
+//! Open tabs and their positions, kept across restarts.
+//!
+//! The session is a text file in the target directory, one pane per line:
+//! the scroll offset, the comma-separated bookmarked lines if there are any,
+//! and the file name, separated by tabs. The right pane of a split tab follows
+//! the tab on a line starting with [`SPLIT_MARKER`] and a tab.
+//!
+//! The session of a read-only target directory is kept in the per-user state directory,
+//! `$XDG_STATE_HOME/logquery` or `~/.local/state/logquery`, named after the target directory.
+
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use itertools::Itertools;
+
+const FILE_NAME: &str = ".logquery-session";
+
+/// Starts the line of the right pane, the sessions without split panes skip it.
+const SPLIT_MARKER: &str = "+";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tab {
+    pub file: Pane,
+    /// The right pane.
+    pub split: Option<Pane>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pane {
+    pub name: String,
+    pub scroll_offset: u32,
+    /// Indices of the bookmarked lines, ascending.
+    pub bookmarks: Vec<u32>,
+}
+
+fn path(target_dir: &Path) -> PathBuf {
+    target_dir.join(FILE_NAME)
+}
+
+fn state_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/state")))
+        .map(|dir| dir.join("logquery"))
+}
+
+/// The session of `target_dir` in the `state_dir`, like `%var%log%app` for `/var/log/app`.
+fn state_path(state_dir: &Path, target_dir: &Path) -> PathBuf {
+    let target_dir = target_dir
+        .canonicalize()
+        .unwrap_or_else(|_| target_dir.to_path_buf());
+    state_dir.join(
+        target_dir
+            .to_string_lossy()
+            .replace(std::path::MAIN_SEPARATOR, "%"),
+    )
+}
+
+/// Reads the saved tabs, skipping the files which do not exist anymore.
+pub fn load(target_dir: &Path) -> Vec<Tab> {
+    load_from(target_dir, state_dir().as_deref())
+}
+
+/// Reads the latest of the sessions in the target and the state directories.
+fn load_from(target_dir: &Path, state_dir: Option<&Path>) -> Vec<Tab> {
+    let Some(text) = std::iter::once(path(target_dir))
+        .chain(state_dir.map(|state_dir| state_path(state_dir, target_dir)))
+        .filter(|path| path.is_file())
+        .max_by_key(|path| {
+            path.metadata()
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        })
+        .and_then(|path| std::fs::read_to_string(path).ok())
+    else {
+        return vec![];
+    };
+
+    let exists = |pane: &Pane| target_dir.join(&pane.name).is_file();
+    parse(&text)
+        .into_iter()
+        .filter_map(|mut tab| {
+            tab.split = tab.split.filter(exists);
+            // The right pane takes the place of a missing file.
+            if !exists(&tab.file) {
+                tab.file = tab.split.take()?;
+            }
+            Some(tab)
+        })
+        .collect()
+}
+
+/// Writes the tabs into the target directory, or into the state directory if that fails.
+pub fn save(target_dir: &Path, tabs: &[Tab]) -> std::io::Result<()> {
+    save_to(target_dir, state_dir().as_deref(), tabs)
+}
+
+fn save_to(target_dir: &Path, state_dir: Option<&Path>, tabs: &[Tab]) -> std::io::Result<()> {
+    let text = format(tabs);
+    std::fs::write(path(target_dir), &text).or_else(|error| {
+        let Some(state_dir) = state_dir else {
+            return Err(error);
+        };
+        tracing::debug!(%error, "Saving the session into {}", state_dir.display());
+        std::fs::create_dir_all(state_dir)?;
+        std::fs::write(state_path(state_dir, target_dir), text)
+    })
+}
+
+fn parse(text: &str) -> Vec<Tab> {
+    let mut tabs: Vec<Tab> = vec![];
+    for line in text.lines() {
+        match line.split_once('\t') {
+            Some((SPLIT_MARKER, pane)) => {
+                if let Some((tab, pane)) = tabs.last_mut().zip(parse_pane(pane)) {
+                    tab.split = Some(pane);
+                }
+            }
+            _ => tabs.extend(parse_pane(line).map(|file| Tab { file, split: None })),
+        }
+    }
+    tabs
+}
+
+fn parse_pane(line: &str) -> Option<Pane> {
+    let (offset, rest) = line.split_once('\t')?;
+
+    // The older sessions have no bookmarks.
+    let (bookmarks, name) = rest
+        .split_once('\t')
+        .and_then(|(bookmarks, name)| Some((parse_bookmarks(bookmarks)?, name)))
+        .unwrap_or((vec![], rest));
+
+    Some(Pane {
+        name: name.to_owned(),
+        scroll_offset: offset.parse().ok()?,
+        bookmarks,
+    })
+}
+
+fn parse_bookmarks(text: &str) -> Option<Vec<u32>> {
+    text.split(',').map(|line| line.parse().ok()).collect()
+}
+
+fn format(tabs: &[Tab]) -> String {
+    let mut text = String::new();
+    for tab in tabs {
+        format_pane(&mut text, &tab.file);
+        if let Some(split) = tab.split.as_ref() {
+            text.push_str(SPLIT_MARKER);
+            text.push('\t');
+            format_pane(&mut text, split);
+        }
+    }
+    text
+}
+
+fn format_pane(text: &mut String, pane: &Pane) {
+    // Writing to a string does not fail.
+    _ = write!(text, "{}\t", pane.scroll_offset);
+    if !pane.bookmarks.is_empty() {
+        _ = write!(text, "{}\t", pane.bookmarks.iter().join(","));
+    }
+    _ = writeln!(text, "{}", pane.name);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format, load_from, parse, save_to, state_path, Pane, Tab, FILE_NAME};
+
+    fn pane(name: &str, scroll_offset: u32, bookmarks: &[u32]) -> Pane {
+        Pane {
+            name: name.to_string(),
+            scroll_offset,
+            bookmarks: bookmarks.to_vec(),
+        }
+    }
+
+    fn tab(name: &str, scroll_offset: u32, bookmarks: &[u32]) -> Tab {
+        Tab {
+            file: pane(name, scroll_offset, bookmarks),
+            split: None,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let tabs = vec![
+            tab("app.log", 120, &[]),
+            tab("with space.log", 0, &[3, 140]),
+            Tab {
+                split: Some(pane("b.log", 7, &[9])),
+                ..tab("a.log", 5, &[])
+            },
+        ];
+
+        assert_eq!(parse(&format(&tabs)), tabs);
+    }
+
+    #[test]
+    fn save_into_the_state_dir() {
+        let target_dir = tempfile::tempdir().unwrap();
+        let state_dir = tempfile::tempdir().unwrap();
+        std::fs::write(target_dir.path().join("a.log"), "").unwrap();
+        // The target directory does not take the session.
+        std::fs::create_dir(target_dir.path().join(FILE_NAME)).unwrap();
+
+        let tabs = vec![tab("a.log", 3, &[1])];
+        save_to(target_dir.path(), Some(state_dir.path()), &tabs).unwrap();
+
+        assert!(state_path(state_dir.path(), target_dir.path()).is_file());
+        assert_eq!(load_from(target_dir.path(), Some(state_dir.path())), tabs);
+        assert!(load_from(target_dir.path(), None).is_empty());
+    }
+
+    #[test]
+    fn skip_malformed_lines() {
+        assert_eq!(
+            parse("12\ta.log\nno offset\nx\tb.log\n3\tc.log\n4\t1,x\td.log"),
+            [
+                tab("a.log", 12, &[]),
+                tab("c.log", 3, &[]),
+                tab("1,x\td.log", 4, &[])
+            ]
+        );
+    }
+}
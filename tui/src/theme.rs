@@ -0,0 +1,209 @@
+//! Colors of the widgets, optionally loaded from a TOML file.
+//!
+//! Every key is optional, a missing one keeps the default color:
+//!
+//! ```toml
+//! border = "dark_gray"
+//! focused_border = "gray"
+//! bookmark = "cyan"
+//! dim = "dark_gray"
+//!
+//! [highlight]
+//! fg = "yellow"
+//! bg = "blue"
+//!
+//! [search_match]
+//! fg = "black"
+//! bg = "yellow"
+//!
+//! [highlight_match]
+//! fg = "black"
+//! bg = "cyan"
+//!
+//! [levels]
+//! error = "red"
+//! warn = "yellow"
+//! info = "white"
+//! debug = "gray"
+//! trace = "dark_gray"
+//! ```
+
+use std::path::Path;
+
+use ratatui::style::{Color, Style, Stylize};
+use toml_edit::DocumentMut;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    /// The selected file, the active tab and the keys in the help.
+    pub highlight: Highlight,
+    pub border: Color,
+    /// The border of the focused pane of a split tab.
+    pub focused_border: Color,
+    /// The numbers of the bookmarked lines.
+    pub bookmark: Color,
+    /// The text found by the search.
+    pub search_match: Highlight,
+    /// The text matching the highlight pattern.
+    pub highlight_match: Highlight,
+    /// The line numbers, the removed files, the placeholders and the other secondary text.
+    pub dim: Color,
+    pub levels: Levels,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Highlight {
+    pub fg: Color,
+    pub bg: Color,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Levels {
+    pub error: Color,
+    pub warn: Color,
+    pub info: Color,
+    pub debug: Color,
+    pub trace: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            highlight: Highlight {
+                fg: Color::Yellow,
+                bg: Color::Blue,
+            },
+            border: Color::DarkGray,
+            focused_border: Color::Gray,
+            bookmark: Color::Cyan,
+            search_match: Highlight {
+                fg: Color::Black,
+                bg: Color::Yellow,
+            },
+            highlight_match: Highlight {
+                fg: Color::Black,
+                bg: Color::Cyan,
+            },
+            dim: Color::DarkGray,
+            levels: Levels {
+                error: Color::Red,
+                warn: Color::Yellow,
+                info: Color::White,
+                debug: Color::Gray,
+                trace: Color::DarkGray,
+            },
+        }
+    }
+}
+
+impl Theme {
+    /// Reads the theme, falling back to the default one if the file is invalid.
+    pub fn load(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .map_err(|error| error.to_string())
+            .and_then(|text| Self::parse(&text))
+            .unwrap_or_else(|error| {
+                tracing::warn!(%error, path = %path.display(), "Failed to load the theme");
+                Self::default()
+            })
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        let document = text
+            .parse::<DocumentMut>()
+            .map_err(|error| error.to_string())?;
+
+        let mut theme = Self::default();
+        for (key, color) in [
+            ("highlight.fg", &mut theme.highlight.fg),
+            ("highlight.bg", &mut theme.highlight.bg),
+            ("border", &mut theme.border),
+            ("focused_border", &mut theme.focused_border),
+            ("bookmark", &mut theme.bookmark),
+            ("search_match.fg", &mut theme.search_match.fg),
+            ("search_match.bg", &mut theme.search_match.bg),
+            ("highlight_match.fg", &mut theme.highlight_match.fg),
+            ("highlight_match.bg", &mut theme.highlight_match.bg),
+            ("dim", &mut theme.dim),
+            ("levels.error", &mut theme.levels.error),
+            ("levels.warn", &mut theme.levels.warn),
+            ("levels.info", &mut theme.levels.info),
+            ("levels.debug", &mut theme.levels.debug),
+            ("levels.trace", &mut theme.levels.trace),
+        ] {
+            let Some(value) = key
+                .split('.')
+                .try_fold(document.as_item(), |item, part| item.get(part))
+            else {
+                continue;
+            };
+
+            let name = value
+                .as_str()
+                .ok_or_else(|| format!("`{key}` is not a string"))?;
+            *color = name
+                .parse()
+                .map_err(|_| format!("Unknown color `{name}` for `{key}`"))?;
+        }
+
+        Ok(theme)
+    }
+
+    pub fn highlight_style(&self) -> Style {
+        Style::default()
+            .bold()
+            .fg(self.highlight.fg)
+            .bg(self.highlight.bg)
+    }
+}
+
+impl Highlight {
+    pub fn style(self) -> Style {
+        Style::default().fg(self.fg).bg(self.bg)
+    }
+}
+
+impl Levels {
+    pub const fn colors(&self) -> [(&'static str, Color); 5] {
+        [
+            ("ERROR", self.error),
+            ("WARN", self.warn),
+            ("INFO", self.info),
+            ("DEBUG", self.debug),
+            ("TRACE", self.trace),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keep_defaults_for_missing_keys() {
+        let theme =
+            Theme::parse("border = \"#102030\"\n[levels]\nerror = \"light red\"\n").unwrap();
+
+        assert_eq!(theme.border, Color::Rgb(0x10, 0x20, 0x30));
+        assert_eq!(theme.levels.error, Color::LightRed);
+        assert_eq!(theme.highlight, Theme::default().highlight);
+    }
+
+    #[test]
+    fn restyle_matches_and_dimmed_text() {
+        let theme =
+            Theme::parse("dim = \"gray\"\n[search_match]\nbg = \"light yellow\"\n").unwrap();
+
+        assert_eq!(theme.dim, Color::Gray);
+        assert_eq!(theme.search_match.fg, Color::Black);
+        assert_eq!(theme.search_match.bg, Color::LightYellow);
+        assert_eq!(theme.highlight_match, Theme::default().highlight_match);
+    }
+
+    #[test]
+    fn reject_invalid_colors() {
+        assert!(Theme::parse("[highlight]\nfg = \"nope\"\n").is_err());
+        assert!(Theme::parse("border = 1\n").is_err());
+        assert!(Theme::parse("border = ").is_err());
+    }
+}
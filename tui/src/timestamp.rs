@@ -0,0 +1,126 @@
+//! Timestamps at the start of the log lines, the age of a file is taken from its last line.
+
+use time::{
+    format_description::BorrowedFormatItem, macros::format_description, parsing::Parsed,
+    OffsetDateTime, PrimitiveDateTime, UtcOffset,
+};
+
+/// Reads the time of a line, a time without an offset is taken in the given one.
+pub type Parser = fn(&str, UtcOffset) -> Option<OffsetDateTime>;
+
+/// `2024-06-01T12:00:00.123+02:00`, the date and the time may be separated by a space,
+/// the fraction and the offset are optional.
+const ISO8601: &[BorrowedFormatItem<'_>] = format_description!(
+    "[year]-[month]-[day][first [T][ ]][hour]:[minute]:[second][optional [.[subsecond]]]"
+);
+
+const ISO8601_OFFSET: &[BorrowedFormatItem<'_>] =
+    format_description!("[offset_hour sign:mandatory][optional [:]][offset_minute]");
+
+/// `Jun  1 12:00:00`, the year is not logged.
+const SYSLOG: &[BorrowedFormatItem<'_>] =
+    format_description!("[month repr:short] [day padding:space] [hour]:[minute]:[second]");
+
+/// The parser by its name on the command line.
+pub fn parser(name: &str) -> Option<Parser> {
+    match name {
+        "log" => Some(any),
+        "iso8601" => Some(iso8601),
+        "syslog" => Some(syslog),
+        _ => None,
+    }
+}
+
+/// Tries all the known formats.
+pub fn any(line: &str, offset: UtcOffset) -> Option<OffsetDateTime> {
+    iso8601(line, offset).or_else(|| syslog(line, offset))
+}
+
+pub fn iso8601(line: &str, offset: UtcOffset) -> Option<OffsetDateTime> {
+    let line = line.strip_prefix('[').unwrap_or(line);
+
+    let mut parsed = Parsed::new();
+    let rest = parsed.parse_items(line.as_bytes(), ISO8601).ok()?;
+    let time = PrimitiveDateTime::try_from(parsed).ok()?;
+
+    let offset = if rest.first() == Some(&b'Z') {
+        UtcOffset::UTC
+    } else {
+        let mut parsed = Parsed::new();
+        parsed
+            .parse_items(rest, ISO8601_OFFSET)
+            .ok()
+            .and_then(|_| UtcOffset::try_from(parsed).ok())
+            .unwrap_or(offset)
+    };
+
+    Some(time.assume_offset(offset))
+}
+
+/// The year is the one that does not put the time into the future.
+pub fn syslog(line: &str, offset: UtcOffset) -> Option<OffsetDateTime> {
+    let now = crate::utils::now().to_offset(offset);
+
+    [now.year(), now.year() - 1].into_iter().find_map(|year| {
+        let mut parsed = Parsed::new();
+        parsed.parse_items(line.as_bytes(), SYSLOG).ok()?;
+        parsed.set_year(year)?;
+        let time = PrimitiveDateTime::try_from(parsed)
+            .ok()?
+            .assume_offset(offset);
+        // Clocks drift, a bit ahead is still now.
+        (time <= now + time::Duration::DAY).then_some(time)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use time::{
+        macros::{datetime, offset},
+        Duration,
+    };
+
+    use super::{any, iso8601, syslog};
+
+    #[test]
+    fn iso8601_times() {
+        let local = offset!(+03);
+
+        assert_eq!(
+            iso8601("2024-06-01T12:00:00Z INFO started", local),
+            Some(datetime!(2024-06-01 12:00:00 UTC))
+        );
+        assert_eq!(
+            iso8601("2024-06-01 12:00:00.250+02:00 started", local),
+            Some(datetime!(2024-06-01 12:00:00.250 +02))
+        );
+        assert_eq!(
+            iso8601("[2024-06-01 12:00:00] started", local),
+            Some(datetime!(2024-06-01 12:00:00 +03))
+        );
+        assert_eq!(iso8601("started at 2024-06-01 12:00:00", local), None);
+    }
+
+    #[test]
+    fn syslog_times() {
+        let now = crate::utils::now();
+        let format = time::macros::format_description!(
+            "[month repr:short] [day padding:space] [hour]:[minute]:[second]"
+        );
+
+        let hour_ago = (now - Duration::HOUR).replace_nanosecond(0).unwrap();
+        let line = format!("{} host app[1]: started", hour_ago.format(format).unwrap());
+        assert_eq!(syslog(&line, time::UtcOffset::UTC), Some(hour_ago));
+        assert_eq!(any(&line, time::UtcOffset::UTC), Some(hour_ago));
+
+        // A week ahead was logged last year.
+        let week_ahead = (now + Duration::WEEK).replace_nanosecond(0).unwrap();
+        let line = week_ahead.format(format).unwrap();
+        assert_eq!(
+            syslog(&line, time::UtcOffset::UTC).map(time::OffsetDateTime::year),
+            Some(now.year() - 1)
+        );
+
+        assert_eq!(syslog("started", time::UtcOffset::UTC), None);
+    }
+}
@@ -1,13 +1,14 @@
 use crossterm::event::{self, KeyCode, KeyEventKind};
 use ratatui::layout::{Constraint, Direction, Layout, Margin, Rect};
 
+/// Times are kept in UTC and converted to the configured offset for display.
 pub fn now() -> time::OffsetDateTime {
-    time::OffsetDateTime::now_utc().to_offset(time::macros::offset!(+07))
+    time::OffsetDateTime::now_utc()
 }
 
 pub fn file_name(path: &std::path::Path) -> Option<String> {
     path.iter()
-        .last()
+        .next_back()
         .map(std::ffi::OsStr::to_string_lossy)
         .as_ref()
         .map(std::borrow::Cow::to_string)
@@ -1,8 +1,12 @@
 mod file_list;
+mod file_table;
 mod file_tabs;
 mod file_view;
+mod help;
+mod prompt;
 mod state;
 
-pub use file_list::{FileList, FileListState};
-pub use file_view::{FileView, FileViewState};
-pub use state::KeyEventHandler;
+pub use file_list::{FileList, FileListSort, FileListState};
+pub use file_view::{FileView, FileViewState, NumberFormat, OpenMode, TailOnOpen};
+pub use help::Help;
+pub use state::{KeyEventHandler, MouseEventHandler};
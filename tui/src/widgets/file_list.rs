@@ -1,28 +1,36 @@
 use std::{
-    cmp::Ordering,
-    fmt::{Display, Write},
-    hash::{DefaultHasher, Hash, Hasher},
+    fmt::Write,
+    ops::Range,
+    path::{Path, PathBuf},
 };
 
-use crossterm::event::{KeyCode, KeyEvent, KeyEventKind};
+use crossterm::event::{
+    KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
 use itertools::Itertools;
 use ratatui::{
     layout::{Constraint, Margin},
     prelude::{Buffer, Rect},
-    style::{Style, Stylize},
+    style::{Color, Stylize},
     text::{Line, Text},
     widgets::{
-        Block, Borders, Clear, HighlightSpacing, Row, StatefulWidget, Table, TableState, Widget,
+        Block, Borders, Clear, HighlightSpacing, Paragraph, Row, StatefulWidget, Table, TableState,
+        Widget,
     },
 };
-use time::macros::format_description;
+use time::{macros::format_description, UtcOffset};
 
 use crate::{
-    repository::{FileInfo, RepoList},
+    repository::{FileInfo, FileInfoSortKey, IndexProgress, RepoList, RepoSummary, SortDirection},
+    theme::Theme,
     utils::{self, RectExt},
 };
 
-use super::KeyEventHandler;
+use super::{
+    file_table::{format_label, sort},
+    prompt::{Prompt, PromptAction, PromptState},
+    KeyEventHandler, MouseEventHandler, OpenMode,
+};
 
 const WIDTHS: [Constraint; 4] = [
     Constraint::Fill(1),    // File name
@@ -34,64 +42,141 @@ const WIDTHS: [Constraint; 4] = [
 const LABELS: [&str; 4] = ["Name", "Lines", "Age", "Last update"];
 const TITLE: &str = "File browser";
 
+/// Follows the names of the removed files.
+const REMOVED_MARKER: &str = " (removed)";
+
 const LAST_UPDATE_FORMAT: &[time::format_description::BorrowedFormatItem<'_>] =
     format_description!("[year]-[month]-[day] [hour]:[minute]:[second]");
 
 #[derive(Debug, Clone, Copy)]
-pub struct FileList {}
+pub struct FileList {
+    pub theme: Theme,
+    /// Offset the last update times are shown in.
+    pub utc_offset: UtcOffset,
+}
+
+/// The column the list is sorted by and the direction, kept while the list is closed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FileListSort {
+    pub key: FileInfoSortKey,
+    pub direction: SortDirection,
+}
+
+/// Tells whether the list needs sorting again, without taking the files from the repository.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct ListKey {
+    /// See [`RepoList::list_version`].
+    version: u64,
+    sort: FileListSort,
+    filter: String,
+}
 
 #[derive(Debug, Default, Clone)]
 pub struct FileListState {
-    hash: u64,
+    key: Option<ListKey>,
     sorted_list: Vec<FileInfo>,
-    sort_column: SortColumn,
-    sort_direction: SortDirection,
+    sort: FileListSort,
     table_state: TableState,
+    /// Where the table was drawn, to find the clicked row.
+    table_area: Rect,
+    /// Show the full paths instead of the file names.
+    show_path: bool,
+    summary: RepoSummary,
+    /// Show the totals over all files in the title.
+    show_summary: bool,
+    /// The files not listed yet, shown in the title.
+    indexing: Vec<IndexProgress>,
+    /// Only the files with the names containing it are listed.
+    filter: String,
+    /// Edits the filter, the list follows the input.
+    prompt: Option<PromptState>,
+    /// The directory the files are found in, named while there are none.
+    dir: Option<PathBuf>,
 }
 
 impl KeyEventHandler for FileListState {
-    type Action = FileInfo;
+    type Action = (FileInfo, OpenMode);
 
     fn handle_key_event(&mut self, event: &KeyEvent) -> Option<Self::Action> {
+        if let Some(prompt) = self.prompt.as_mut() {
+            match prompt.handle_key_event(event) {
+                Some(PromptAction::Submit(input)) => {
+                    self.filter = input;
+                    self.prompt = None;
+                }
+                Some(PromptAction::Cancel) => self.prompt = None,
+                None => {}
+            }
+            return None;
+        }
+
         if let Some(selected) = self.selected() {
             if (KeyEventKind::Press, KeyCode::Enter) == (event.kind, event.code) {
-                return selected.into();
+                // Terminals report the modifiers of Enter only with the keyboard enhancement on.
+                let mode = if event.modifiers.contains(KeyModifiers::CONTROL) {
+                    OpenMode::Merge
+                } else if event.modifiers.contains(KeyModifiers::SHIFT) {
+                    OpenMode::Split
+                } else {
+                    OpenMode::Tab
+                };
+                return (selected, mode).into();
             }
         }
 
         match (event.kind, event.code) {
             // File list table sorting
             (KeyEventKind::Press, KeyCode::Char('n')) => {
-                self.sort_column = SortColumn::Name;
-                self.sort_direction = SortDirection::Ascending;
+                self.sort = FileListSort {
+                    key: FileInfoSortKey::Name,
+                    direction: SortDirection::Ascending,
+                };
             }
             (KeyEventKind::Press, KeyCode::Char('N')) => {
-                self.sort_column = SortColumn::Name;
-                self.sort_direction = SortDirection::Descending;
+                self.sort = FileListSort {
+                    key: FileInfoSortKey::Name,
+                    direction: SortDirection::Descending,
+                };
             }
             (KeyEventKind::Press, KeyCode::Char('l')) => {
-                self.sort_column = SortColumn::LineCount;
-                self.sort_direction = SortDirection::Ascending;
+                self.sort = FileListSort {
+                    key: FileInfoSortKey::LineCount,
+                    direction: SortDirection::Ascending,
+                };
             }
             (KeyEventKind::Press, KeyCode::Char('L')) => {
-                self.sort_column = SortColumn::LineCount;
-                self.sort_direction = SortDirection::Descending;
+                self.sort = FileListSort {
+                    key: FileInfoSortKey::LineCount,
+                    direction: SortDirection::Descending,
+                };
             }
             (KeyEventKind::Press, KeyCode::Char('a')) => {
-                self.sort_column = SortColumn::Age;
-                self.sort_direction = SortDirection::Ascending;
+                self.sort = FileListSort {
+                    key: FileInfoSortKey::LastUpdate,
+                    direction: SortDirection::Ascending,
+                };
             }
             (KeyEventKind::Press, KeyCode::Char('A')) => {
-                self.sort_column = SortColumn::Age;
-                self.sort_direction = SortDirection::Descending;
+                self.sort = FileListSort {
+                    key: FileInfoSortKey::LastUpdate,
+                    direction: SortDirection::Descending,
+                };
+            }
+
+            (KeyEventKind::Press, KeyCode::Char('p')) => self.show_path = !self.show_path,
+            (KeyEventKind::Press, KeyCode::Char('s')) => self.show_summary = !self.show_summary,
+            (KeyEventKind::Press, KeyCode::Char('/')) => {
+                let mut prompt = PromptState::new('/');
+                prompt.set_input(&self.filter);
+                self.prompt = Some(prompt);
             }
 
-            // File list selection
-            (KeyEventKind::Press, KeyCode::Up) => {
+            // File list selection, `l` is taken by sorting so there is no `h`/`l` here.
+            (KeyEventKind::Press, KeyCode::Up | KeyCode::Char('k')) => {
                 self.table_state
                     .select(self.table_state.selected().map(|v| v.saturating_sub(1)));
             }
-            (KeyEventKind::Press, KeyCode::Down) => {
+            (KeyEventKind::Press, KeyCode::Down | KeyCode::Char('j')) => {
                 self.table_state
                     .select(self.table_state.selected().map(|v| {
                         v.saturating_add(1)
@@ -106,60 +191,176 @@ impl KeyEventHandler for FileListState {
     }
 }
 
+impl MouseEventHandler for FileListState {
+    type Action = ();
+
+    fn handle_mouse_event(&mut self, event: MouseEvent) -> Option<Self::Action> {
+        if event.kind != MouseEventKind::Down(MouseButton::Left) {
+            return None;
+        }
+
+        // Below the border, the header and its margin.
+        let rows = self.table_area.inner(&Margin::new(1, 1));
+        let first_row = rows.y + 2;
+
+        if !(rows.x..rows.right()).contains(&event.column)
+            || !(first_row..rows.bottom()).contains(&event.row)
+        {
+            return None;
+        }
+
+        let index = self.table_state.offset() + usize::from(event.row - first_row);
+        if index < self.sorted_list.len() {
+            self.table_state.select(Some(index));
+        }
+
+        None
+    }
+}
+
 impl FileListState {
+    pub fn new(sort: FileListSort) -> Self {
+        Self {
+            sort,
+            ..Self::default()
+        }
+    }
+
+    pub const fn sort(&self) -> FileListSort {
+        self.sort
+    }
+
     pub fn update(&mut self, repo: &impl RepoList) {
-        let files = repo.list();
+        self.summary = repo.summary();
+        self.indexing = repo.indexing();
+        if self.dir.as_deref() != repo.dir() {
+            self.dir = repo.dir().map(Path::to_path_buf);
+        }
 
-        let hash = {
-            let mut h = DefaultHasher::new();
-            files.hash(&mut h);
-            h.finish()
-        };
+        let filter = self
+            .prompt
+            .as_ref()
+            .map_or(self.filter.as_str(), PromptState::input);
 
-        if self.hash == hash {
+        // Only the order depends on the list, the ages are computed on every render.
+        let key = ListKey {
+            version: repo.list_version(),
+            sort: self.sort,
+            filter: filter.to_owned(),
+        };
+        if self.key.as_ref() == Some(&key) {
             return;
         }
 
+        let files = repo
+            .list()
+            .into_iter()
+            .filter(|info| info.name.contains(&key.filter))
+            .collect();
+        self.key = Some(key);
+
         let index = self
             .table_state
             .selected()
             .and_then(|s| self.sorted_list.get(s))
             .map(|info| info.name.clone());
 
-        self.sorted_list = sort(files, self.sort_column, self.sort_direction);
+        self.sorted_list = sort(files, self.sort.key, self.sort.direction);
 
         let index =
             index.and_then(|name| self.sorted_list.iter().position(|info| info.name == name));
 
-        self.table_state.select(index.or(Some(0)));
+        self.table_state
+            .select(index.or_else(|| (!self.sorted_list.is_empty()).then_some(0)));
+    }
+
+    pub const fn is_prompting(&self) -> bool {
+        self.prompt.is_some()
+    }
+
+    /// The rows shown in a table of `height` rows, keeping the selected one in view.
+    fn window(&self, height: usize) -> Range<usize> {
+        let len = self.sorted_list.len();
+        let mut offset = self.table_state.offset();
+
+        if let Some(selected) = self.table_state.selected() {
+            if selected < offset {
+                offset = selected;
+            } else if selected >= offset + height {
+                offset = (selected + 1).saturating_sub(height);
+            }
+        }
+        let offset = offset.min(len.saturating_sub(height));
+
+        offset..(offset + height).min(len)
     }
 
     fn selected(&self) -> Option<FileInfo> {
         self.sorted_list.get(self.table_state.selected()?).cloned()
     }
+
+    /// Shown in place of the rows while there are none.
+    fn empty_message(&self) -> Option<String> {
+        if !self.sorted_list.is_empty() {
+            return None;
+        }
+
+        let filter = self
+            .prompt
+            .as_ref()
+            .map_or(self.filter.as_str(), PromptState::input);
+        if !filter.is_empty() {
+            return Some(format!("No files match /{filter}"));
+        }
+        if !self.indexing.is_empty() {
+            return Some("Indexing…".to_string());
+        }
+
+        Some(self.dir.as_deref().map_or_else(
+            || "Waiting for input…".to_string(),
+            |dir| format!("No log files found in {} — waiting…", dir.display()),
+        ))
+    }
+
+    fn title(&self) -> String {
+        let mut title = if self.show_summary {
+            format!("{TITLE}: {}", self.summary)
+        } else {
+            TITLE.to_owned()
+        };
+        if self.prompt.is_none() && !self.filter.is_empty() {
+            title.push_str(" /");
+            title.push_str(&self.filter);
+        }
+        for progress in &self.indexing {
+            _ = write!(title, " [indexing {progress}]");
+        }
+        title
+    }
 }
 
-struct Renderer<'state>(&'state FileListState);
+/// Renders the rows, dimming the removed files with the color.
+struct Renderer<'state>(&'state FileListState, UtcOffset, Color);
 
 impl<'state> Renderer<'state> {
     fn header(&self) -> Row<'state> {
         Row::new(vec![
             Text::from(format_label(
                 LABELS[0],
-                self.0.sort_column == SortColumn::Name,
-                self.0.sort_direction,
+                self.0.sort.key == FileInfoSortKey::Name,
+                self.0.sort.direction,
             ))
             .left_aligned(),
             Text::from(format_label(
                 LABELS[1],
-                self.0.sort_column == SortColumn::LineCount,
-                self.0.sort_direction,
+                self.0.sort.key == FileInfoSortKey::LineCount,
+                self.0.sort.direction,
             ))
             .right_aligned(),
             Text::from(format_label(
                 LABELS[2],
-                self.0.sort_column == SortColumn::Age,
-                self.0.sort_direction,
+                self.0.sort.key == FileInfoSortKey::LastUpdate,
+                self.0.sort.direction,
             ))
             .right_aligned(),
             Text::from(LABELS[3]).left_aligned(),
@@ -167,20 +368,41 @@ impl<'state> Renderer<'state> {
         .bottom_margin(1)
     }
 
-    fn rows(&self) -> Vec<Row<'state>> {
+    /// Only the rows in `range` are built, the rest is not shown anyway.
+    fn rows(&self, range: Range<usize>) -> Vec<Row<'state>> {
         self.0
             .sorted_list
+            .get(range)
+            .unwrap_or_default()
             .iter()
             .map(|file| {
                 let age = (utils::now() - file.last_update).whole_seconds();
-                let last_update = file.last_update.format(LAST_UPDATE_FORMAT).unwrap();
-
-                Row::new(vec![
-                    Text::from(file.name.clone()).left_aligned(),
+                let last_update = file
+                    .last_update
+                    .to_offset(self.1)
+                    .format(LAST_UPDATE_FORMAT)
+                    .unwrap();
+
+                let mut name = if self.0.show_path {
+                    file.path.display().to_string()
+                } else {
+                    file.name.clone()
+                };
+                if !file.present {
+                    name.push_str(REMOVED_MARKER);
+                }
+
+                let row = Row::new(vec![
+                    Text::from(name).left_aligned(),
                     Text::from(file.number_of_lines.to_string()).right_aligned(),
                     Text::from(Line::from_iter([age.to_string(), "s".into()])).right_aligned(),
                     Text::from(last_update).left_aligned(),
-                ])
+                ]);
+                if file.present {
+                    row
+                } else {
+                    row.fg(self.2)
+                }
             })
             .collect_vec()
     }
@@ -192,99 +414,433 @@ impl StatefulWidget for FileList {
     type State = FileListState;
 
     fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
-        let renderer = Renderer(state);
+        let centered = area.inner_centered(60, 80);
 
-        let table = Table::new(renderer.rows(), WIDTHS)
-            .block(Block::default().title(TITLE).borders(Borders::ALL))
-            .header(renderer.header())
-            .highlight_spacing(HighlightSpacing::Always)
-            .highlight_style(Style::default().bold().yellow().on_blue());
+        // The table gets the visible rows only, scroll them like the table would.
+        let window = state.window(visible_rows(centered));
+        *state.table_state.offset_mut() = window.start;
+        let mut table_state = TableState::default().with_selected(
+            state
+                .table_state
+                .selected()
+                .and_then(|selected| selected.checked_sub(window.start)),
+        );
 
-        let mut table_state = state.table_state.clone();
+        let renderer = Renderer(state, self.utc_offset, self.theme.dim);
 
-        let centered = area.inner_centered(60, 80);
+        let table = Table::new(renderer.rows(window), WIDTHS)
+            .block(Block::default().title(state.title()).borders(Borders::ALL))
+            .header(renderer.header())
+            .highlight_spacing(HighlightSpacing::Always)
+            .highlight_style(self.theme.highlight_style());
 
         // Dim the backround.
-        Block::new().dark_gray().render(area, buf);
+        Block::new().fg(self.theme.dim).render(area, buf);
 
         // Clear the are for popup.
         Clear.render(centered.outer(Margin::new(2, 1)).clamp(area), buf);
 
         StatefulWidget::render(table, centered, buf, &mut table_state);
 
-        state.table_state = table_state;
+        // On the first row.
+        if let Some(message) = state.empty_message().filter(|_| visible_rows(centered) > 0) {
+            let rows = centered.inner(&Margin::new(1, 1));
+            let line = Rect {
+                y: rows.y + 2,
+                height: 1,
+                ..rows
+            };
+            Paragraph::new(message)
+                .centered()
+                .fg(self.theme.dim)
+                .render(line, buf);
+        }
+
+        // Over the bottom border.
+        if let Some(prompt) = state.prompt.as_mut() {
+            let line = Rect {
+                y: centered.bottom().saturating_sub(1),
+                height: 1,
+                ..centered.inner(&Margin::new(1, 0))
+            }
+            .clamp(area);
+            StatefulWidget::render(Prompt {}, line, buf, prompt);
+        }
+
+        state.table_area = centered;
     }
 }
 
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
-enum SortColumn {
-    Age,
-    LineCount,
-    #[default]
-    Name,
+/// The table rows fitting the area, below the borders, the header and its margin.
+const fn visible_rows(area: Rect) -> usize {
+    area.height.saturating_sub(4) as usize
 }
 
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
-enum SortDirection {
-    #[default]
-    Ascending,
-    Descending,
-}
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crossterm::event::{
+        KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+    };
+    use ratatui::{
+        buffer::{Buffer, Cell},
+        layout::Rect,
+        widgets::StatefulWidget,
+    };
+    use time::{Duration, UtcOffset};
+
+    use crate::{
+        repository::{FileInfo, IndexProgress, RepoList, RepoSummary},
+        theme::Theme,
+        utils,
+        widgets::{KeyEventHandler, MouseEventHandler},
+    };
+
+    use super::{visible_rows, FileList, FileListState};
 
-impl From<SortDirection> for char {
-    fn from(direction: SortDirection) -> Self {
-        match direction {
-            SortDirection::Ascending => '▼',
-            SortDirection::Descending => '▲',
+    struct Repo(Vec<FileInfo>);
+
+    impl RepoList for Repo {
+        fn list(&self) -> Vec<FileInfo> {
+            self.0.clone()
+        }
+
+        fn list_version(&self) -> u64 {
+            0
+        }
+
+        fn summary(&self) -> RepoSummary {
+            RepoSummary::default()
+        }
+
+        fn indexing(&self) -> Vec<IndexProgress> {
+            vec![]
+        }
+
+        fn dir(&self) -> Option<&Path> {
+            Some(Path::new("/var/log/app"))
         }
     }
-}
 
-impl Display for SortDirection {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_char(char::from(*self))
+    fn info(name: &str) -> FileInfo {
+        FileInfo {
+            name: name.to_string(),
+            path: name.into(),
+            last_update: utils::now(),
+            number_of_lines: 0,
+            present: true,
+        }
     }
-}
 
-fn sort(files: Vec<FileInfo>, column: SortColumn, direction: SortDirection) -> Vec<FileInfo> {
-    let cmp = match column {
-        SortColumn::Name => FileInfoExt::cmp_by_name,
-        SortColumn::Age => FileInfoExt::cmp_by_age,
-        SortColumn::LineCount => FileInfoExt::cmp_by_line_count,
-    };
+    /// The rendered rows as text.
+    fn rows(buf: &Buffer) -> Vec<String> {
+        buf.content()
+            .chunks(usize::from(buf.area.width))
+            .map(|row| row.iter().map(Cell::symbol).collect())
+            .collect()
+    }
 
-    let sorted = files.into_iter().sorted_by(cmp);
+    fn names(state: &FileListState) -> Vec<String> {
+        state
+            .sorted_list
+            .iter()
+            .map(|info| info.name.clone())
+            .collect()
+    }
 
-    match direction {
-        SortDirection::Ascending => sorted.collect(),
-        SortDirection::Descending => sorted.rev().collect(),
+    fn click(column: u16, row: u16) -> MouseEvent {
+        MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        }
     }
-}
 
-trait FileInfoExt {
-    fn cmp_by_name(&self, other: &Self) -> Ordering;
-    fn cmp_by_age(&self, other: &Self) -> Ordering;
-    fn cmp_by_line_count(&self, other: &Self) -> Ordering;
-}
+    #[test]
+    fn select_clicked_row() {
+        let mut state = FileListState {
+            sorted_list: ["a", "b", "c"].map(info).into(),
+            table_area: Rect::new(10, 5, 40, 20),
+            ..FileListState::default()
+        };
+        state.table_state.select(Some(0));
+
+        // The border, the header and its margin come before the first row at 8.
+        state.handle_mouse_event(click(20, 9));
+        assert_eq!(state.table_state.selected(), Some(1));
+
+        // Below the last row.
+        state.handle_mouse_event(click(20, 11));
+        assert_eq!(state.table_state.selected(), Some(1));
+
+        // On the header.
+        state.handle_mouse_event(click(20, 6));
+        assert_eq!(state.table_state.selected(), Some(1));
+    }
+
+    #[test]
+    fn empty_directory() {
+        let mut state = FileListState::default();
+        state.update(&Repo(vec![]));
+
+        for code in [
+            KeyCode::Down,
+            KeyCode::Up,
+            KeyCode::Char('j'),
+            KeyCode::Enter,
+        ] {
+            let action = state.handle_key_event(&KeyEvent::new(code, KeyModifiers::NONE));
+            assert!(action.is_none());
+        }
+        assert_eq!(state.table_state.selected(), None);
 
-impl FileInfoExt for FileInfo {
-    fn cmp_by_name(&self, other: &Self) -> Ordering {
-        self.name.cmp(&other.name)
+        let area = Rect::new(0, 0, 100, 20);
+        let mut buf = Buffer::empty(area);
+        let widget = FileList {
+            theme: Theme::default(),
+            utc_offset: UtcOffset::UTC,
+        };
+        widget.render(area, &mut buf, &mut state);
+
+        let message = rows(&buf)
+            .into_iter()
+            .find(|row| row.contains("No log files"));
+        assert!(message.is_some_and(|row| row.contains("in /var/log/app — waiting…")));
+
+        // On the first row, there is nothing to select.
+        let first_row = state.table_area.y + 3;
+        state.handle_mouse_event(click(state.table_area.x + 5, first_row));
+        assert_eq!(state.table_state.selected(), None);
+
+        state.filter = "api".to_string();
+        assert_eq!(
+            state.empty_message().as_deref(),
+            Some("No files match /api")
+        );
     }
 
-    fn cmp_by_age(&self, other: &Self) -> Ordering {
-        self.last_update.cmp(&other.last_update).reverse()
+    #[test]
+    fn mark_removed_files() {
+        let repo = Repo(
+            [("a", true), ("b", false)]
+                .map(|(name, present)| FileInfo {
+                    present,
+                    ..info(name)
+                })
+                .into(),
+        );
+
+        let mut state = FileListState::default();
+        state.update(&repo);
+
+        let area = Rect::new(0, 0, 100, 20);
+        let mut buf = Buffer::empty(area);
+        let widget = FileList {
+            theme: Theme::default(),
+            utc_offset: UtcOffset::UTC,
+        };
+        widget.render(area, &mut buf, &mut state);
+
+        let rows = rows(&buf);
+        let row = |name: &str| rows.iter().zip(0..).find(|(row, _)| row.contains(name));
+
+        let (removed, y) = row("│b").unwrap();
+        assert!(removed.contains("b (removed)"));
+        let x = u16::try_from(removed.find("│b").unwrap()).unwrap() + 1;
+        assert_eq!(buf.get(x, y).fg, ratatui::style::Color::DarkGray);
+
+        let (present, _) = row("│a").unwrap();
+        assert!(!present.contains("(removed)"));
     }
 
-    fn cmp_by_line_count(&self, other: &Self) -> Ordering {
-        self.number_of_lines.cmp(&other.number_of_lines)
+    #[test]
+    fn age_follows_the_clock() {
+        let mut state = FileListState {
+            sorted_list: vec![FileInfo {
+                last_update: utils::now() - Duration::seconds(5),
+                ..info("a")
+            }],
+            ..FileListState::default()
+        };
+
+        let age = |state: &mut FileListState| {
+            let area = Rect::new(0, 0, 100, 20);
+            let mut buf = Buffer::empty(area);
+            let widget = FileList {
+                theme: Theme::default(),
+                utc_offset: UtcOffset::UTC,
+            };
+            widget.render(area, &mut buf, state);
+
+            rows(&buf)
+                .into_iter()
+                .find(|row| row.contains("│a "))
+                .and_then(|row| {
+                    row.split_whitespace()
+                        .find(|word| word.ends_with('s'))
+                        .map(str::to_string)
+                })
+        };
+
+        assert_eq!(age(&mut state).as_deref(), Some("5s"));
+
+        // The list is the same, rendering does not wait for it to change.
+        state.sorted_list[0].last_update -= Duration::seconds(10);
+        assert_eq!(age(&mut state).as_deref(), Some("15s"));
     }
-}
 
-fn format_label(label: &str, sorted: bool, direction: SortDirection) -> String {
-    if sorted {
-        format!("{label} {direction}")
-    } else {
-        label.to_string()
+    #[test]
+    fn summary_title() {
+        let mut state = FileListState {
+            summary: RepoSummary {
+                files: 3,
+                lines: 1_234,
+                bytes: 5 * 1024 * 1024 + 512 * 1024,
+            },
+            ..FileListState::default()
+        };
+        assert_eq!(state.title(), "File browser");
+
+        state.show_summary = true;
+        assert_eq!(state.title(), "File browser: 3 files, 1234 lines, 5.5 MiB");
+
+        state.summary.bytes = 100;
+        assert_eq!(state.title(), "File browser: 3 files, 1234 lines, 100 B");
+
+        state.show_summary = false;
+        state.indexing = vec![IndexProgress {
+            name: "big.log".to_string(),
+            read: 3 * 1024,
+            total: 4 * 1024,
+        }];
+        assert_eq!(state.title(), "File browser [indexing big.log 75%]");
+    }
+
+    #[test]
+    fn reopen_with_the_last_sort() {
+        let repo = Repo(
+            [("a", 10), ("b", 30), ("c", 20)]
+                .map(|(name, number_of_lines)| FileInfo {
+                    number_of_lines,
+                    ..info(name)
+                })
+                .into(),
+        );
+
+        let mut state = FileListState::default();
+        state.update(&repo);
+        assert_eq!(names(&state), ["a", "b", "c"]);
+
+        state.handle_key_event(&KeyEvent::new(KeyCode::Char('L'), KeyModifiers::SHIFT));
+        state.update(&repo);
+        assert_eq!(names(&state), ["b", "c", "a"]);
+
+        let mut reopened = FileListState::new(state.sort());
+        reopened.update(&repo);
+        assert_eq!(names(&reopened), ["b", "c", "a"]);
+    }
+
+    #[test]
+    fn render_thousands_of_files() {
+        let now = utils::now();
+        let repo = Repo(
+            (0..10_000)
+                .map(|i| {
+                    let name = format!("app-{i:05}.log");
+                    FileInfo {
+                        path: format!("/var/log/{name}").into(),
+                        last_update: now - Duration::seconds(i),
+                        number_of_lines: u32::try_from(i).unwrap(),
+                        ..info(&name)
+                    }
+                })
+                .collect(),
+        );
+
+        let mut state = FileListState::default();
+        let area = Rect::new(0, 0, 100, 50);
+        let mut buf = Buffer::empty(area);
+        let widget = FileList {
+            theme: Theme::default(),
+            utc_offset: UtcOffset::UTC,
+        };
+
+        state.update(&repo);
+        widget.render(area, &mut buf, &mut state);
+        let sorted = state.sorted_list.as_ptr();
+
+        // The list is sorted once, while its key stays the same.
+        for _ in 0..10 {
+            state.update(&repo);
+            widget.render(area, &mut buf, &mut state);
+        }
+        assert_eq!(state.sorted_list.as_ptr(), sorted);
+
+        // Only the visible rows are built.
+        let height = visible_rows(state.table_area);
+        assert_eq!(state.window(height).len(), height);
+
+        let down = KeyEvent::new(KeyCode::Char('j'), KeyModifiers::NONE);
+        for _ in 0..10_000 {
+            state.handle_key_event(&down);
+        }
+        widget.render(area, &mut buf, &mut state);
+
+        let rows = rows(&buf);
+        assert!(rows.iter().any(|row| row.contains("app-09999.log")));
+        assert!(!rows.iter().any(|row| row.contains("app-00000.log")));
+        assert_eq!(state.table_state.selected(), Some(9_999));
+        assert_eq!(
+            state.table_state.offset(),
+            10_000 - visible_rows(state.table_area)
+        );
+    }
+
+    #[test]
+    fn filter_by_name() {
+        let repo = Repo(["app-1.log", "db.log", "app-2.log"].map(info).into());
+        let press = |state: &mut FileListState, code| {
+            let action = state.handle_key_event(&KeyEvent::new(code, KeyModifiers::NONE));
+            state.update(&repo);
+            action
+        };
+
+        let mut state = FileListState::default();
+        state.update(&repo);
+        press(&mut state, KeyCode::Char('j'));
+        press(&mut state, KeyCode::Char('j'));
+        assert_eq!(state.selected().unwrap().name, "db.log");
+
+        // The list follows the input.
+        press(&mut state, KeyCode::Char('/'));
+        press(&mut state, KeyCode::Char('a'));
+        assert_eq!(names(&state), ["app-1.log", "app-2.log"]);
+        press(&mut state, KeyCode::Char('p'));
+        press(&mut state, KeyCode::Enter);
+        assert!(!state.is_prompting());
+        assert_eq!(state.title(), "File browser /ap");
+
+        press(&mut state, KeyCode::Char('j'));
+        let (info, _) = press(&mut state, KeyCode::Enter).unwrap();
+        assert_eq!(info.name, "app-2.log");
+
+        // Cancelling keeps the filter.
+        press(&mut state, KeyCode::Char('/'));
+        press(&mut state, KeyCode::Backspace);
+        assert_eq!(names(&state), ["app-1.log", "app-2.log"]);
+        press(&mut state, KeyCode::Backspace);
+        assert_eq!(names(&state), ["app-1.log", "app-2.log", "db.log"]);
+        press(&mut state, KeyCode::Esc);
+        assert_eq!(names(&state), ["app-1.log", "app-2.log"]);
+
+        // An empty filter shows all files.
+        press(&mut state, KeyCode::Char('/'));
+        press(&mut state, KeyCode::Backspace);
+        press(&mut state, KeyCode::Backspace);
+        press(&mut state, KeyCode::Enter);
+        assert_eq!(names(&state), ["app-1.log", "app-2.log", "db.log"]);
+        assert_eq!(state.selected().unwrap().name, "app-2.log");
     }
 }
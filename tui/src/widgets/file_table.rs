@@ -0,0 +1,136 @@
+//! Sorting and labels of the file tables.
+
+use std::cmp::Ordering;
+
+use itertools::Itertools;
+
+use crate::repository::{FileInfo, FileInfoSortKey, SortDirection};
+
+pub fn sort(files: Vec<FileInfo>, key: FileInfoSortKey, direction: SortDirection) -> Vec<FileInfo> {
+    let cmp = match key {
+        FileInfoSortKey::Name => FileInfoExt::cmp_by_name,
+        FileInfoSortKey::LastUpdate => FileInfoExt::cmp_by_age,
+        FileInfoSortKey::LineCount => FileInfoExt::cmp_by_line_count,
+    };
+
+    let sorted = files.into_iter().sorted_by(cmp);
+    let sorted = match direction {
+        SortDirection::Ascending => sorted.collect_vec(),
+        SortDirection::Descending => sorted.rev().collect_vec(),
+    };
+
+    // The removed files come last in either direction.
+    let (present, removed): (Vec<_>, Vec<_>) = sorted.into_iter().partition(|info| info.present);
+    present.into_iter().chain(removed).collect()
+}
+
+trait FileInfoExt {
+    fn cmp_by_name(&self, other: &Self) -> Ordering;
+    fn cmp_by_age(&self, other: &Self) -> Ordering;
+    fn cmp_by_line_count(&self, other: &Self) -> Ordering;
+}
+
+impl FileInfoExt for FileInfo {
+    fn cmp_by_name(&self, other: &Self) -> Ordering {
+        self.name.cmp(&other.name)
+    }
+
+    fn cmp_by_age(&self, other: &Self) -> Ordering {
+        self.last_update.cmp(&other.last_update).reverse()
+    }
+
+    fn cmp_by_line_count(&self, other: &Self) -> Ordering {
+        self.number_of_lines.cmp(&other.number_of_lines)
+    }
+}
+
+pub fn format_label(label: &str, sorted: bool, direction: SortDirection) -> String {
+    if sorted {
+        format!("{label} {direction}")
+    } else {
+        label.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::Duration;
+
+    use crate::{
+        repository::{FileInfo, FileInfoSortKey, SortDirection},
+        utils,
+    };
+
+    use super::{format_label, sort};
+
+    fn files() -> Vec<FileInfo> {
+        let now = utils::now();
+        [("b", 3, 20), ("a", 1, 10), ("c", 2, 30)]
+            .map(|(name, number_of_lines, age)| FileInfo {
+                name: name.to_string(),
+                path: name.into(),
+                last_update: now - Duration::seconds(age),
+                number_of_lines,
+                present: true,
+            })
+            .into()
+    }
+
+    fn names(key: FileInfoSortKey, direction: SortDirection) -> Vec<String> {
+        sort(files(), key, direction)
+            .into_iter()
+            .map(|info| info.name)
+            .collect()
+    }
+
+    #[test]
+    fn descending_reverses_ascending() {
+        use FileInfoSortKey::{LastUpdate, LineCount, Name};
+        use SortDirection::{Ascending, Descending};
+
+        assert_eq!(names(Name, Ascending), ["a", "b", "c"]);
+        assert_eq!(names(LineCount, Ascending), ["a", "c", "b"]);
+        assert_eq!(names(LastUpdate, Ascending), ["a", "b", "c"]);
+
+        for key in [Name, LineCount, LastUpdate] {
+            let mut descending = names(key, Descending);
+            descending.reverse();
+            assert_eq!(names(key, Ascending), descending);
+        }
+    }
+
+    #[test]
+    fn removed_files_last() {
+        use FileInfoSortKey::{LastUpdate, Name};
+        use SortDirection::{Ascending, Descending};
+
+        let names = |key, direction| {
+            let mut files = files();
+            files[1].present = false;
+            sort(files, key, direction)
+                .into_iter()
+                .map(|info| info.name)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names(Name, Ascending), ["b", "c", "a"]);
+        assert_eq!(names(Name, Descending), ["c", "b", "a"]);
+        assert_eq!(names(LastUpdate, Descending), ["c", "b", "a"]);
+    }
+
+    #[test]
+    fn label_shows_direction() {
+        assert_eq!(
+            format_label("Name", true, SortDirection::Ascending),
+            "Name ▼"
+        );
+        assert_eq!(
+            format_label("Name", true, SortDirection::Descending),
+            "Name ▲"
+        );
+        assert_eq!(
+            format_label("Name", false, SortDirection::Descending),
+            "Name"
+        );
+    }
+}
@@ -1,40 +1,190 @@
-#![allow(dead_code)]
-
 use ratatui::{
     prelude::{Buffer, Rect},
-    widgets::StatefulWidget,
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{StatefulWidget, Widget},
 };
 
-pub type TabTitle = Box<str>;
-pub type Tabs = Box<[TabTitle]>;
+/// Shown on the side with tabs scrolled out of the bar.
+const MORE_LEFT: &str = "‹ ";
+const MORE_RIGHT: &str = " ›";
+const MARKER_WIDTH: usize = 2;
+const DIVIDER: &str = " ";
 
+/// The selected tab and the first one shown, the titles come with the widget.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub struct FileTabsState {
-    tabs: Tabs,
     selected: usize,
+    offset: usize,
 }
 
 impl FileTabsState {
-    pub const fn new(tabs: Tabs) -> Self {
-        assert!(!tabs.is_empty());
-        Self { tabs, selected: 0 }
-    }
-
     pub const fn selected(&self) -> usize {
         self.selected
     }
 
-    pub fn select(&mut self, selected: usize) {
-        assert!(selected < self.tabs.len());
+    pub const fn select(&mut self, selected: usize) {
         self.selected = selected;
     }
+
+    /// Selects the tab after the selected one of `len` tabs, the first after the last.
+    pub const fn next(&mut self, len: usize) {
+        if len > 0 {
+            self.selected = (self.selected + 1) % len;
+        }
+    }
+
+    /// Selects the tab before the selected one of `len` tabs, the last before the first.
+    pub const fn previous(&mut self, len: usize) {
+        self.selected = match self.selected.checked_sub(1) {
+            Some(previous) => previous,
+            None => len.saturating_sub(1),
+        };
+    }
 }
 
-pub struct FileTabs;
+/// A bar of tab titles, scrolled to the selected one when they do not fit.
+pub struct FileTabs {
+    titles: Vec<String>,
+    highlight_style: Style,
+    marker_style: Style,
+}
+
+impl FileTabs {
+    pub const fn new(titles: Vec<String>) -> Self {
+        Self {
+            titles,
+            highlight_style: Style::new(),
+            marker_style: Style::new().fg(Color::DarkGray),
+        }
+    }
+
+    /// The style of the selected title.
+    #[must_use]
+    pub const fn highlight_style(mut self, style: Style) -> Self {
+        self.highlight_style = style;
+        self
+    }
+
+    /// The style of the markers of the hidden titles.
+    #[must_use]
+    pub const fn marker_style(mut self, style: Style) -> Self {
+        self.marker_style = style;
+        self
+    }
+
+    /// The columns taken by the titles `from..=to`, with the markers of the hidden ones.
+    fn width(&self, widths: &[usize], from: usize, to: usize) -> usize {
+        let markers =
+            MARKER_WIDTH * (usize::from(from > 0) + usize::from(to < self.titles.len() - 1));
+        widths[from..=to].iter().sum::<usize>() + (to - from) * DIVIDER.len() + markers
+    }
+}
 
 impl StatefulWidget for FileTabs {
     type State = FileTabsState;
 
     fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
-        let _ = (area, buf, state);
+        let Some(last) = self.titles.len().checked_sub(1) else {
+            return;
+        };
+        let widths = self
+            .titles
+            .iter()
+            .map(|title| Line::raw(title.as_str()).width())
+            .collect::<Vec<_>>();
+        let fits = |from, to| self.width(&widths, from, to) <= usize::from(area.width);
+
+        // Scroll left as far as the tabs up to the last fit, then right until the selected one does.
+        let selected = state.selected.min(last);
+        let mut offset = state.offset.min(selected);
+        while offset > 0 && fits(offset - 1, last) {
+            offset -= 1;
+        }
+        while offset < selected && !fits(offset, selected) {
+            offset += 1;
+        }
+        state.offset = offset;
+
+        let end = (offset..=last)
+            .take_while(|&to| fits(offset, to))
+            .last()
+            .unwrap_or(offset);
+
+        let mut spans = vec![];
+        if offset > 0 {
+            spans.push(Span::raw(MORE_LEFT).style(self.marker_style));
+        }
+        for (i, title) in self.titles.iter().enumerate().take(end + 1).skip(offset) {
+            if i > offset {
+                spans.push(Span::raw(DIVIDER));
+            }
+            let span = Span::raw(title.as_str());
+            spans.push(if i == selected {
+                span.style(self.highlight_style)
+            } else {
+                span
+            });
+        }
+        if end < last {
+            spans.push(Span::raw(MORE_RIGHT).style(self.marker_style));
+        }
+
+        Line::from(spans).render(area, buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::{buffer::Buffer, layout::Rect, widgets::StatefulWidget};
+
+    use super::{FileTabs, FileTabsState};
+
+    fn render(titles: &[&str], width: u16, state: &mut FileTabsState) -> String {
+        let area = Rect::new(0, 0, width, 1);
+        let mut buf = Buffer::empty(area);
+        FileTabs::new(titles.iter().map(ToString::to_string).collect())
+            .render(area, &mut buf, state);
+
+        buf.content()
+            .iter()
+            .map(ratatui::buffer::Cell::symbol)
+            .collect::<String>()
+            .trim_end()
+            .to_string()
+    }
+
+    #[test]
+    fn scroll_to_the_selected_tab() {
+        let titles = ["aaaa", "bbbb", "cccc", "dddd", "eeee"];
+        let mut state = FileTabsState::default();
+
+        assert_eq!(render(&titles, 30, &mut state), "aaaa bbbb cccc dddd eeee");
+        assert_eq!(render(&titles, 12, &mut state), "aaaa bbbb ›");
+
+        state.select(4);
+        assert_eq!(render(&titles, 12, &mut state), "‹ dddd eeee");
+
+        // The tabs stay where they are while the selected one is shown.
+        state.select(3);
+        assert_eq!(render(&titles, 12, &mut state), "‹ dddd eeee");
+
+        state.select(1);
+        assert_eq!(render(&titles, 14, &mut state), "‹ bbbb cccc ›");
+
+        // Room for all of them again.
+        assert_eq!(render(&titles, 30, &mut state), "aaaa bbbb cccc dddd eeee");
+    }
+
+    #[test]
+    fn wrap_around() {
+        let mut state = FileTabsState::default();
+        state.previous(3);
+        assert_eq!(state.selected(), 2);
+        state.next(3);
+        assert_eq!(state.selected(), 0);
+
+        state.next(0);
+        assert_eq!(state.selected(), 0);
     }
 }
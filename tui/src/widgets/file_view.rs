@@ -1,309 +1,3528 @@
-use std::sync::Arc;
+use std::{
+    collections::{BTreeSet, HashMap, VecDeque},
+    fmt::Write,
+    ops::Range,
+    path::PathBuf,
+    sync::Arc,
+};
 
-use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseEvent, MouseEventKind};
 use itertools::Itertools;
 use ratatui::{
     prelude::*,
     widgets::{
-        Block, Borders, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState, StatefulWidget,
-        Tabs,
+        Block, Borders, Clear, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState,
+        StatefulWidget, Wrap,
+    },
+};
+
+use time::{OffsetDateTime, UtcOffset};
+use tokio::sync::{
+    mpsc,
+    oneshot::{self, error::TryRecvError},
+};
+
+use crate::{
+    repository::{
+        join_runs, ByteSize, FileInfo, LineState, Matches, RepoLines, Run, SearchDirection,
+        SearchOptions,
     },
+    session,
+    theme::{Levels, Theme},
+    timestamp,
+    utils::{self, RectExt},
+};
+
+use super::{
+    file_tabs::{FileTabs, FileTabsState},
+    prompt::{Prompt, PromptAction, PromptState},
+    KeyEventHandler, MouseEventHandler,
 };
 
-use crate::repository::{FileInfo, RepoLines};
+/// Lines to scroll per mouse wheel step.
+const WHEEL_STEP: u32 = 3;
 
-use super::KeyEventHandler;
+/// Lines read at once while collecting the runs of equal lines.
+const RUN_CHUNK: u32 = 1_024;
+
+/// A run of equal lines longer than this is shown in parts.
+const MAX_RUN_LINES: u32 = 65_536;
+
+/// Shown in place of a line that is still read.
+const PENDING_LINE: &str = "…";
+
+/// Shown instead of the lines of a removed file.
+const REMOVED_BANNER: &str = "<file removed>";
 
 struct FileState {
     pub name: String,
     total_lines: u32,
-    number_column_width: u16,
     scroll_offset: u32,
     display_lines: Box<[Arc<str>]>,
+    /// Rows after the display lines that are still read, drawn as placeholders.
+    pending_lines: u32,
     stick_to_bottom: bool,
+    search: Option<Search>,
+    /// The first column of the text shown.
+    horizontal_offset: u16,
+    /// Shows only the matching lines, the full view keeps its position meanwhile.
+    filter: Option<Filter>,
+    /// Shows the lines of this and other files interleaved.
+    merged: Option<MergedFileState>,
+    /// The file is gone, the tab waits for a file with the same name.
+    removed: bool,
+    /// Indices of the bookmarked lines.
+    bookmarks: BTreeSet<u32>,
+    /// Lines fitting in the view when they are wrapped, learned on render.
+    page: Option<u32>,
+    /// Shows each run of equal lines as a single row in the full view.
+    collapsed: Option<Collapsed>,
+    /// Scrolls to the end once the number of lines is known, see [`TailOnOpen`].
+    tail: TailOnOpen,
+    /// Lines picked in the full view, exported by `E` instead of the visible ones.
+    selection: Option<Selection>,
+    /// The scroll offset was restored, it is clamped once the number of lines is known.
+    restored: bool,
+    /// The byte offsets of the lines in the view, read only for [`NumberFormat::Offset`].
+    offsets: Box<[Option<u64>]>,
+    /// The index of the file the filtered and counted lines were found in,
+    /// see [`RepoLines::index_version`].
+    index_version: u64,
 }
 
-impl From<FileInfo> for FileState {
-    fn from(info: FileInfo) -> Self {
+/// Lines from the anchor set by `V` to the head moved by the movement keys.
+#[derive(Debug)]
+struct Selection {
+    anchor: u32,
+    head: u32,
+    /// The selected lines and their bytes, counted again when the lines change.
+    bytes: Option<(Range<u32>, u64)>,
+}
+
+impl Selection {
+    const fn new(line: u32) -> Self {
         Self {
-            name: info.name,
-            total_lines: info.number_of_lines,
-            number_column_width: info
-                .number_of_lines
-                .to_string()
-                .len()
-                .try_into()
-                .unwrap_or(1u16)
-                + 3,
-            scroll_offset: 0,
-            display_lines: Box::default(),
-            stick_to_bottom: false,
+            anchor: line,
+            head: line,
+            bytes: None,
         }
     }
+
+    fn lines(&self) -> Range<u32> {
+        self.anchor.min(self.head)..self.anchor.max(self.head) + 1
+    }
+}
+
+/// Where a newly opened file starts.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TailOnOpen {
+    /// At the first line.
+    #[default]
+    Off,
+    /// At the last screenful.
+    Page,
+    /// At the last this many lines.
+    Lines(u32),
 }
 
+struct Search {
+    needle: String,
+    options: SearchOptions,
+    /// Finds the matches to highlight.
+    re: regex::Regex,
+    /// The last found line.
+    current: Option<u32>,
+    /// The search to request on the next update.
+    next: Option<(u32, SearchDirection)>,
+    pending: Option<oneshot::Receiver<Option<u32>>>,
+    /// The lines containing the needle counted so far, in order.
+    matches: Vec<u32>,
+    /// The lines before this one are counted or being counted.
+    counted: u32,
+    counting: Option<mpsc::UnboundedReceiver<Vec<u32>>>,
+}
+
+struct Filter {
+    needle: String,
+    options: SearchOptions,
+    /// The matching lines found so far.
+    lines: Matches,
+    /// The position in the filtered lines.
+    scroll_offset: u32,
+    /// The lines before this one are filtered or requested already.
+    scanned: u32,
+    pending: Option<oneshot::Receiver<Matches>>,
+}
+
+/// The runs of equal lines around the top line, read on update.
+/// Scrolling moves by runs, the scroll offset stays the index of the top line.
 #[derive(Default)]
-pub struct FileViewState {
-    height: u32,
-    files: Vec<FileState>,
-    active: usize,
+struct Collapsed {
+    /// The runs above the top line.
+    before: Vec<Run>,
+    /// The runs from the top line, one more than fits in the view.
+    after: Vec<Run>,
+    /// The runs in `after` reach the end of the file.
+    at_end: bool,
 }
 
-impl KeyEventHandler for FileViewState {
-    type Action = ();
+impl Collapsed {
+    /// Moves the top down by `runs`, keeping a full page at the end of the file.
+    /// Returns the new top line, if it moved.
+    fn scroll_down(&mut self, runs: u32, height: u32) -> Option<u32> {
+        let last = if self.at_end {
+            self.after.len().saturating_sub(height as usize)
+        } else {
+            self.after.len().saturating_sub(1)
+        };
+        let steps = (runs as usize).min(last);
+        if steps == 0 {
+            return None;
+        }
 
-    fn handle_key_event(&mut self, event: &KeyEvent) -> Option<Self::Action> {
-        let active = self.files.get_mut(self.active)?;
+        self.before.extend(self.after.drain(..steps));
+        self.after.first().map(|run| run.start)
+    }
 
-        let with_shift = event.modifiers.contains(KeyModifiers::SHIFT);
+    /// Moves the top up by `runs`, returns the new top line if there were runs above.
+    fn scroll_up(&mut self, runs: u32) -> Option<u32> {
+        let steps = (runs as usize).min(self.before.len());
+        if steps == 0 {
+            return None;
+        }
 
-        match (event.kind, event.code) {
-            (KeyEventKind::Press, KeyCode::Up) => {
-                active.scroll_offset = if with_shift {
-                    active.scroll_offset.saturating_sub(self.height)
-                } else {
-                    active.scroll_offset.saturating_sub(1)
-                };
-                active.stick_to_bottom = false;
-            }
-            (KeyEventKind::Press, KeyCode::Down) => {
-                active.scroll_offset = if with_shift {
-                    active.scroll_offset.saturating_add(self.height)
-                } else {
-                    active.scroll_offset.saturating_add(1)
+        let mut moved = self.before.split_off(self.before.len() - steps);
+        join_runs(&mut moved, self.after.drain(..));
+        self.after = moved;
+        self.after.first().map(|run| run.start)
+    }
+}
+
+/// Reads the runs starting at `from` until there are more than `count` of them,
+/// the end of the file or [`MAX_RUN_LINES`]. The last run might continue further.
+fn runs_after(repo: &impl RepoLines, name: &str, from: u32, total: u32, count: usize) -> Vec<Run> {
+    let mut runs = vec![];
+    let mut next = from;
+
+    while next < total && runs.len() <= count && next - from < MAX_RUN_LINES {
+        let to = next.saturating_add(RUN_CHUNK).min(total);
+        join_runs(&mut runs, repo.runs(name, next, to).into_vec());
+        next = to;
+    }
+
+    runs
+}
+
+/// Reads the runs ending at `to` until there are `count` whole ones before them,
+/// the start of the file or [`MAX_RUN_LINES`].
+fn runs_before(repo: &impl RepoLines, name: &str, to: u32, count: usize) -> Vec<Run> {
+    let mut runs = vec![];
+    let mut start = to;
+
+    while start > 0 && runs.len() <= count && to - start < MAX_RUN_LINES {
+        let from = start.saturating_sub(RUN_CHUNK);
+        let mut earlier = repo.runs(name, from, start).into_vec();
+        join_runs(&mut earlier, runs);
+        runs = earlier;
+        start = from;
+    }
+
+    // The first run might start before the lines read.
+    if start > 0 && runs.len() > count {
+        runs.remove(0);
+    }
+
+    runs
+}
+
+/// Lines written to a file in the background.
+struct Export {
+    name: String,
+    path: PathBuf,
+    lines: Range<u32>,
+    /// Only the lines containing the needle are written.
+    matching: Option<(String, SearchOptions)>,
+    pending: Option<oneshot::Receiver<Result<u64, String>>>,
+}
+
+/// Lines to filter per request, so the first matches show up before the whole file is read.
+const FILTER_CHUNK: u32 = 100_000;
+
+/// Lines of several files interleaved by their leading timestamps.
+///
+/// A line without a timestamp stays right after the line before it in its file.
+/// Lines appended to a file after the others are merged to the end are not moved back.
+struct MergedFileState {
+    sources: Vec<Source>,
+    /// Reads the leading timestamps of the lines, the ones without an offset are in `utc_offset`.
+    parser: timestamp::Parser,
+    utc_offset: UtcOffset,
+    /// Merged lines as source and line indices.
+    lines: Vec<(usize, u32)>,
+    scroll_offset: u32,
+    /// The source of the last merged line, lines without a timestamp follow it.
+    current: Option<usize>,
+}
+
+struct Source {
+    name: String,
+    total_lines: u32,
+    /// The next line to merge.
+    next: u32,
+    /// Timestamps of the lines read ahead of `next`, `None` for a line without one.
+    ahead: VecDeque<Option<OffsetDateTime>>,
+}
+
+/// Lines to read ahead per file, that many lines are merged per update at most.
+const MERGE_READ_AHEAD: u32 = 1_000;
+
+impl MergedFileState {
+    fn new<const N: usize>(
+        names: [String; N],
+        parser: timestamp::Parser,
+        utc_offset: UtcOffset,
+    ) -> Self {
+        Self {
+            sources: names.into_iter().map(Source::new).collect(),
+            parser,
+            utc_offset,
+            lines: vec![],
+            scroll_offset: 0,
+            current: None,
+        }
+    }
+
+    /// Adds a file and merges all the files again.
+    fn add(&mut self, name: String) {
+        if self.sources.iter().any(|source| source.name == name) {
+            return;
+        }
+
+        self.sources.push(Source::new(name));
+        for source in &mut self.sources {
+            source.next = 0;
+            source.ahead.clear();
+        }
+        self.lines.clear();
+        self.scroll_offset = 0;
+        self.current = None;
+    }
+
+    /// Merges the lines read so far.
+    /// It stops at a file which has lines left but none read yet, one of them may go first.
+    fn update(&mut self, repo: &impl RepoLines) {
+        for source in &mut self.sources {
+            source.read_ahead(repo, self.parser, self.utc_offset);
+        }
+
+        loop {
+            if let Some(index) = self.current {
+                let source = &self.sources[index];
+                match source.ahead.front() {
+                    Some(None) => {
+                        self.take(index);
+                        continue;
+                    }
+                    None if source.next < source.total_lines => return,
+                    _ => {}
                 }
-                .min(active.total_lines.saturating_sub(self.height));
-                active.stick_to_bottom = false;
-            }
-            (KeyEventKind::Press, KeyCode::PageUp) => {
-                active.scroll_offset = active.scroll_offset.saturating_sub(self.height);
-                active.stick_to_bottom = false;
             }
-            (KeyEventKind::Press, KeyCode::PageDown) => {
-                active.scroll_offset = active
-                    .scroll_offset
-                    .saturating_add(self.height)
-                    .min(active.total_lines.saturating_sub(self.height));
-                active.stick_to_bottom = false;
-            }
-            (KeyEventKind::Press, KeyCode::Char('B')) => {
-                active.stick_to_bottom = true;
+
+            if self
+                .sources
+                .iter()
+                .any(|source| source.ahead.is_empty() && source.next < source.total_lines)
+            {
+                return;
             }
-            _ => {}
+
+            let Some((_, index)) = self
+                .sources
+                .iter()
+                .enumerate()
+                .filter_map(|(index, source)| Some((*source.ahead.front()?, index)))
+                .min()
+            else {
+                return;
+            };
+
+            self.take(index);
         }
+    }
 
-        None
+    fn take(&mut self, index: usize) {
+        let source = &mut self.sources[index];
+        source.ahead.pop_front();
+        self.lines.push((index, source.next));
+        source.next += 1;
+        self.current = Some(index);
     }
-}
 
-impl FileViewState {
-    pub fn push(&mut self, info: FileInfo) {
-        if let Some(pos) = self.files.iter().position(|state| state.name == info.name) {
-            self.active = pos;
-        } else {
-            self.files.push(info.into());
-            self.active = self.files.len() - 1;
-        }
+    fn display_lines(&self, repo: &impl RepoLines, height: u32) -> Box<[Arc<str>]> {
+        self.visible_lines(height)
+            .map(|&(index, line)| {
+                repo.loaded_lines(&self.sources[index].name, line, line + 1)
+                    .first()
+                    .cloned()
+                    .unwrap_or_else(|| "".into())
+            })
+            .collect()
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.files.is_empty()
+    fn visible_lines(&self, height: u32) -> impl Iterator<Item = &(usize, u32)> {
+        self.lines
+            .iter()
+            .skip(self.scroll_offset as usize)
+            .take(height as usize)
     }
+}
 
-    pub fn update(&mut self, repo: &impl RepoLines) {
-        if let Some(state) = self.files.get_mut(self.active) {
-            let name = &state.name;
+impl Source {
+    const fn new(name: String) -> Self {
+        Self {
+            name,
+            total_lines: 0,
+            next: 0,
+            ahead: VecDeque::new(),
+        }
+    }
 
-            state.total_lines = repo.total(name);
+    fn read_ahead(&mut self, repo: &impl RepoLines, parser: timestamp::Parser, offset: UtcOffset) {
+        self.total_lines = repo.total(&self.name);
 
-            state.display_lines = repo.lines(
-                name,
-                state.scroll_offset,
-                (state.scroll_offset + self.height).min(state.total_lines),
-            );
+        if self.ahead.is_empty() && self.next < self.total_lines {
+            let to = self
+                .next
+                .saturating_add(MERGE_READ_AHEAD)
+                .min(self.total_lines);
 
-            if state.stick_to_bottom {
-                state.scroll_offset = state.total_lines.saturating_sub(self.height);
-            }
+            // The lines not in the cache yet are read in the background, until then nothing is returned.
+            self.ahead.extend(
+                repo.loaded_lines(&self.name, self.next, to)
+                    .iter()
+                    .map(|line| parser(line, offset)),
+            );
         }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub struct FileView {}
+impl FileState {
+    fn filter(&mut self, needle: String, options: SearchOptions) {
+        self.filter = Some(Filter {
+            needle,
+            options,
+            lines: vec![],
+            scroll_offset: 0,
+            scanned: 0,
+            pending: None,
+        });
+    }
 
-impl StatefulWidget for FileView {
-    type State = FileViewState;
+    /// Collects the result of the pending filter request and requests the next chunk.
+    /// The lines found in a `reindexed` file are dropped.
+    fn poll_filter(&mut self, repo: &impl RepoLines, reindexed: bool) {
+        let Some(filter) = self.filter.as_mut() else {
+            return;
+        };
 
-    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
-        // Update the visible lines count
-        state.height = area.height.saturating_sub(3).into();
+        // The file was replaced, start over.
+        if reindexed {
+            filter.lines.clear();
+            filter.scroll_offset = 0;
+            filter.scanned = 0;
+            filter.pending = None;
+        }
 
-        let frame_height = state.height;
+        if let Some(pending) = filter.pending.as_mut() {
+            match pending.try_recv() {
+                Ok(matches) => filter.lines.extend(matches),
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Closed) => {}
+            }
+            filter.pending = None;
+        }
 
-        let tab_titles = state
-            .files
-            .iter()
-            .map(|state| state.name.clone())
-            .collect_vec();
+        if filter.scanned < self.total_lines {
+            let to = filter
+                .scanned
+                .saturating_add(FILTER_CHUNK)
+                .min(self.total_lines);
+            filter.pending = Some(repo.filter(
+                &self.name,
+                &filter.needle,
+                filter.options,
+                filter.scanned,
+                to,
+            ));
+            filter.scanned = to;
+        }
+    }
 
-        let Some(active_state) = state.files.get_mut(state.active) else {
-            return;
+    /// Number of lines in the current view, merged, filtered or full.
+    fn view_len(&self) -> u32 {
+        let len = match (self.merged.as_ref(), self.filter.as_ref()) {
+            (Some(merged), _) => merged.lines.len(),
+            (None, Some(filter)) => filter.lines.len(),
+            (None, None) => return self.total_lines,
         };
+        u32::try_from(len).unwrap_or(u32::MAX)
+    }
 
-        let layout = FileViewLayout::new(area, active_state.number_column_width);
+    /// Lines fitting in the view of `height` rows, fewer than that when they wrap.
+    fn page(&self, height: u32) -> u32 {
+        self.page.map_or(height, |page| page.min(height))
+    }
 
-        // Tabs
-        {
-            Tabs::new(tab_titles)
-                .highlight_style(Style::default().bold().yellow())
-                .padding("", "")
-                .divider(" ")
-                .select(state.active)
-                .render(layout.tabs, buf);
+    /// Learns the page of the pane in `area`, see [`Self::fitting_lines`].
+    fn learn_page(
+        &mut self,
+        area: Rect,
+        height: u32,
+        wrap: bool,
+        mode: LineNumberMode,
+        format: NumberFormat,
+    ) {
+        self.page = wrap.then(|| {
+            let layout = PaneLayout::new(area, self.number_column_width(mode, format, height));
+            // Minus the left border.
+            self.fitting_lines(height, layout.text.width.saturating_sub(1))
+        });
+    }
+
+    /// Counts the shown lines whose wrapped rows add up to at most `height`,
+    /// a line taller than the view still takes the whole page.
+    /// The rows below the shown lines are counted as a line each.
+    fn fitting_lines(&self, height: u32, width: u16) -> u32 {
+        let mut free = height;
+        for (fitting, line) in (0..).zip(self.display_lines.iter()) {
+            let rows = u32::try_from(wrapped_rows(line, width)).unwrap_or(u32::MAX);
+            if rows > free {
+                return fitting.max(1);
+            }
+            free -= rows;
         }
 
-        // Numbers column
-        {
-            let line_numbers = ((active_state.scroll_offset)
-                ..(active_state.scroll_offset + frame_height))
-                .map(|i| {
-                    Line::from(vec![Span::raw((i + 1).to_string()), Span::raw(" ")])
-                        .right_aligned()
-                        .dark_gray()
-                })
-                .collect_vec();
+        u32::try_from(self.display_lines.len())
+            .unwrap_or(u32::MAX)
+            .saturating_add(free)
+    }
 
-            let column = Paragraph::new(line_numbers).block(
-                Block::new()
-                    .borders(Borders::TOP | Borders::BOTTOM)
-                    .border_style(Style::default().dark_gray()),
-            );
+    /// Position in the current view, merged, filtered or full.
+    const fn view_offset(&self) -> u32 {
+        match (self.merged.as_ref(), self.filter.as_ref()) {
+            (Some(merged), _) => merged.scroll_offset,
+            (None, Some(filter)) => filter.scroll_offset,
+            (None, None) => self.scroll_offset,
+        }
+    }
 
-            Widget::render(column, layout.numbers, buf);
+    const fn view_offset_mut(&mut self) -> &mut u32 {
+        match (self.merged.as_mut(), self.filter.as_mut()) {
+            (Some(merged), _) => &mut merged.scroll_offset,
+            (None, Some(filter)) => &mut filter.scroll_offset,
+            (None, None) => &mut self.scroll_offset,
         }
+    }
 
-        // Text area
-        {
-            let lines = active_state
-                .display_lines
-                .iter()
-                .map(|line| Line::from(line.as_ref()))
-                .collect_vec();
+    /// Adds the file to the merged view, starting one if there is none.
+    /// The lines are merged by the timestamps `parser` reads, see [`MergedFileState`].
+    fn merge(&mut self, name: String, parser: timestamp::Parser, utc_offset: UtcOffset) {
+        if let Some(merged) = self.merged.as_mut() {
+            merged.add(name);
+            return;
+        }
 
-            // Use custom border set to merge [Numbers] and [Text] bottom borders.
-            let border_set = symbols::border::Set {
-                bottom_left: symbols::line::NORMAL.horizontal_up,
-                top_left: symbols::line::NORMAL.horizontal_down,
-                ..symbols::border::PLAIN
-            };
+        self.merged = Some(MergedFileState::new(
+            [self.name.clone(), name],
+            parser,
+            utc_offset,
+        ));
+        self.search = None;
+        self.filter = None;
+        self.stick_to_bottom = false;
+    }
 
-            let par = Paragraph::new(lines).block(
-                Block::new()
-                    .borders(Borders::LEFT | Borders::TOP | Borders::BOTTOM)
-                    .border_style(Style::default().dark_gray())
-                    .border_set(border_set),
-            );
+    fn title(&self) -> String {
+        self.merged.as_ref().map_or_else(
+            || self.name.clone(),
+            |merged| merged.sources.iter().map(|source| &source.name).join(" + "),
+        )
+    }
 
-            Widget::render(par, layout.text, buf);
+    /// The collapsed runs, if they are shown, the filtered and the merged views are not collapsed.
+    fn shown_runs(&self) -> Option<&Collapsed> {
+        self.collapsed
+            .as_ref()
+            .filter(|_| self.filter.is_none() && self.merged.is_none())
+    }
+
+    /// Scrolls by `lines`, or by the runs of equal lines when they are collapsed.
+    fn scroll_up(&mut self, lines: u32) {
+        let full = self.filter.is_none() && self.merged.is_none();
+        let collapsed = self
+            .collapsed
+            .as_mut()
+            .filter(|_| full && lines < self.scroll_offset);
+
+        if let Some(top) = collapsed.and_then(|collapsed| collapsed.scroll_up(lines)) {
+            self.scroll_offset = top;
+        } else {
+            let offset = self.view_offset_mut();
+            *offset = offset.saturating_sub(lines);
         }
+        self.stick_to_bottom = false;
+    }
 
-        // Top-right corner
-        {
-            let block = Block::new()
-                .borders(Borders::TOP | Borders::RIGHT)
-                .border_style(Style::default().dark_gray());
+    fn scroll_down(&mut self, lines: u32, height: u32) {
+        let full = self.filter.is_none() && self.merged.is_none();
+        let collapsed = self
+            .collapsed
+            .as_mut()
+            .filter(|_| full && lines < self.total_lines);
 
-            Widget::render(block, layout.top_right_corner, buf);
+        if let Some(collapsed) = collapsed {
+            if let Some(top) = collapsed.scroll_down(lines, height) {
+                self.scroll_offset = top;
+            }
+        } else {
+            let last = self.view_len().saturating_sub(height);
+            let offset = self.view_offset_mut();
+            *offset = offset.saturating_add(lines).min(last);
         }
+        self.stick_to_bottom = false;
+    }
 
-        // Scrollbar
-        {
-            if active_state.total_lines > frame_height {
-                let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight)
-                    .begin_symbol(None)
-                    .end_symbol(None)
-                    .track_symbol("│".into())
-                    .thumb_symbol("┃");
+    fn search(&mut self, needle: String, options: SearchOptions) {
+        self.search = Some(Search {
+            re: options.regex(&needle),
+            needle,
+            options,
+            current: None,
+            next: Some((self.scroll_offset, SearchDirection::Forward)),
+            pending: None,
+            matches: vec![],
+            counted: 0,
+            counting: None,
+        });
+    }
 
-                let mut scrollbar_state =
-                    ScrollbarState::new(active_state.total_lines.saturating_sub(frame_height) as _)
-                        .position(active_state.scroll_offset as _);
+    const fn search_next(&mut self, direction: SearchDirection) {
+        if let Some(search) = self.search.as_mut() {
+            let from = match (search.current, direction) {
+                (Some(current), SearchDirection::Forward) => current.saturating_add(1),
+                (Some(current), SearchDirection::Backward) => current,
+                (None, _) => self.scroll_offset,
+            };
+            search.next = Some((from, direction));
+        }
+    }
 
-                StatefulWidget::render(scrollbar, layout.scrollbar, buf, &mut scrollbar_state);
-            } else {
-                let block = Block::new()
-                    .borders(Borders::RIGHT)
-                    .border_style(Style::default().dark_gray());
+    /// Sends the requested search and checks for its result.
+    /// Returns a message if nothing was found.
+    fn poll_search(&mut self, repo: &impl RepoLines, height: u32) -> Option<String> {
+        let search = self.search.as_mut()?;
 
-                Widget::render(block, layout.scrollbar, buf);
-            }
+        if let Some((from, direction)) = search.next.take() {
+            search.pending =
+                Some(repo.find(&self.name, &search.needle, search.options, from, direction));
         }
 
-        // Bottom-right corner
-        {
-            let block = Block::new()
-                .borders(Borders::BOTTOM | Borders::RIGHT)
-                .border_style(Style::default().dark_gray());
+        let found = search.pending.as_mut()?.try_recv();
+        if found != Err(TryRecvError::Empty) {
+            search.pending = None;
+        }
 
-            Widget::render(block, layout.bottom_right_corner, buf);
+        match found {
+            Ok(Some(index)) => {
+                search.current = Some(index);
+                self.scroll_into_view(index, height);
+                None
+            }
+            Ok(None) => Some(format!("Pattern not found: {}", search.needle)),
+            Err(_) => None,
         }
     }
-}
 
-struct FileViewLayout {
-    tabs: Rect,
-    numbers: Rect,
-    text: Rect,
-    top_right_corner: Rect,
-    scrollbar: Rect,
-    bottom_right_corner: Rect,
-}
+    /// Collects the matches counted so far and counts the ones in the lines appended since.
+    /// The matches counted in a `reindexed` file are dropped.
+    fn poll_count(&mut self, repo: &impl RepoLines, reindexed: bool) {
+        let Some(search) = self.search.as_mut() else {
+            return;
+        };
 
-/// Layout of the file view
-///  ```
-/// [          tabs       ]
-/// [numbers][text] [scrollbar]
-///                 [bottom_right_corner]
-/// ```
-impl FileViewLayout {
-    fn new(area: Rect, number_column_width: u16) -> Self {
-        let layout = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([Constraint::Length(1), Constraint::Fill(1)])
-            .split(area);
+        // The file was replaced, start over.
+        if reindexed {
+            search.matches.clear();
+            search.counted = 0;
+            search.counting = None;
+        }
+
+        if let Some(counting) = search.counting.as_mut() {
+            loop {
+                match counting.try_recv() {
+                    Ok(batch) => search.matches.extend(batch),
+                    Err(mpsc::error::TryRecvError::Empty) => return,
+                    Err(mpsc::error::TryRecvError::Disconnected) => break,
+                }
+            }
+            search.counting = None;
+        }
 
-        let tabs = layout[0];
+        if search.counted < self.total_lines {
+            search.counting = Some(repo.count_matches(
+                &self.name,
+                &search.needle,
+                search.options,
+                search.counted,
+                self.total_lines,
+            ));
+            search.counted = self.total_lines;
+        }
+    }
 
-        let main = Layout::horizontal(vec![
-            Constraint::Length(number_column_width),
-            Constraint::Fill(1),
-            Constraint::Length(1),
-        ])
-        .split(layout[1]);
+    /// Shows the match, counting from one, as if the search found it.
+    /// Returns a message if there is no such match or it is not counted yet.
+    fn go_to_match(&mut self, number: usize, height: u32) -> Option<String> {
+        let Some(search) = self.search.as_mut() else {
+            return Some("Search for a pattern first".to_string());
+        };
 
-        let numbers = main[0];
-        let text = main[1];
+        match number.checked_sub(1).and_then(|i| search.matches.get(i)) {
+            Some(&index) => {
+                search.current = Some(index);
+                search.next = None;
+                search.pending = None;
+                self.scroll_into_view(index, height);
+                None
+            }
+            None if search.counting.is_some() => Some(format!("Match {number} is not counted yet")),
+            None => Some(format!(
+                "No match {number}, there are {}",
+                search.matches.len()
+            )),
+        }
+    }
 
-        // Split scrollbar area into 3 rows because Scrollbar cannot be wrapped with a borderd block.
-        let scrollbar_area = Layout::vertical(vec![
-            Constraint::Length(1),
-            Constraint::Fill(1),
-            Constraint::Length(1),
-        ])
-        .split(main[2]);
+    /// The position of the last found line among the matches and their number,
+    /// with an ellipsis while they are still counted.
+    fn match_counter(&self) -> Option<String> {
+        let search = self.search.as_ref()?;
+        let total = thousands(u32::try_from(search.matches.len()).unwrap_or(u32::MAX));
+        let more = if search.counting.is_some() { "…" } else { "" };
+
+        let position = search
+            .current
+            .and_then(|current| search.matches.binary_search(&current).ok());
+        Some(position.map_or_else(
+            || format!("{total} matches{more}"),
+            |i| {
+                format!(
+                    "match {} of {total}{more}",
+                    thousands(u32::try_from(i + 1).unwrap_or(u32::MAX))
+                )
+            },
+        ))
+    }
 
-        let top_right_corner = scrollbar_area[0];
-        let scrollbar = scrollbar_area[1];
-        let bottom_right_corner = scrollbar_area[2];
+    /// Scrolls the view so the line, counting from one, is on the top.
+    fn go_to_line(&mut self, line: u32, height: u32) {
+        self.scroll_offset = line
+            .saturating_sub(1)
+            .min(self.total_lines.saturating_sub(height));
+        self.stick_to_bottom = false;
+    }
 
-        Self {
-            tabs,
-            numbers,
-            text,
-            top_right_corner,
-            scrollbar,
-            bottom_right_corner,
+    /// Scrolls the view as little as needed to show the line.
+    const fn keep_in_view(&mut self, index: u32, height: u32) {
+        if index < self.scroll_offset {
+            self.scroll_offset = index;
+        } else if index >= self.scroll_offset.saturating_add(height) {
+            self.scroll_offset = (index + 1).saturating_sub(height);
+        }
+        self.stick_to_bottom = false;
+    }
+
+    /// Starts selecting at the top line, or drops the selection.
+    /// Returns a message if the view does not show the lines one by one.
+    fn toggle_selection(&mut self) -> Option<String> {
+        if self.selection.take().is_some() {
+            return None;
         }
+        if self.filter.is_some() || self.collapsed.is_some() || self.merged.is_some() {
+            return Some("Selecting works in the full view only".to_string());
+        }
+
+        self.selection = self.top_line().map(Selection::new);
+        None
+    }
+
+    fn selected_lines(&self) -> Option<Range<u32>> {
+        self.selection.as_ref().map(Selection::lines)
+    }
+
+    /// Scrolls the view so the line is on the top, unless it is visible already.
+    fn scroll_into_view(&mut self, index: u32, height: u32) {
+        if index < self.scroll_offset || index >= self.scroll_offset.saturating_add(height) {
+            self.scroll_offset = index.min(self.total_lines.saturating_sub(height));
+        }
+        self.stick_to_bottom = false;
+    }
+
+    /// Keeps the text from scrolling past the longest visible line.
+    fn clamp_horizontal_offset(&mut self) {
+        let longest = self
+            .display_lines
+            .iter()
+            .map(|line| Line::raw(line.as_ref()).width())
+            .max()
+            .unwrap_or_default();
+
+        self.horizontal_offset = self
+            .horizontal_offset
+            .min(u16::try_from(longest).unwrap_or(u16::MAX));
+    }
+
+    /// The selected or the visible lines, or the lines in the view: the filtered lines,
+    /// the search matches or the whole file.
+    fn export(&self, path: PathBuf, visible: bool, height: u32) -> Export {
+        let filter = self
+            .filter
+            .as_ref()
+            .map(|filter| (filter.needle.clone(), filter.options));
+
+        let (lines, matching) = match (visible, self.selected_lines()) {
+            (true, Some(selected)) => (selected, None),
+            (true, None) => {
+                let numbers = self.visible_numbers(height);
+                // The last row might be a run of lines.
+                let end = |last: u32| {
+                    self.shown_runs()
+                        .and_then(|collapsed| collapsed.after.get(numbers.len() - 1))
+                        .map_or(last + 1, Run::end)
+                };
+                let lines = match (numbers.first(), numbers.last()) {
+                    (Some(&first), Some(&last)) => first..end(last),
+                    _ => 0..0,
+                };
+                (lines, filter)
+            }
+            (false, _) => {
+                let search = self
+                    .search
+                    .as_ref()
+                    .map(|search| (search.needle.clone(), search.options));
+                (0..self.total_lines, filter.or(search))
+            }
+        };
+
+        Export {
+            name: self.name.clone(),
+            path,
+            lines,
+            matching,
+            pending: None,
+        }
+    }
+
+    /// The index of the top line in the full or the filtered view.
+    fn top_line(&self) -> Option<u32> {
+        self.filter.as_ref().map_or_else(
+            || (self.scroll_offset < self.total_lines).then_some(self.scroll_offset),
+            |filter| {
+                filter
+                    .lines
+                    .get(filter.scroll_offset as usize)
+                    .map(|(index, _)| *index)
+            },
+        )
+    }
+
+    /// Bookmarks the top line or removes its bookmark, returns a message telling which.
+    fn toggle_bookmark(&mut self) -> Option<String> {
+        let line = self.top_line()?;
+
+        Some(if self.bookmarks.remove(&line) {
+            format!("Removed the bookmark of line {}", line + 1)
+        } else {
+            self.bookmarks.insert(line);
+            format!("Bookmarked line {}", line + 1)
+        })
+    }
+
+    /// Scrolls the next bookmarked line in the view to the top, as far as the view scrolls.
+    /// Returns `false` if there is no bookmark to go to.
+    fn go_to_bookmark(&mut self, direction: SearchDirection, height: u32) -> bool {
+        let offset = self.view_offset();
+
+        // Where the bookmarked lines are in the view, a filter might hide some.
+        let mut positions = self
+            .bookmarks
+            .iter()
+            .filter_map(|&line| match &self.filter {
+                Some(filter) => filter
+                    .lines
+                    .binary_search_by_key(&line, |(index, _)| *index)
+                    .ok()
+                    .and_then(|position| u32::try_from(position).ok()),
+                None => (line < self.total_lines).then_some(line),
+            });
+
+        let position = match direction {
+            SearchDirection::Forward => positions.find(|&position| position > offset),
+            SearchDirection::Backward => positions.rev().find(|&position| position < offset),
+        };
+
+        let Some(position) =
+            position.map(|position| position.min(self.view_len().saturating_sub(height)))
+        else {
+            return false;
+        };
+        if position == offset {
+            return false;
+        }
+
+        *self.view_offset_mut() = position;
+        self.stick_to_bottom = false;
+        true
+    }
+}
+
+impl From<FileInfo> for FileState {
+    fn from(info: FileInfo) -> Self {
+        Self {
+            name: info.name,
+            total_lines: info.number_of_lines,
+            scroll_offset: 0,
+            display_lines: Box::default(),
+            pending_lines: 0,
+            stick_to_bottom: false,
+            search: None,
+            horizontal_offset: 0,
+            filter: None,
+            merged: None,
+            removed: false,
+            bookmarks: BTreeSet::new(),
+            page: None,
+            collapsed: None,
+            tail: TailOnOpen::Off,
+            selection: None,
+            restored: false,
+            offsets: Box::default(),
+            index_version: 0,
+        }
+    }
+}
+
+/// A tab shows a file, or two files side by side.
+struct Tab {
+    file: FileState,
+    /// The right pane.
+    split: Option<FileState>,
+    /// Keys go to the right pane.
+    split_focused: bool,
+}
+
+impl Tab {
+    /// The pane receiving the keys.
+    const fn focused(&self) -> &FileState {
+        match self.split.as_ref() {
+            Some(split) if self.split_focused => split,
+            _ => &self.file,
+        }
+    }
+
+    /// The pane receiving the keys.
+    const fn focused_mut(&mut self) -> &mut FileState {
+        match self.split.as_mut() {
+            Some(split) if self.split_focused => split,
+            _ => &mut self.file,
+        }
+    }
+
+    fn title(&self) -> String {
+        self.split.as_ref().map_or_else(
+            || self.file.title(),
+            |split| format!("{} | {}", self.file.title(), split.title()),
+        )
+    }
+
+    /// Closes the focused pane and returns it, `None` if the tab is not split.
+    fn close_pane(&mut self) -> Option<FileState> {
+        let split = self.split.take()?;
+
+        let closed = if self.split_focused {
+            split
+        } else {
+            std::mem::replace(&mut self.file, split)
+        };
+        self.split_focused = false;
+
+        Some(closed)
+    }
+}
+
+impl From<FileInfo> for Tab {
+    fn from(info: FileInfo) -> Self {
+        Self {
+            file: info.into(),
+            split: None,
+            split_focused: false,
+        }
+    }
+}
+
+/// How to open a file picked in the file list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpenMode {
+    /// In a new tab.
+    Tab,
+    /// Next to the file in the active tab.
+    Split,
+    /// Into the view of the active tab.
+    Merge,
+}
+
+pub struct FileViewState {
+    height: u32,
+    files: Vec<Tab>,
+    tabs: FileTabsState,
+    prompt: Option<(PromptKind, PromptState)>,
+    /// Shown at the bottom until the next key press.
+    message: Option<String>,
+    long_lines: LongLineMode,
+    /// Color lines by their log level.
+    level_colors: bool,
+    line_numbers: LineNumberMode,
+    number_format: NumberFormat,
+    /// Matches of the regex are highlighted in every file.
+    highlight: Option<regex::Regex>,
+    /// The options of the last search or filter prompt.
+    search_options: SearchOptions,
+    /// The bookmarked lines of the focused file, shown in a popup.
+    bookmark_list: Option<Vec<(u32, Arc<str>)>>,
+    export: Option<Export>,
+    /// Where the files opened from now on start.
+    tail_on_open: TailOnOpen,
+    /// Reads the timestamps the merged files are interleaved by.
+    merge_format: timestamp::Parser,
+    /// The offset of the timestamps logged without one.
+    utc_offset: UtcOffset,
+    /// The scroll offsets of the closed files, they start there when opened again.
+    closed_offsets: HashMap<String, u32>,
+}
+
+impl Default for FileViewState {
+    fn default() -> Self {
+        Self {
+            height: 0,
+            files: vec![],
+            tabs: FileTabsState::default(),
+            prompt: None,
+            message: None,
+            long_lines: LongLineMode::default(),
+            level_colors: true,
+            line_numbers: LineNumberMode::default(),
+            number_format: NumberFormat::default(),
+            highlight: None,
+            search_options: SearchOptions::default(),
+            bookmark_list: None,
+            export: None,
+            tail_on_open: TailOnOpen::Off,
+            merge_format: timestamp::any,
+            utc_offset: UtcOffset::UTC,
+            closed_offsets: HashMap::new(),
+        }
+    }
+}
+
+/// What the numbers column shows.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum LineNumberMode {
+    /// The line numbers in the file.
+    #[default]
+    Absolute,
+    /// The number of the top line, then the distances to it, for moving by a count.
+    Relative,
+}
+
+impl LineNumberMode {
+    const fn toggled(self) -> Self {
+        match self {
+            Self::Absolute => Self::Relative,
+            Self::Relative => Self::Absolute,
+        }
+    }
+}
+
+/// How the numbers column writes the line numbers, the relative distances are always plain.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NumberFormat {
+    /// `1234567`.
+    #[default]
+    Plain,
+    /// `1,234,567`.
+    Grouped,
+    /// The byte offsets of the lines in their files, in hex.
+    Offset,
+}
+
+/// How the lines wider than the view are shown.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum LongLineMode {
+    /// Cut at the edge, the view scrolls horizontally.
+    #[default]
+    Scroll,
+    /// Wrapped from the first column, there is nothing to scroll horizontally.
+    Wrap,
+}
+
+impl LongLineMode {
+    const fn toggled(self) -> Self {
+        match self {
+            Self::Scroll => Self::Wrap,
+            Self::Wrap => Self::Scroll,
+        }
+    }
+
+    const fn wraps(self) -> bool {
+        matches!(self, Self::Wrap)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PromptKind {
+    Search,
+    GoToLine,
+    /// The number of a search match, counting from one.
+    GoToMatch,
+    Filter,
+    Highlight,
+    /// The path to write the visible lines, or the whole view, to.
+    Export {
+        visible: bool,
+    },
+}
+
+impl KeyEventHandler for FileViewState {
+    type Action = ();
+
+    fn handle_key_event(&mut self, event: &KeyEvent) -> Option<Self::Action> {
+        if self.prompt.is_some() {
+            self.handle_prompt_key(event);
+            return None;
+        }
+
+        if event.kind == KeyEventKind::Press {
+            self.message = None;
+        }
+
+        let active = self.files.get_mut(self.tabs.selected())?.focused_mut();
+
+        if active.handle_movement_key(event, active.page(self.height), self.long_lines) {
+            return None;
+        }
+
+        let with_control = event.modifiers.contains(KeyModifiers::CONTROL);
+
+        match (event.kind, event.code) {
+            (KeyEventKind::Press, KeyCode::Char('w')) if with_control => {
+                self.close_active();
+            }
+            (KeyEventKind::Press, KeyCode::Char('w')) => {
+                self.toggle_wrap();
+            }
+            (KeyEventKind::Press, KeyCode::Char('c')) => {
+                self.level_colors = !self.level_colors;
+            }
+            (KeyEventKind::Press, KeyCode::Char('r')) => {
+                self.line_numbers = self.line_numbers.toggled();
+            }
+            (KeyEventKind::Press, KeyCode::Tab) => {
+                self.tabs.next(self.files.len());
+            }
+            (KeyEventKind::Press, KeyCode::BackTab) => {
+                self.tabs.previous(self.files.len());
+            }
+            (KeyEventKind::Press, KeyCode::Left | KeyCode::Right) if with_control => {
+                if let Some(tab) = self.files.get_mut(self.tabs.selected()) {
+                    tab.split_focused = tab.split.is_some() && event.code == KeyCode::Right;
+                }
+            }
+            (KeyEventKind::Press, KeyCode::Char('B')) => {
+                active.stick_to_bottom = true;
+            }
+            // Follow mode, turning it off keeps the current position.
+            (KeyEventKind::Press, KeyCode::Char('F')) => {
+                active.stick_to_bottom = !active.stick_to_bottom;
+            }
+            // Turning it off shows the raw lines from the same top line.
+            (KeyEventKind::Press, KeyCode::Char('D')) => {
+                active.collapsed = active.collapsed.is_none().then(Collapsed::default);
+            }
+            // They work on the lines of a single file.
+            (
+                KeyEventKind::Press,
+                KeyCode::Char(
+                    '/' | '&' | ':' | '#' | 'n' | 'N' | 'm' | 'M' | '[' | ']' | 'e' | 'E',
+                ),
+            ) if active.merged.is_some() => {
+                self.message = Some("Not available in a merged view".to_string());
+            }
+            (KeyEventKind::Press, KeyCode::Char('/')) => {
+                self.prompt = Some((
+                    PromptKind::Search,
+                    PromptState::search('/', self.search_options),
+                ));
+            }
+            // Turning the filter off returns to the full view where it was left.
+            (KeyEventKind::Press, KeyCode::Char('&')) if active.filter.is_some() => {
+                active.filter = None;
+            }
+            (KeyEventKind::Press, KeyCode::Char('&')) => {
+                self.prompt = Some((
+                    PromptKind::Filter,
+                    PromptState::search('&', self.search_options),
+                ));
+            }
+            (KeyEventKind::Press, KeyCode::Char('*')) => {
+                self.prompt = Some((PromptKind::Highlight, PromptState::new('*')));
+            }
+            (KeyEventKind::Press, KeyCode::Char(':')) => {
+                self.prompt = Some((PromptKind::GoToLine, PromptState::digits(':')));
+            }
+            (KeyEventKind::Press, KeyCode::Char('#')) => {
+                self.prompt = Some((PromptKind::GoToMatch, PromptState::digits('#')));
+            }
+            (KeyEventKind::Press, KeyCode::Char(key @ ('e' | 'E'))) => {
+                let kind = PromptKind::Export {
+                    visible: key == 'E',
+                };
+                self.prompt = Some((kind, PromptState::new('>')));
+            }
+            (KeyEventKind::Press, KeyCode::Char('n')) => {
+                active.search_next(SearchDirection::Forward);
+            }
+            (KeyEventKind::Press, KeyCode::Char('N')) => {
+                active.search_next(SearchDirection::Backward);
+            }
+            _ => self.handle_bookmark_key(event),
+        }
+
+        None
+    }
+}
+
+impl FileViewState {
+    /// Starts or drops the selection of the focused file.
+    fn handle_selection_toggle(&mut self, event: &KeyEvent) {
+        let Some(active) = self
+            .files
+            .get_mut(self.tabs.selected())
+            .map(Tab::focused_mut)
+        else {
+            return;
+        };
+
+        match (event.kind, event.code) {
+            (KeyEventKind::Press, KeyCode::Char('V')) => self.message = active.toggle_selection(),
+            (KeyEventKind::Press, KeyCode::Esc) => active.selection = None,
+            _ => {}
+        }
+    }
+
+    fn handle_bookmark_key(&mut self, event: &KeyEvent) {
+        let height = self.height;
+        let Some(active) = self
+            .files
+            .get_mut(self.tabs.selected())
+            .map(Tab::focused_mut)
+        else {
+            return;
+        };
+
+        match (event.kind, event.code) {
+            (KeyEventKind::Press, KeyCode::Char('m')) => {
+                self.message = active.toggle_bookmark();
+            }
+            (KeyEventKind::Press, KeyCode::Char(key @ (']' | '['))) => {
+                let (direction, message) = if key == ']' {
+                    (SearchDirection::Forward, "No bookmark below")
+                } else {
+                    (SearchDirection::Backward, "No bookmark above")
+                };
+                if !active.go_to_bookmark(direction, height) {
+                    self.message = Some(message.to_string());
+                }
+            }
+            (KeyEventKind::Press, KeyCode::Char('M')) => {
+                self.bookmark_list = match self.bookmark_list {
+                    Some(_) => None,
+                    None => Some(vec![]),
+                };
+            }
+            (KeyEventKind::Press, KeyCode::Esc) if self.bookmark_list.is_some() => {
+                self.bookmark_list = None;
+            }
+            _ => self.handle_selection_toggle(event),
+        }
+    }
+}
+
+impl FileState {
+    /// Moves the head of the selection, returns `false` if the key does not move it.
+    /// The view follows the head, the selection may span more lines than it shows.
+    fn handle_selection_key(&mut self, event: &KeyEvent, height: u32) -> bool {
+        if self.filter.is_some() || self.collapsed.is_some() || self.merged.is_some() {
+            return false;
+        }
+        let Some(selection) = self.selection.as_mut() else {
+            return false;
+        };
+
+        let with_shift = event.modifiers.contains(KeyModifiers::SHIFT);
+        let with_control = event.modifiers.contains(KeyModifiers::CONTROL);
+        let step = if with_shift { height } else { 1 };
+        let head = selection.head;
+
+        selection.head = match event.code {
+            KeyCode::Char('u') if with_control => head.saturating_sub(height / 2),
+            KeyCode::Char('d') if with_control => head.saturating_add(height / 2),
+            KeyCode::Up | KeyCode::Char('k') => head.saturating_sub(step),
+            KeyCode::Down | KeyCode::Char('j') => head.saturating_add(step),
+            KeyCode::PageUp => head.saturating_sub(height),
+            KeyCode::PageDown => head.saturating_add(height),
+            KeyCode::Home | KeyCode::Char('g') => 0,
+            KeyCode::End | KeyCode::Char('G') => u32::MAX,
+            _ => return false,
+        }
+        .min(self.total_lines.saturating_sub(1));
+
+        let head = selection.head;
+        self.keep_in_view(head, height);
+        true
+    }
+
+    /// Scrolls the view, returns `false` if the key does not move it.
+    /// The horizontal keys do nothing while the lines wrap.
+    fn handle_movement_key(
+        &mut self,
+        event: &KeyEvent,
+        height: u32,
+        long_lines: LongLineMode,
+    ) -> bool {
+        if event.kind != KeyEventKind::Press {
+            return false;
+        }
+        if self.handle_selection_key(event, height) {
+            return true;
+        }
+
+        let with_shift = event.modifiers.contains(KeyModifiers::SHIFT);
+        let with_control = event.modifiers.contains(KeyModifiers::CONTROL);
+
+        match event.code {
+            KeyCode::Char('u') if with_control => self.scroll_up(height / 2),
+            KeyCode::Char('d') if with_control => self.scroll_down(height / 2, height),
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.scroll_up(if with_shift { height } else { 1 });
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.scroll_down(if with_shift { height } else { 1 }, height);
+            }
+            KeyCode::PageUp => self.scroll_up(height),
+            KeyCode::PageDown => self.scroll_down(height, height),
+            KeyCode::Left | KeyCode::Right | KeyCode::Char('h' | 'l')
+                if !with_shift && !with_control && long_lines.wraps() => {}
+            KeyCode::Left | KeyCode::Char('h') if !with_shift && !with_control => {
+                self.horizontal_offset = self.horizontal_offset.saturating_sub(1);
+            }
+            KeyCode::Right | KeyCode::Char('l') if !with_shift && !with_control => {
+                self.horizontal_offset = self.horizontal_offset.saturating_add(1);
+                self.clamp_horizontal_offset();
+            }
+            KeyCode::Home | KeyCode::Char('g') => self.scroll_up(self.view_offset()),
+            KeyCode::End | KeyCode::Char('G') => self.scroll_down(self.view_len(), height),
+            _ => return false,
+        }
+
+        true
+    }
+}
+
+impl MouseEventHandler for FileViewState {
+    type Action = ();
+
+    fn handle_mouse_event(&mut self, event: MouseEvent) -> Option<Self::Action> {
+        let active = self.files.get_mut(self.tabs.selected())?.focused_mut();
+
+        match event.kind {
+            MouseEventKind::ScrollUp => active.scroll_up(WHEEL_STEP),
+            MouseEventKind::ScrollDown => {
+                active.scroll_down(WHEEL_STEP, active.page(self.height));
+            }
+            _ => {}
+        }
+
+        None
+    }
+}
+
+impl FileViewState {
+    pub fn with_tail_on_open(tail_on_open: TailOnOpen) -> Self {
+        Self {
+            tail_on_open,
+            ..Self::default()
+        }
+    }
+
+    #[must_use]
+    pub const fn number_format(mut self, format: NumberFormat) -> Self {
+        self.number_format = format;
+        self
+    }
+
+    /// The timestamps of the merged lines are read by `parser`, the ones without an offset
+    /// are in `utc_offset`.
+    #[must_use]
+    pub const fn merge_format(mut self, parser: timestamp::Parser, utc_offset: UtcOffset) -> Self {
+        self.merge_format = parser;
+        self.utc_offset = utc_offset;
+        self
+    }
+
+    pub fn open(&mut self, info: FileInfo, mode: OpenMode) {
+        match (mode, self.files.get_mut(self.tabs.selected())) {
+            (OpenMode::Split, Some(tab)) => {
+                tab.split = Some(Self::new_file(
+                    info,
+                    self.tail_on_open,
+                    &self.closed_offsets,
+                ));
+                tab.split_focused = true;
+            }
+            (OpenMode::Merge, Some(tab)) => {
+                tab.focused_mut()
+                    .merge(info.name, self.merge_format, self.utc_offset);
+            }
+            _ => self.push(info),
+        }
+    }
+
+    fn push(&mut self, info: FileInfo) {
+        if let Some(pos) = self.files.iter().position(|tab| tab.file.name == info.name) {
+            self.tabs.select(pos);
+        } else {
+            self.files.push(Tab {
+                file: Self::new_file(info, self.tail_on_open, &self.closed_offsets),
+                split: None,
+                split_focused: false,
+            });
+            self.tabs.select(self.files.len() - 1);
+        }
+    }
+
+    /// A file closed before starts where it was left, other files as `tail_on_open` tells.
+    fn new_file(
+        info: FileInfo,
+        tail_on_open: TailOnOpen,
+        closed_offsets: &HashMap<String, u32>,
+    ) -> FileState {
+        let mut file = FileState::from(info);
+        if let Some(&offset) = closed_offsets.get(&file.name) {
+            file.scroll_offset = offset;
+            file.restored = true;
+        } else {
+            file.tail = tail_on_open;
+        }
+        file
+    }
+
+    /// Opens a tab for the file, it is read once the repository finds it.
+    pub fn open_name(&mut self, name: String) {
+        self.push(Self::unread(name));
+    }
+
+    /// A file known by its name only, it is read once the repository finds it.
+    fn unread(name: String) -> FileInfo {
+        FileInfo {
+            path: name.clone().into(),
+            name,
+            last_update: utils::now(),
+            number_of_lines: 0,
+            present: true,
+        }
+    }
+
+    /// Focuses the file and sticks it to the bottom, opening a tab for it if there is none.
+    pub fn follow(&mut self, name: String) {
+        self.open_name(name);
+
+        if let Some(tab) = self.files.get_mut(self.tabs.selected()) {
+            tab.split_focused = false;
+            tab.file.stick_to_bottom = true;
+        }
+    }
+
+    /// Shows the message at the bottom until the next key press.
+    pub fn show_message(&mut self, message: String) {
+        self.message = Some(message);
+    }
+
+    /// Opens a tab saved in the session, the file is read once the repository finds it.
+    pub fn restore(&mut self, tab: session::Tab) {
+        self.open_name(tab.file.name.clone());
+        if let Some(split) = tab.split.as_ref() {
+            self.open(Self::unread(split.name.clone()), OpenMode::Split);
+        }
+
+        if let Some(restored) = self.files.get_mut(self.tabs.selected()) {
+            restored.file.restore(tab.file);
+            if let Some((file, pane)) = restored.split.as_mut().zip(tab.split) {
+                file.restore(pane);
+            }
+            restored.split_focused = false;
+        }
+    }
+
+    /// The file names of the panes, their scroll offsets and bookmarks.
+    pub fn session(&self) -> Vec<session::Tab> {
+        self.files
+            .iter()
+            .map(|tab| session::Tab {
+                file: tab.file.session(),
+                split: tab.split.as_ref().map(FileState::session),
+            })
+            .collect()
+    }
+
+    /// Closes the merged view or the focused pane of a split tab.
+    /// Otherwise closes the active tab and activates its right neighbour, or the left one for the last tab.
+    fn close_active(&mut self) {
+        let Some(tab) = self.files.get_mut(self.tabs.selected()) else {
+            return;
+        };
+
+        // Back to the single file.
+        if tab.focused_mut().merged.take().is_some() {
+            return;
+        }
+
+        let closed = tab.close_pane().unwrap_or_else(|| {
+            let closed = self.files.remove(self.tabs.selected()).file;
+            self.tabs
+                .select(self.tabs.selected().min(self.files.len().saturating_sub(1)));
+            closed
+        });
+        self.closed_offsets
+            .insert(closed.name, closed.scroll_offset);
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Turns wrapping on or off. The wrapped lines start at the first column,
+    /// so the panes scrolled horizontally are scrolled back.
+    fn toggle_wrap(&mut self) {
+        self.long_lines = self.long_lines.toggled();
+        if self.long_lines.wraps() {
+            for tab in &mut self.files {
+                for pane in std::iter::once(&mut tab.file).chain(tab.split.as_mut()) {
+                    pane.horizontal_offset = 0;
+                }
+            }
+        }
+    }
+
+    fn handle_prompt_key(&mut self, event: &KeyEvent) {
+        let Some((kind, prompt)) = self.prompt.as_mut() else {
+            return;
+        };
+
+        match prompt.handle_key_event(event) {
+            Some(PromptAction::Submit(input)) => {
+                let kind = *kind;
+                if let Some(options) = prompt.search_options() {
+                    self.search_options = options;
+                }
+                self.prompt = None;
+                self.submit(kind, input);
+            }
+            Some(PromptAction::Cancel) => self.prompt = None,
+            None => {}
+        }
+    }
+
+    fn submit(&mut self, kind: PromptKind, input: String) {
+        let height = self.height;
+        let Some(active) = self
+            .files
+            .get_mut(self.tabs.selected())
+            .map(Tab::focused_mut)
+        else {
+            return;
+        };
+
+        match kind {
+            // Repeat the last search, like `less` does.
+            PromptKind::Search if input.is_empty() => active.search_next(SearchDirection::Forward),
+            PromptKind::Search => active.search(input, self.search_options),
+            PromptKind::Filter
+            | PromptKind::GoToLine
+            | PromptKind::GoToMatch
+            | PromptKind::Export { .. }
+                if input.is_empty() => {}
+            PromptKind::Filter => active.filter(input, self.search_options),
+            PromptKind::GoToLine => match input.parse() {
+                Ok(line) => active.go_to_line(line, height),
+                Err(_) => self.message = Some(format!("Invalid line number: {input}")),
+            },
+            PromptKind::GoToMatch => match input.parse() {
+                Ok(number) => {
+                    if let Some(message) = active.go_to_match(number, height) {
+                        self.message = Some(message);
+                    }
+                }
+                Err(_) => self.message = Some(format!("Invalid match number: {input}")),
+            },
+            PromptKind::Highlight => self.highlight(&input),
+            PromptKind::Export { visible } => {
+                self.export = Some(active.export(input.into(), visible, height));
+            }
+        }
+    }
+
+    /// Highlights the matches of the regex, an empty one turns highlighting off.
+    fn highlight(&mut self, input: &str) {
+        if input.is_empty() {
+            self.highlight = None;
+            return;
+        }
+
+        match regex::Regex::new(input) {
+            Ok(re) => self.highlight = Some(re),
+            // The syntax errors span several lines, the last one tells what is wrong.
+            Err(error) => {
+                let error = error.to_string();
+                self.message = Some(format!(
+                    "Invalid regex: {}",
+                    error.lines().last().unwrap_or_default().trim()
+                ));
+            }
+        }
+    }
+
+    /// Requests the export and checks for its result, returns a message once it is done.
+    fn poll_export(&mut self, repo: &impl RepoLines) -> Option<String> {
+        let export = self.export.as_mut()?;
+
+        let pending = export.pending.get_or_insert_with(|| {
+            repo.export(
+                &export.name,
+                export.lines.start,
+                export.lines.end,
+                export
+                    .matching
+                    .as_ref()
+                    .map(|(needle, options)| (needle.as_str(), *options)),
+                export.path.clone(),
+            )
+        });
+
+        let message = match pending.try_recv() {
+            Err(TryRecvError::Empty) => return None,
+            Ok(Ok(bytes)) => format!("Exported {bytes} bytes to {}", export.path.display()),
+            Ok(Err(error)) => format!("Failed to export to {}: {error}", export.path.display()),
+            Err(TryRecvError::Closed) => format!("Failed to export to {}", export.path.display()),
+        };
+
+        self.export = None;
+        Some(message)
+    }
+
+    /// Returns `true` if key presses go to a prompt.
+    pub const fn is_prompting(&self) -> bool {
+        self.prompt.is_some()
+    }
+
+    pub fn update(&mut self, repo: &impl RepoLines) {
+        if let Some(message) = self.poll_export(repo) {
+            self.message = Some(message);
+        }
+
+        let Some(tab) = self.files.get_mut(self.tabs.selected()) else {
+            return;
+        };
+
+        for file in std::iter::once(&mut tab.file).chain(tab.split.as_mut()) {
+            if let Some(message) = file.update(repo, self.height) {
+                self.message = Some(message);
+            }
+            if self.number_format == NumberFormat::Offset {
+                file.read_offsets(repo, self.height);
+            }
+        }
+
+        if let Some(list) = self.bookmark_list.as_mut() {
+            let file = tab.focused();
+            *list = file
+                .bookmarks
+                .iter()
+                .map(|&line| {
+                    let text = repo
+                        .loaded_lines(&file.name, line, line + 1)
+                        .first()
+                        .cloned();
+                    (line, text.unwrap_or_else(|| Arc::from("")))
+                })
+                .collect();
+        }
+    }
+}
+
+impl FileState {
+    /// Starts where the pane was saved, the offset is clamped once the number of lines is known.
+    fn restore(&mut self, pane: session::Pane) {
+        self.tail = TailOnOpen::Off;
+        self.scroll_offset = pane.scroll_offset;
+        self.restored = true;
+        self.bookmarks = pane.bookmarks.into_iter().collect();
+    }
+
+    fn session(&self) -> session::Pane {
+        session::Pane {
+            name: self.name.clone(),
+            scroll_offset: self.scroll_offset,
+            bookmarks: self.bookmarks.iter().copied().collect(),
+        }
+    }
+
+    /// Reads the byte offsets of the lines in the view, each in the file it comes from.
+    fn read_offsets(&mut self, repo: &impl RepoLines, height: u32) {
+        self.offsets = self.merged.as_ref().map_or_else(
+            || {
+                self.visible_numbers(height)
+                    .into_iter()
+                    .map(|line| repo.offset_of(&self.name, line))
+                    .collect()
+            },
+            |merged| {
+                merged
+                    .visible_lines(height)
+                    .map(|&(source, line)| repo.offset_of(&merged.sources[source].name, line))
+                    .collect()
+            },
+        );
+    }
+
+    /// Reads the visible lines, returns a message if a search found nothing.
+    fn update(&mut self, repo: &impl RepoLines, height: u32) -> Option<String> {
+        let removed = repo.is_removed(&self.name);
+        if self.removed && !removed {
+            // The positions and matches belong to the old content.
+            self.scroll_offset = 0;
+            self.search = None;
+            self.filter = None;
+            self.bookmarks.clear();
+            self.selection = None;
+        }
+        self.removed = removed;
+        self.pending_lines = 0;
+
+        if self.removed {
+            self.total_lines = 0;
+            self.display_lines = Box::default();
+            return None;
+        }
+
+        self.total_lines = repo.total(&self.name);
+        let index_version = repo.index_version(&self.name);
+        let reindexed = std::mem::replace(&mut self.index_version, index_version) != index_version;
+
+        // Not known until the repository finds the file.
+        // The file might have been truncated since the offset was saved.
+        if self.total_lines > 0 && std::mem::take(&mut self.restored) {
+            self.scroll_offset = self
+                .scroll_offset
+                .min(self.total_lines.saturating_sub(self.page(height)));
+        }
+        if self.total_lines > 0 {
+            let tail = match std::mem::take(&mut self.tail) {
+                TailOnOpen::Off => None,
+                TailOnOpen::Page => Some(self.page(height)),
+                TailOnOpen::Lines(lines) => Some(lines),
+            };
+            if let Some(lines) = tail {
+                self.scroll_offset = self.total_lines.saturating_sub(lines);
+            }
+        }
+
+        if let Some(merged) = self.merged.as_mut() {
+            merged.update(repo);
+
+            if self.stick_to_bottom {
+                *self.view_offset_mut() = self.view_len().saturating_sub(self.page(height));
+            }
+
+            self.display_lines = self.merged.as_ref()?.display_lines(repo, height);
+            self.clamp_horizontal_offset();
+            return None;
+        }
+
+        let message = self.poll_search(repo, height);
+
+        self.poll_filter(repo, reindexed);
+        self.poll_count(repo, reindexed);
+
+        if let Some(selection) = self.selection.as_mut() {
+            let lines = selection.lines();
+            if selection
+                .bytes
+                .as_ref()
+                .is_none_or(|(counted, _)| *counted != lines)
+            {
+                let bytes = repo.byte_len(&self.name, lines.start, lines.end);
+                selection.bytes = Some((lines, bytes));
+            }
+        }
+
+        if self.filter.is_none() && self.collapsed.is_some() {
+            self.read_runs(repo, height);
+            self.clamp_horizontal_offset();
+            return message;
+        }
+
+        // Scroll before reading, so appended lines are shown on the same update.
+        if self.stick_to_bottom {
+            *self.view_offset_mut() = self.view_len().saturating_sub(self.page(height));
+        }
+
+        (self.display_lines, self.pending_lines) = self.filter.as_ref().map_or_else(
+            || self.read_lines(repo, height),
+            |filter| {
+                let lines = filter
+                    .lines
+                    .iter()
+                    .skip(filter.scroll_offset as usize)
+                    .take(height as usize)
+                    .map(|(_, line)| line.clone())
+                    .collect();
+                (lines, 0)
+            },
+        );
+        self.clamp_horizontal_offset();
+
+        message
+    }
+
+    /// The leading loaded lines of the view and the number of the rest, still being read.
+    /// A line loaded after a pending one is shown on a later update with the rest.
+    fn read_lines(&self, repo: &impl RepoLines, height: u32) -> (Box<[Arc<str>]>, u32) {
+        let lines = repo.lines(
+            &self.name,
+            self.scroll_offset,
+            (self.scroll_offset + height).min(self.total_lines),
+        );
+        let rows = lines.len();
+        let loaded = lines
+            .into_vec()
+            .into_iter()
+            .map_while(LineState::loaded)
+            .collect::<Box<_>>();
+
+        let pending = u32::try_from(rows - loaded.len()).unwrap_or(u32::MAX);
+        (loaded, pending)
+    }
+
+    /// Reads the runs of equal lines around the top line, a row for each run.
+    fn read_runs(&mut self, repo: &impl RepoLines, height: u32) {
+        let total = self.total_lines;
+        if self.stick_to_bottom {
+            self.scroll_offset = total;
+        }
+
+        let mut after = runs_after(repo, &self.name, self.scroll_offset, total, height as usize);
+        let mut before = runs_before(repo, &self.name, self.scroll_offset, height as usize);
+        let at_end = after.last().is_none_or(|run| run.end() >= total);
+
+        // Like the full view, the last page is not left half empty.
+        let missing = (self.page(height) as usize).saturating_sub(after.len());
+        if at_end && missing > 0 {
+            let mut moved = before.split_off(before.len().saturating_sub(missing));
+            if let Some(first) = moved.first() {
+                self.scroll_offset = first.start;
+            }
+            join_runs(&mut moved, after);
+            after = moved;
+        }
+
+        let shown = after.iter().take(height as usize).collect_vec();
+        let loaded = shown
+            .iter()
+            .map_while(|run| run.line.clone().loaded())
+            .collect::<Box<_>>();
+        self.pending_lines = u32::try_from(shown.len() - loaded.len()).unwrap_or(u32::MAX);
+        self.display_lines = loaded;
+
+        self.collapsed = Some(Collapsed {
+            before,
+            after,
+            at_end,
+        });
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FileView {
+    pub theme: Theme,
+}
+
+impl StatefulWidget for FileView {
+    type State = FileViewState;
+
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        let has_bottom_line = state.prompt.is_some() || state.message.is_some();
+
+        // Update the visible lines count
+        state.height = area
+            .height
+            .saturating_sub(4 + u16::from(has_bottom_line))
+            .into();
+
+        let layout = FileViewLayout::new(area, has_bottom_line);
+
+        // Tabs
+        {
+            FileTabs::new(state.files.iter().map(Tab::title).collect_vec())
+                .highlight_style(Style::default().bold().fg(self.theme.highlight.fg))
+                .marker_style(Style::default().fg(self.theme.dim))
+                .render(layout.tabs, buf, &mut state.tabs);
+        }
+
+        let [left, right] = Layout::horizontal([Constraint::Fill(1); 2]).areas(layout.main);
+
+        // The wrapped lines of the last update decide the page of the next keys.
+        if let Some(tab) = state.files.get_mut(state.tabs.selected()) {
+            let (height, wrap) = (state.height, state.long_lines.wraps());
+            let (mode, format) = (state.line_numbers, state.number_format);
+            if let Some(split) = tab.split.as_mut() {
+                tab.file.learn_page(left, height, wrap, mode, format);
+                split.learn_page(right, height, wrap, mode, format);
+            } else {
+                tab.file.learn_page(layout.main, height, wrap, mode, format);
+            }
+        }
+
+        // Panes
+        if let Some(tab) = state.files.get(state.tabs.selected()) {
+            let pane = |file, highlighted| Pane {
+                file,
+                height: state.height,
+                wrap: state.long_lines.wraps(),
+                line_numbers: state.line_numbers,
+                number_format: state.number_format,
+                levels: state.level_colors.then_some(&self.theme.levels),
+                theme: &self.theme,
+                highlight: state.highlight.as_ref(),
+                highlighted,
+            };
+
+            if let Some(split) = tab.split.as_ref() {
+                pane(&tab.file, !tab.split_focused).render(left, buf);
+                pane(split, tab.split_focused).render(right, buf);
+            } else {
+                pane(&tab.file, false).render(layout.main, buf);
+            }
+
+            // Status bar
+            Paragraph::new(tab.focused().status())
+                .reversed()
+                .render(layout.status_bar, buf);
+
+            // Bookmarks popup
+            if let Some(list) = state.bookmark_list.as_deref() {
+                let digits = tab.focused().number_width() as usize;
+                let lines = if list.is_empty() {
+                    vec![Line::from("No bookmarks, press m to add one").fg(self.theme.dim)]
+                } else {
+                    list.iter()
+                        .map(|(line, text)| {
+                            Line::from(vec![
+                                Span::raw(format!("{:>digits$}  ", line + 1))
+                                    .fg(self.theme.bookmark),
+                                Span::raw(text.as_ref()),
+                            ])
+                        })
+                        .collect()
+                };
+
+                let popup = layout.main.inner_centered(80, 60);
+                Clear.render(popup, buf);
+                Paragraph::new(lines)
+                    .block(Block::bordered().title("Bookmarks"))
+                    .render(popup, buf);
+            }
+        }
+
+        // Bottom line
+        {
+            if let Some((_, prompt)) = state.prompt.as_mut() {
+                StatefulWidget::render(Prompt {}, layout.bottom_line, buf, prompt);
+            } else if let Some(message) = state.message.as_deref() {
+                Widget::render(Paragraph::new(message), layout.bottom_line, buf);
+            }
+        }
+    }
+}
+
+/// A file with its line numbers and scrollbar.
+struct Pane<'a> {
+    file: &'a FileState,
+    height: u32,
+    wrap: bool,
+    line_numbers: LineNumberMode,
+    number_format: NumberFormat,
+    /// Colors of the log levels, if enabled.
+    levels: Option<&'a Levels>,
+    theme: &'a Theme,
+    highlight: Option<&'a regex::Regex>,
+    /// Marks the focused pane of a split tab.
+    highlighted: bool,
+}
+
+impl Widget for Pane<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let file = self.file;
+        let frame_height = self.height;
+        let layout = PaneLayout::new(
+            area,
+            file.number_column_width(self.line_numbers, self.number_format, frame_height),
+        );
+
+        let border_style = Style::default().fg(if self.highlighted {
+            self.theme.focused_border
+        } else {
+            self.theme.border
+        });
+
+        // Minus the left border.
+        let wrap_width = self.wrap.then(|| layout.text.width.saturating_sub(1));
+
+        // Numbers column
+        {
+            let column = Paragraph::new(file.line_numbers(
+                frame_height,
+                wrap_width,
+                self.line_numbers,
+                self.number_format,
+                self.theme,
+            ))
+            .block(
+                Block::new()
+                    .borders(Borders::TOP | Borders::BOTTOM)
+                    .border_style(border_style),
+            );
+
+            Widget::render(column, layout.numbers, buf);
+        }
+
+        // Text area
+        {
+            // Use custom border set to merge [Numbers] and [Text] bottom borders.
+            let border_set = symbols::border::Set {
+                bottom_left: symbols::line::NORMAL.horizontal_up,
+                top_left: symbols::line::NORMAL.horizontal_down,
+                ..symbols::border::PLAIN
+            };
+
+            let text = if file.removed {
+                vec![Line::from(REMOVED_BANNER).centered()]
+            } else {
+                let mut text = file.text_lines(self.levels, self.highlight, self.theme);
+                if let Some(selected) = file.selected_lines() {
+                    for (line, i) in text.iter_mut().zip(file.visible_numbers(frame_height)) {
+                        if selected.contains(&i) {
+                            *line = std::mem::take(line).patch_style(self.theme.highlight_style());
+                        }
+                    }
+                }
+                text
+            };
+
+            let par = Paragraph::new(text).block(
+                Block::new()
+                    .borders(Borders::LEFT | Borders::TOP | Borders::BOTTOM)
+                    .border_style(border_style)
+                    .border_set(border_set),
+            );
+
+            // Only the text scrolls horizontally, the numbers column stays in place.
+            let par = if self.wrap {
+                par.wrap(Wrap { trim: false })
+            } else {
+                par.scroll((0, file.horizontal_offset))
+            };
+
+            Widget::render(par, layout.text, buf);
+        }
+
+        // Top-right corner
+        {
+            let block = Block::new()
+                .borders(Borders::TOP | Borders::RIGHT)
+                .border_style(border_style);
+
+            Widget::render(block, layout.top_right_corner, buf);
+        }
+
+        // Scrollbar
+        // It tracks logical lines, like the scroll offset does, a page is the lines fitting when wrapped.
+        {
+            let page = file.page(frame_height);
+            if file.view_len() > page {
+                let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight)
+                    .begin_symbol(None)
+                    .end_symbol(None)
+                    .track_symbol("│".into())
+                    .thumb_symbol("┃");
+
+                let mut scrollbar_state =
+                    ScrollbarState::new(file.view_len().saturating_sub(page) as _)
+                        .position(file.view_offset() as _)
+                        .viewport_content_length(page as _);
+
+                StatefulWidget::render(scrollbar, layout.scrollbar, buf, &mut scrollbar_state);
+            } else {
+                let block = Block::new()
+                    .borders(Borders::RIGHT)
+                    .border_style(border_style);
+
+                Widget::render(block, layout.scrollbar, buf);
+            }
+        }
+
+        // Bottom-right corner
+        {
+            let block = Block::new()
+                .borders(Borders::BOTTOM | Borders::RIGHT)
+                .border_style(border_style);
+
+            Widget::render(block, layout.bottom_right_corner, buf);
+        }
+    }
+}
+
+impl FileState {
+    /// Fits the largest line number shown, it grows with the file.
+    fn number_column_width(&self, mode: LineNumberMode, format: NumberFormat, height: u32) -> u16 {
+        self.shown_number_width(mode, format, height) + if self.merged.is_some() { 5 } else { 3 }
+    }
+
+    /// The name, the top line, the number of lines and how far the top line is,
+    /// e.g. `app.log  12,340/98,765  12%`, followed by the selected lines if any.
+    fn status(&self) -> String {
+        let total = self.view_len();
+        let top = if total == 0 {
+            0
+        } else {
+            self.view_offset() + 1
+        };
+        let percent = if total == 0 {
+            0
+        } else {
+            u64::from(top) * 100 / u64::from(total)
+        };
+
+        let mut status = format!(
+            "{}  {}/{}  {percent}%",
+            self.title(),
+            thousands(top),
+            thousands(total)
+        );
+
+        if let Some(counter) = self.match_counter() {
+            _ = write!(status, "  {counter}");
+        }
+
+        if let Some(selection) = self.selection.as_ref() {
+            let lines = selection.lines();
+            _ = write!(
+                status,
+                "  selected lines {}–{}",
+                thousands(lines.start + 1),
+                thousands(lines.end)
+            );
+            if let Some((_, bytes)) = selection.bytes {
+                _ = write!(status, ", {}", ByteSize(bytes));
+            }
+        }
+
+        status
+    }
+
+    /// Digits in the largest line number.
+    fn number_width(&self) -> u16 {
+        u16::try_from(self.max_line_number().to_string().len()).unwrap_or(1)
+    }
+
+    /// The number of lines in the file, in the longest file of a merged view.
+    fn max_line_number(&self) -> u32 {
+        self.merged.as_ref().map_or(self.total_lines, |merged| {
+            merged
+                .sources
+                .iter()
+                .map(|source| source.total_lines)
+                .max()
+                .unwrap_or_default()
+        })
+    }
+
+    /// Characters in the widest number shown for the view of `height` lines.
+    /// The column fits the largest line number, or the largest offset in the view.
+    fn shown_number_width(&self, mode: LineNumberMode, format: NumberFormat, height: u32) -> u16 {
+        let widest = match (mode, format) {
+            (LineNumberMode::Absolute, NumberFormat::Plain) => return self.number_width(),
+            (LineNumberMode::Absolute, NumberFormat::Grouped) => {
+                thousands(self.max_line_number()).len()
+            }
+            (LineNumberMode::Absolute, NumberFormat::Offset) => {
+                let largest = self.offsets.iter().flatten().max();
+                largest.map_or(1, |offset| format!("{offset:x}").len())
+            }
+            (LineNumberMode::Relative, _) => {
+                let top = self
+                    .visible_numbers(1)
+                    .first()
+                    .map_or(1, |&i| self.shown_number(format, 0, i).len());
+                top.max(height.saturating_sub(1).to_string().len())
+            }
+        };
+        u16::try_from(widest).unwrap_or(1)
+    }
+
+    /// The line number, counting from one, or the byte offset of the line in row `row`.
+    fn shown_number(&self, format: NumberFormat, row: usize, line: u32) -> String {
+        match format {
+            NumberFormat::Plain => (line + 1).to_string(),
+            NumberFormat::Grouped => thousands(line + 1),
+            NumberFormat::Offset => self
+                .offsets
+                .get(row)
+                .copied()
+                .flatten()
+                .map_or_else(String::new, |offset| format!("{offset:x}")),
+        }
+    }
+
+    /// Indices of the lines in the view, they are not consecutive when filtered or merged.
+    fn visible_numbers(&self, height: u32) -> Vec<u32> {
+        if let Some(collapsed) = self.shown_runs() {
+            return collapsed
+                .after
+                .iter()
+                .take(height as usize)
+                .map(|run| run.start)
+                .collect();
+        }
+
+        if let Some(merged) = self.merged.as_ref() {
+            return merged
+                .visible_lines(height)
+                .map(|(_, line)| *line)
+                .collect();
+        }
+
+        self.filter.as_ref().map_or_else(
+            || (self.scroll_offset..(self.scroll_offset + height).min(self.total_lines)).collect(),
+            |filter| {
+                filter
+                    .lines
+                    .iter()
+                    .skip(filter.scroll_offset as usize)
+                    .take(height as usize)
+                    .map(|(index, _)| *index)
+                    .collect()
+            },
+        )
+    }
+
+    /// With `wrap_width`, each number is followed by empty rows for the wrapped part of its line.
+    /// A merged view tags the numbers with the source files.
+    fn line_numbers(
+        &self,
+        height: u32,
+        wrap_width: Option<u16>,
+        mode: LineNumberMode,
+        format: NumberFormat,
+        theme: &Theme,
+    ) -> Vec<Line<'_>> {
+        let digits = self.shown_number_width(mode, format, height) as usize;
+        let tags = self.merged.as_ref().map(|merged| {
+            merged
+                .visible_lines(height)
+                .map(|(source, _)| source_tag(*source))
+                .collect_vec()
+        });
+
+        self.visible_numbers(height)
+            .into_iter()
+            .zip(
+                self.display_lines
+                    .iter()
+                    .map(Some)
+                    .chain(std::iter::repeat(None)),
+            )
+            .enumerate()
+            .flat_map(|(row, (i, line))| {
+                let rows = wrap_width
+                    .zip(line)
+                    .map_or(1, |(width, line)| wrapped_rows(line, width));
+                let shown = match mode {
+                    LineNumberMode::Relative if row > 0 => row.to_string(),
+                    _ => self.shown_number(format, row, i),
+                };
+
+                let number = tags.as_ref().and_then(|tags| tags.get(row)).map_or_else(
+                    || {
+                        let number = Line::from(vec![Span::raw(shown.clone()), Span::raw(" ")]);
+                        if self.bookmarks.contains(&i) {
+                            number.fg(theme.bookmark)
+                        } else {
+                            number.fg(theme.dim)
+                        }
+                    },
+                    |tag| {
+                        Line::from(vec![
+                            tag.clone(),
+                            Span::raw(format!(" {shown:>digits$} ")).fg(theme.dim),
+                        ])
+                    },
+                );
+
+                std::iter::once(number.right_aligned())
+                    .chain(std::iter::repeat_n(Line::default(), rows - 1))
+            })
+            .take(height as usize)
+            .collect_vec()
+    }
+
+    /// Highlights the search matches, and the regex matches where they do not overlap.
+    fn text_lines(
+        &self,
+        levels: Option<&Levels>,
+        re: Option<&regex::Regex>,
+        theme: &Theme,
+    ) -> Vec<Line<'_>> {
+        let search = self.search.as_ref().map(|search| &search.re);
+        let runs = self
+            .shown_runs()
+            .map_or(&[][..], |collapsed| collapsed.after.as_slice());
+
+        self.display_lines
+            .iter()
+            .zip(runs.iter().map(|run| run.len).chain(std::iter::repeat(1)))
+            .map(|(line, count)| {
+                let found = search
+                    .into_iter()
+                    .flat_map(|re| re.find_iter(line))
+                    .map(|found| (found.range(), theme.search_match.style()))
+                    .collect_vec();
+
+                let matched = re
+                    .into_iter()
+                    .flat_map(|re| re.find_iter(line))
+                    .filter(|found| !found.is_empty())
+                    .map(|found| (found.range(), theme.highlight_match.style()));
+
+                let mut text = highlight(line, found.into_iter().chain(matched));
+                if count > 1 {
+                    text.spans
+                        .push(Span::raw(format!(" (×{count})")).fg(theme.dim));
+                }
+
+                match levels.and_then(|levels| level_style(line, levels)) {
+                    Some(style) => text.style(style),
+                    None => text,
+                }
+            })
+            .chain(std::iter::repeat_n(
+                Line::raw(PENDING_LINE).fg(theme.dim),
+                self.pending_lines as usize,
+            ))
+            .collect_vec()
+    }
+}
+
+/// Picks the style of the most severe level mentioned in the line.
+/// It runs on every render, so plain substring checks are used.
+fn level_style(line: &str, levels: &Levels) -> Option<Style> {
+    levels
+        .colors()
+        .iter()
+        .find(|(level, _)| line.contains(level))
+        .map(|(_, color)| Style::default().fg(*color))
+}
+
+/// Formats the number with comma separated thousands.
+fn thousands(n: u32) -> String {
+    let digits = n.to_string();
+    let mut formatted = String::with_capacity(digits.len() + digits.len() / 3);
+
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            formatted.push(',');
+        }
+        formatted.push(digit);
+    }
+
+    formatted
+}
+
+/// Marks the lines of a merged file with a letter.
+fn source_tag(index: usize) -> Span<'static> {
+    const COLORS: [Color; 4] = [Color::Cyan, Color::Magenta, Color::Green, Color::Blue];
+
+    let letter = u8::try_from(index % 26).map_or('?', |index| char::from(b'A' + index));
+    Span::styled(
+        letter.to_string(),
+        Style::default().fg(COLORS[index % COLORS.len()]),
+    )
+}
+
+/// Number of rows the line takes when wrapped to `width`.
+fn wrapped_rows(line: &str, width: u16) -> usize {
+    Paragraph::new(line)
+        .wrap(Wrap { trim: false })
+        .line_count(width)
+        .max(1)
+}
+
+/// Styles the ranges of the line, a range overlapping an earlier one is skipped.
+fn highlight(line: &str, ranges: impl Iterator<Item = (Range<usize>, Style)>) -> Line<'_> {
+    let mut spans = vec![];
+    let mut last = 0;
+
+    for (range, style) in ranges.sorted_by_key(|(range, _)| range.start) {
+        if range.start < last {
+            continue;
+        }
+        spans.push(Span::raw(&line[last..range.start]));
+        spans.push(Span::styled(&line[range.clone()], style));
+        last = range.end;
+    }
+    spans.push(Span::raw(&line[last..]));
+
+    Line::from(spans)
+}
+
+struct FileViewLayout {
+    tabs: Rect,
+    main: Rect,
+    status_bar: Rect,
+    bottom_line: Rect,
+}
+
+/// Layout of the file view
+///  ```
+/// [          tabs       ]
+/// [         main        ]
+/// [      status bar     ]
+/// [     bottom line     ]
+/// ```
+/// The main area holds one pane, or two for a split tab.
+/// The bottom line is for a prompt or a message, it is empty when there is none.
+impl FileViewLayout {
+    fn new(area: Rect, has_bottom_line: bool) -> Self {
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(1),
+                Constraint::Fill(1),
+                Constraint::Length(1),
+                Constraint::Length(u16::from(has_bottom_line)),
+            ])
+            .split(area);
+
+        Self {
+            tabs: layout[0],
+            main: layout[1],
+            status_bar: layout[2],
+            bottom_line: layout[3],
+        }
+    }
+}
+
+struct PaneLayout {
+    numbers: Rect,
+    text: Rect,
+    top_right_corner: Rect,
+    scrollbar: Rect,
+    bottom_right_corner: Rect,
+}
+
+/// Layout of a pane
+///  ```
+/// [numbers][text] [top_right_corner]
+///                 [scrollbar]
+///                 [bottom_right_corner]
+/// ```
+impl PaneLayout {
+    fn new(area: Rect, number_column_width: u16) -> Self {
+        let main = Layout::horizontal(vec![
+            Constraint::Length(number_column_width),
+            Constraint::Fill(1),
+            Constraint::Length(1),
+        ])
+        .split(area);
+
+        let numbers = main[0];
+        let text = main[1];
+
+        // Split scrollbar area into 3 rows because Scrollbar cannot be wrapped with a borderd block.
+        let scrollbar_area = Layout::vertical(vec![
+            Constraint::Length(1),
+            Constraint::Fill(1),
+            Constraint::Length(1),
+        ])
+        .split(main[2]);
+
+        Self {
+            numbers,
+            text,
+            top_right_corner: scrollbar_area[0],
+            scrollbar: scrollbar_area[1],
+            bottom_right_corner: scrollbar_area[2],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        hash::{DefaultHasher, Hash, Hasher},
+    };
+
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use tokio::sync::{mpsc, oneshot};
+
+    use crate::{
+        repository::{FileInfo, LineState, Matches, RepoLines, SearchDirection, SearchOptions},
+        theme::Theme,
+        timestamp, utils,
+        widgets::KeyEventHandler,
+    };
+
+    use ratatui::style::Color;
+    use time::UtcOffset;
+
+    use super::{
+        level_style, FileViewState, LineNumberMode, LongLineMode, MergedFileState, NumberFormat,
+        OpenMode, TailOnOpen,
+    };
+
+    /// Files kept in memory, only filtering and counting the matches are supported, all at once.
+    /// The lines equal to [`PENDING`] are not read yet. A file is indexed anew when its first line changes.
+    struct Repo(HashMap<&'static str, Vec<&'static str>>);
+
+    const PENDING: &str = "<pending>";
+
+    impl RepoLines for Repo {
+        fn lines(&self, name: &str, from: u32, to: u32) -> Box<[LineState]> {
+            self.0[name][from as usize..to as usize]
+                .iter()
+                .map(|&line| match line {
+                    PENDING => LineState::Pending,
+                    line => LineState::Loaded(line.into()),
+                })
+                .collect()
+        }
+
+        fn total(&self, name: &str) -> u32 {
+            self.0[name].len().try_into().unwrap()
+        }
+
+        fn is_removed(&self, name: &str) -> bool {
+            !self.0.contains_key(name)
+        }
+
+        fn index_version(&self, name: &str) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            self.0[name].first().hash(&mut hasher);
+            hasher.finish()
+        }
+
+        fn byte_len(&self, name: &str, from: u32, to: u32) -> u64 {
+            self.0[name][from as usize..to as usize]
+                .iter()
+                .map(|line| line.len() as u64)
+                .sum()
+        }
+
+        fn offset_of(&self, name: &str, line: u32) -> Option<u64> {
+            let before = self.0[name].get(..line as usize)?;
+            Some(before.iter().map(|line| line.len() as u64 + 1).sum())
+        }
+
+        fn find(
+            &self,
+            _: &str,
+            _: &str,
+            _: SearchOptions,
+            _: u32,
+            _: SearchDirection,
+        ) -> oneshot::Receiver<Option<u32>> {
+            oneshot::channel().1
+        }
+
+        fn filter(
+            &self,
+            name: &str,
+            needle: &str,
+            _: SearchOptions,
+            from: u32,
+            to: u32,
+        ) -> oneshot::Receiver<Matches> {
+            let (sender, receiver) = oneshot::channel();
+            let matches = (from..to)
+                .map(|i| (i, self.0[name][i as usize]))
+                .filter(|(_, line)| line.contains(needle))
+                .map(|(i, line)| (i, line.into()))
+                .collect();
+            sender.send(matches).unwrap();
+            receiver
+        }
+
+        fn count_matches(
+            &self,
+            name: &str,
+            needle: &str,
+            _: SearchOptions,
+            from: u32,
+            to: u32,
+        ) -> mpsc::UnboundedReceiver<Vec<u32>> {
+            let (sender, receiver) = mpsc::unbounded_channel();
+            let matches = (from..to)
+                .filter(|&i| self.0[name][i as usize].contains(needle))
+                .collect();
+            sender.send(matches).unwrap();
+            receiver
+        }
+
+        fn export(
+            &self,
+            _: &str,
+            _: u32,
+            _: u32,
+            _: Option<(&str, SearchOptions)>,
+            _: std::path::PathBuf,
+        ) -> oneshot::Receiver<Result<u64, String>> {
+            oneshot::channel().1
+        }
+    }
+
+    fn info(name: &str) -> FileInfo {
+        FileInfo {
+            name: name.to_string(),
+            path: name.into(),
+            last_update: utils::now(),
+            number_of_lines: 0,
+            present: true,
+        }
+    }
+
+    fn file_view(names: &[&str]) -> FileViewState {
+        let mut state = FileViewState::default();
+        for name in names {
+            state.open(info(name), OpenMode::Tab);
+        }
+        state
+    }
+
+    fn names(state: &FileViewState) -> Vec<&str> {
+        state
+            .files
+            .iter()
+            .map(|tab| tab.file.name.as_str())
+            .collect()
+    }
+
+    fn close_tab(state: &mut FileViewState) {
+        state.handle_key_event(&KeyEvent::new(KeyCode::Char('w'), KeyModifiers::CONTROL));
+    }
+
+    #[test]
+    fn close_first_tab() {
+        let mut state = file_view(&["a", "b", "c"]);
+        state.tabs.select(0);
+
+        close_tab(&mut state);
+
+        assert_eq!(names(&state), ["b", "c"]);
+        assert_eq!(state.tabs.selected(), 0);
+    }
+
+    #[test]
+    fn close_middle_tab() {
+        let mut state = file_view(&["a", "b", "c"]);
+        state.tabs.select(1);
+
+        close_tab(&mut state);
+
+        assert_eq!(names(&state), ["a", "c"]);
+        assert_eq!(state.tabs.selected(), 1);
+    }
+
+    #[test]
+    fn close_last_tab() {
+        let mut state = file_view(&["a", "b", "c"]);
+        assert_eq!(state.tabs.selected(), 2);
+
+        close_tab(&mut state);
+
+        assert_eq!(names(&state), ["a", "b"]);
+        assert_eq!(state.tabs.selected(), 1);
+    }
+
+    #[test]
+    fn switch_tabs() {
+        let mut state = file_view(&["a", "b", "c"]);
+        for (tab, offset) in state.files.iter_mut().zip([0, 10, 20]) {
+            tab.file.scroll_offset = offset;
+        }
+
+        let tab = KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE);
+        let back_tab = KeyEvent::new(KeyCode::BackTab, KeyModifiers::SHIFT);
+
+        state.handle_key_event(&tab);
+        assert_eq!(state.tabs.selected(), 0);
+        state.handle_key_event(&tab);
+        assert_eq!(state.tabs.selected(), 1);
+        state.handle_key_event(&back_tab);
+        state.handle_key_event(&back_tab);
+        assert_eq!(state.tabs.selected(), 2);
+
+        let offsets = state.files.iter().map(|tab| tab.file.scroll_offset);
+        assert!(offsets.eq([0, 10, 20]));
+    }
+
+    #[test]
+    fn close_only_tab() {
+        let mut state = file_view(&["a"]);
+
+        close_tab(&mut state);
+
+        assert!(state.is_empty());
+        assert_eq!(state.tabs.selected(), 0);
+    }
+
+    #[test]
+    fn scroll_horizontally() {
+        let mut state = file_view(&["a"]);
+        state.files[0].file.display_lines = ["short", "longest"].map(Into::into).into();
+
+        let left = KeyEvent::new(KeyCode::Left, KeyModifiers::NONE);
+        let right = KeyEvent::new(KeyCode::Right, KeyModifiers::NONE);
+
+        state.handle_key_event(&left);
+        assert_eq!(state.files[0].file.horizontal_offset, 0);
+
+        for _ in 0..10 {
+            state.handle_key_event(&right);
+        }
+        assert_eq!(state.files[0].file.horizontal_offset, 7);
+
+        state.handle_key_event(&left);
+        assert_eq!(state.files[0].file.horizontal_offset, 6);
+    }
+
+    #[test]
+    fn wrap_scrolls_back_horizontally() {
+        let mut state = file_view(&["a", "b"]);
+        state.open(info("c"), OpenMode::Split);
+        for tab in &mut state.files {
+            for pane in std::iter::once(&mut tab.file).chain(tab.split.as_mut()) {
+                pane.horizontal_offset = 5;
+            }
+        }
+
+        state.handle_key_event(&KeyEvent::new(KeyCode::Char('w'), KeyModifiers::NONE));
+
+        assert_eq!(state.long_lines, LongLineMode::Wrap);
+        let offsets = state
+            .files
+            .iter()
+            .flat_map(|tab| std::iter::once(&tab.file).chain(tab.split.as_ref()))
+            .map(|pane| pane.horizontal_offset);
+        assert!(
+            offsets.eq([0, 0, 0]),
+            "every pane, not only the focused one"
+        );
+    }
+
+    #[test]
+    fn no_horizontal_scroll_while_wrapping() {
+        let mut state = file_view(&["a"]);
+        state.files[0].file.display_lines = ["longest"].map(Into::into).into();
+        state.long_lines = LongLineMode::Wrap;
+
+        for code in [KeyCode::Right, KeyCode::Char('l')] {
+            state.handle_key_event(&KeyEvent::new(code, KeyModifiers::NONE));
+        }
+
+        assert_eq!(state.files[0].file.horizontal_offset, 0);
+    }
+
+    #[test]
+    fn unwrap_scrolls_horizontally_again() {
+        let mut state = file_view(&["a"]);
+        state.files[0].file.display_lines = ["longest"].map(Into::into).into();
+        state.long_lines = LongLineMode::Wrap;
+
+        let wrap = KeyEvent::new(KeyCode::Char('w'), KeyModifiers::NONE);
+        let right = KeyEvent::new(KeyCode::Right, KeyModifiers::NONE);
+
+        state.handle_key_event(&wrap);
+        state.handle_key_event(&right);
+
+        assert_eq!(state.long_lines, LongLineMode::Scroll);
+        assert_eq!(state.files[0].file.horizontal_offset, 1);
+    }
+
+    #[test]
+    fn relative_line_numbers() {
+        let mut state = file_view(&["a"]);
+        let file = &mut state.files[0].file;
+        file.total_lines = 1_000;
+        file.scroll_offset = 994;
+
+        let numbers = |mode| {
+            file.line_numbers(12, None, mode, NumberFormat::Plain, &Theme::default())
+                .iter()
+                .map(|line| line.to_string().trim().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            numbers(LineNumberMode::Absolute),
+            ["995", "996", "997", "998", "999", "1000"]
+        );
+        assert_eq!(
+            numbers(LineNumberMode::Relative),
+            ["995", "1", "2", "3", "4", "5"]
+        );
+
+        // The column fits the numbers shown rather than the number of lines.
+        assert_eq!(
+            file.number_column_width(LineNumberMode::Absolute, NumberFormat::Plain, 12),
+            7
+        );
+        assert_eq!(
+            file.number_column_width(LineNumberMode::Relative, NumberFormat::Plain, 12),
+            6
+        );
+
+        file.scroll_offset = 0;
+        assert_eq!(
+            file.number_column_width(LineNumberMode::Relative, NumberFormat::Plain, 12),
+            5
+        );
+    }
+
+    #[test]
+    fn wrapped_line_numbers() {
+        let mut state = file_view(&["a"]);
+        let file = &mut state.files[0].file;
+        file.total_lines = 2;
+        file.display_lines = ["abcdef", "ab"].map(Into::into).into();
+
+        let numbers = |wrap_width| {
+            file.line_numbers(
+                10,
+                wrap_width,
+                LineNumberMode::Absolute,
+                NumberFormat::Plain,
+                &Theme::default(),
+            )
+            .iter()
+            .map(|line| line.to_string().trim().to_string())
+            .collect::<Vec<_>>()
+        };
+
+        assert_eq!(numbers(None), ["1", "2"]);
+        assert_eq!(numbers(Some(4)), ["1", "", "2"]);
+    }
+
+    #[test]
+    fn grouped_line_numbers() {
+        let mut state = file_view(&["a"]);
+        let file = &mut state.files[0].file;
+        file.total_lines = 1_234_567;
+        file.scroll_offset = 1_234_565;
+
+        let numbers = |mode| {
+            file.line_numbers(12, None, mode, NumberFormat::Grouped, &Theme::default())
+                .iter()
+                .map(|line| line.to_string().trim().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            numbers(LineNumberMode::Absolute),
+            ["1,234,566", "1,234,567"]
+        );
+        // The distances are short, they stay plain.
+        assert_eq!(numbers(LineNumberMode::Relative), ["1,234,566", "1"]);
+
+        assert_eq!(
+            file.number_column_width(LineNumberMode::Absolute, NumberFormat::Grouped, 12),
+            12
+        );
+    }
+
+    #[test]
+    fn offset_line_numbers() {
+        // 17 bytes a line, the newline included.
+        let repo = Repo(HashMap::from([("a", vec!["0123456789abcdef"; 20])]));
+        let mut state = file_view(&["a"]).number_format(NumberFormat::Offset);
+        state.height = 3;
+        state.update(&repo);
+        state.files[0].file.scroll_offset = 16;
+        state.update(&repo);
+
+        let file = &state.files[0].file;
+        let numbers = file
+            .line_numbers(
+                3,
+                None,
+                LineNumberMode::Absolute,
+                NumberFormat::Offset,
+                &Theme::default(),
+            )
+            .iter()
+            .map(|line| line.to_string().trim().to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(numbers, ["110", "121", "132"]);
+        assert_eq!(
+            file.number_column_width(LineNumberMode::Absolute, NumberFormat::Offset, 3),
+            6
+        );
+    }
+
+    #[test]
+    fn wrapped_page() {
+        let mut state = file_view(&["a"]);
+        state.height = 5;
+        let file = &mut state.files[0].file;
+        file.total_lines = 100;
+        file.display_lines = ["a".repeat(25), "b".into(), "c".repeat(10)]
+            .map(Into::into)
+            .into();
+
+        // 3 + 1 + 1 rows at the width of 10.
+        assert_eq!(file.fitting_lines(5, 10), 3);
+        assert_eq!(file.fitting_lines(4, 10), 2);
+        assert_eq!(file.fitting_lines(2, 10), 1);
+        // The rows below the shown lines.
+        assert_eq!(file.fitting_lines(8, 10), 6);
+        assert_eq!(file.fitting_lines(5, 30), 5);
+
+        file.page = Some(2);
+        let down = KeyEvent::new(KeyCode::PageDown, KeyModifiers::NONE);
+        state.handle_key_event(&down);
+        assert_eq!(state.files[0].file.scroll_offset, 2);
+    }
+
+    #[test]
+    fn collapse_repeated_lines() {
+        let repo = Repo(HashMap::from([(
+            "a",
+            vec!["x", "x", "x", "y", "z", "z", "x", "x"],
+        )]));
+
+        let runs = repo
+            .runs("a", 0, 8)
+            .iter()
+            .map(|run| (run.start, run.len))
+            .collect::<Vec<_>>();
+        assert_eq!(runs, [(0, 3), (3, 1), (4, 2), (6, 2)]);
+
+        let mut state = file_view(&["a"]);
+        state.height = 2;
+        state.handle_key_event(&KeyEvent::new(KeyCode::Char('D'), KeyModifiers::NONE));
+        state.update(&repo);
+
+        let file = &state.files[0].file;
+        assert_eq!(file.visible_numbers(2), [0, 3]);
+        let rows = file.text_lines(None, None, &Theme::default());
+        assert_eq!(rows[0].to_string(), "x (×3)");
+        assert_eq!(rows[1].to_string(), "y");
+
+        // A page is two runs, the last page is kept full.
+        let down = KeyEvent::new(KeyCode::PageDown, KeyModifiers::NONE);
+        state.handle_key_event(&down);
+        state.update(&repo);
+        assert_eq!(state.files[0].file.scroll_offset, 4);
+        state.handle_key_event(&down);
+        state.update(&repo);
+        assert_eq!(state.files[0].file.scroll_offset, 4);
+        assert_eq!(state.files[0].file.visible_numbers(2), [4, 6]);
+
+        let up = KeyEvent::new(KeyCode::Up, KeyModifiers::NONE);
+        state.handle_key_event(&up);
+        state.update(&repo);
+        assert_eq!(state.files[0].file.scroll_offset, 3);
+
+        state.handle_key_event(&KeyEvent::new(KeyCode::Char('B'), KeyModifiers::NONE));
+        state.update(&repo);
+        assert_eq!(state.files[0].file.visible_numbers(2), [4, 6]);
+        state.handle_key_event(&up);
+        state.update(&repo);
+        assert_eq!(state.files[0].file.scroll_offset, 3);
+
+        // The raw lines from the same top line.
+        state.handle_key_event(&KeyEvent::new(KeyCode::Char('D'), KeyModifiers::NONE));
+        state.update(&repo);
+        assert_eq!(state.files[0].file.visible_numbers(2), [3, 4]);
+        assert_eq!(
+            state.files[0]
+                .file
+                .text_lines(None, None, &Theme::default())[1]
+                .to_string(),
+            "z"
+        );
+    }
+
+    #[test]
+    fn tail_on_open() {
+        let repo = Repo(HashMap::from([
+            ("long", vec!["x"; 10]),
+            ("short", vec!["x"; 2]),
+            ("empty", vec![]),
+        ]));
+
+        let mut state = FileViewState::with_tail_on_open(TailOnOpen::Page);
+        state.height = 4;
+        for name in ["long", "short", "empty"] {
+            state.open_name(name.to_string());
+            state.update(&repo);
+        }
+        let offsets = |state: &FileViewState| {
+            state
+                .files
+                .iter()
+                .map(|tab| tab.file.scroll_offset)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(offsets(&state), [6, 0, 0]);
+
+        // Only once, scrolling away from the end is kept.
+        state.tabs.select(0);
+        state.files[0].file.scroll_offset = 1;
+        state.update(&repo);
+        assert_eq!(offsets(&state)[0], 1);
+
+        let mut state = FileViewState::with_tail_on_open(TailOnOpen::Lines(3));
+        state.height = 4;
+        state.open_name("long".to_string());
+        state.update(&repo);
+        assert_eq!(offsets(&state), [7]);
+
+        let mut state = FileViewState::with_tail_on_open(TailOnOpen::Off);
+        state.height = 4;
+        state.open_name("long".to_string());
+        state.update(&repo);
+        assert_eq!(offsets(&state), [0]);
+    }
+
+    #[test]
+    fn select_lines() {
+        let repo = Repo(HashMap::from([("a", vec!["0123456789"; 10])]));
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+
+        let mut state = file_view(&["a"]);
+        state.height = 4;
+        state.update(&repo);
+        state.files[0].file.scroll_offset = 1;
+
+        state.handle_key_event(&key(KeyCode::Char('V')));
+        for _ in 0..5 {
+            state.handle_key_event(&key(KeyCode::Down));
+        }
+        state.update(&repo);
+
+        // The view follows the head, the anchor is out of it.
+        let file = &state.files[0].file;
+        assert_eq!(file.selected_lines(), Some(1..7));
+        assert_eq!(file.scroll_offset, 3);
+        assert_eq!(file.status(), "a  4/10  40%  selected lines 2–7, 60 B");
+        assert_eq!(
+            file.export("out".into(), true, 4).lines,
+            1..7,
+            "the selection is exported instead of the visible lines"
+        );
+
+        // Above the anchor, then clamped to the first line.
+        state.handle_key_event(&key(KeyCode::PageUp));
+        state.handle_key_event(&key(KeyCode::PageUp));
+        assert_eq!(state.files[0].file.selected_lines(), Some(0..2));
+        assert_eq!(state.files[0].file.scroll_offset, 0);
+
+        state.handle_key_event(&key(KeyCode::Char('G')));
+        assert_eq!(state.files[0].file.selected_lines(), Some(1..10));
+
+        state.handle_key_event(&key(KeyCode::Esc));
+        assert_eq!(state.files[0].file.selected_lines(), None);
+
+        // Moving without a selection scrolls the view.
+        state.handle_key_event(&key(KeyCode::Char('g')));
+        assert_eq!(state.files[0].file.scroll_offset, 0);
+    }
+
+    #[test]
+    fn reopen_where_it_was_left() {
+        let mut state = FileViewState::with_tail_on_open(TailOnOpen::Page);
+        state.height = 2;
+        let mut repo = Repo(HashMap::from([("a", vec!["x"; 10]), ("b", vec!["x"; 10])]));
+
+        state.open_name("a".to_string());
+        state.update(&repo);
+        state.files[0].file.scroll_offset = 5;
+        state.open(info("b"), OpenMode::Split);
+        state.update(&repo);
+        state.files[0].split.as_mut().unwrap().scroll_offset = 7;
+
+        close_tab(&mut state);
+        close_tab(&mut state);
+        assert!(state.is_empty());
+
+        // Remembered over tailing.
+        state.open_name("a".to_string());
+        state.update(&repo);
+        assert_eq!(state.files[0].file.scroll_offset, 5);
+
+        // Truncated below the remembered offset.
+        repo.0.insert("b", vec!["x"; 4]);
+        state.open(info("b"), OpenMode::Split);
+        state.update(&repo);
+        assert_eq!(state.files[0].split.as_ref().unwrap().scroll_offset, 2);
+    }
+
+    #[test]
+    fn follow_a_file() {
+        let mut state = file_view(&["a", "b"]);
+        state.files[1].file.stick_to_bottom = false;
+
+        state.follow("a".to_string());
+        assert_eq!(state.tabs.selected(), 0);
+        assert!(state.files[0].file.stick_to_bottom);
+
+        state.follow("c".to_string());
+        assert_eq!(names(&state), ["a", "b", "c"]);
+        assert_eq!(state.tabs.selected(), 2);
+        assert!(state.files[2].file.stick_to_bottom);
+    }
+
+    #[test]
+    fn level_colors() {
+        let levels = Theme::default().levels;
+        let color = |line| level_style(line, &levels).and_then(|style| style.fg);
+
+        assert_eq!(color("ERROR failed"), Some(Color::Red));
+        assert_eq!(color("12:00:00 [WARN] slow"), Some(Color::Yellow));
+        assert_eq!(color("INFO retrying after ERROR"), Some(Color::Red));
+        assert_eq!(color("plain text"), None);
+    }
+
+    #[test]
+    fn filter_keeps_line_numbers() {
+        let mut state = file_view(&["a"]);
+        state.height = 2;
+        let file = &mut state.files[0].file;
+        file.total_lines = 100;
+        file.scroll_offset = 50;
+        file.filter("x".to_string(), SearchOptions::default());
+        file.filter.as_mut().unwrap().lines = [3, 30, 60].map(|index| (index, "x".into())).into();
+
+        assert_eq!(file.visible_numbers(2), [3, 30]);
+
+        let down = KeyEvent::new(KeyCode::PageDown, KeyModifiers::NONE);
+        state.handle_key_event(&down);
+        assert_eq!(state.files[0].file.visible_numbers(2), [30, 60]);
+
+        state.handle_key_event(&KeyEvent::new(KeyCode::Char('&'), KeyModifiers::NONE));
+        assert!(state.files[0].file.filter.is_none());
+        assert_eq!(state.files[0].file.scroll_offset, 50);
+    }
+
+    #[test]
+    fn filter_restarts_on_reindex() {
+        let mut repo = Repo(HashMap::from([("a", vec!["error 1", "ok", "error 2"])]));
+        let mut state = file_view(&["a"]);
+        state.height = 2;
+        state.update(&repo);
+        state.files[0]
+            .file
+            .filter("error".into(), SearchOptions::default());
+
+        let filtered = |state: &FileViewState| {
+            state.files[0]
+                .file
+                .filter
+                .as_ref()
+                .unwrap()
+                .lines
+                .iter()
+                .map(|(index, line)| (*index, line.to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        state.update(&repo);
+        state.update(&repo);
+        assert_eq!(
+            filtered(&state),
+            [(0, "error 1".to_string()), (2, "error 2".to_string())]
+        );
+
+        // Replaced by a longer file before the next update.
+        repo.0
+            .insert("a", vec!["error A", "ok", "ok", "ok", "error B"]);
+        state.update(&repo);
+        state.update(&repo);
+        assert_eq!(
+            filtered(&state),
+            [(0, "error A".to_string()), (4, "error B".to_string())]
+        );
+    }
+
+    #[test]
+    fn jump_to_top_and_bottom() {
+        let mut state = file_view(&["a"]);
+        state.height = 10;
+        state.files[0].file.total_lines = 100;
+        state.files[0].file.scroll_offset = 50;
+
+        for (code, expected) in [
+            (KeyCode::Home, 0),
+            (KeyCode::End, 90),
+            (KeyCode::Char('g'), 0),
+            (KeyCode::Char('G'), 90),
+        ] {
+            state.handle_key_event(&KeyEvent::new(code, KeyModifiers::NONE));
+            assert_eq!(state.files[0].file.scroll_offset, expected, "{code:?}");
+        }
+    }
+
+    #[test]
+    fn go_to_bookmarks() {
+        let mut state = file_view(&["a"]);
+        state.height = 10;
+        state.files[0].file.total_lines = 100;
+
+        let press = |state: &mut FileViewState, c| {
+            state.handle_key_event(&KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+            state.files[0].file.scroll_offset
+        };
+
+        for offset in [95, 40, 5] {
+            state.files[0].file.scroll_offset = offset;
+            press(&mut state, 'm');
+        }
+        // The last bookmarked line is at the top.
+        assert_eq!(state.message.as_deref(), Some("Bookmarked line 6"));
+
+        assert_eq!(press(&mut state, ']'), 40);
+        // Scrolled as far as the view goes.
+        assert_eq!(press(&mut state, ']'), 90);
+        assert_eq!(press(&mut state, ']'), 90);
+        assert_eq!(state.message.as_deref(), Some("No bookmark below"));
+        assert_eq!(press(&mut state, '['), 40);
+
+        // Removes the bookmark at the top.
+        press(&mut state, 'm');
+        assert_eq!(press(&mut state, '['), 5);
+        assert_eq!(press(&mut state, ']'), 90);
+
+        let bookmarks = &state.session()[0].file.bookmarks;
+        assert_eq!(bookmarks, &[5, 95]);
+    }
+
+    #[test]
+    fn restore_split_tab() {
+        let mut state = file_view(&["a"]);
+        state.open(info("b"), OpenMode::Split);
+        state.files[0].file.scroll_offset = 10;
+        state.files[0].split.as_mut().unwrap().scroll_offset = 20;
+
+        let mut restored = FileViewState::default();
+        for tab in state.session() {
+            restored.restore(tab);
+        }
+
+        let tab = &restored.files[0];
+        assert_eq!((tab.file.name.as_str(), tab.file.scroll_offset), ("a", 10));
+        let split = tab.split.as_ref().unwrap();
+        assert_eq!((split.name.as_str(), split.scroll_offset), ("b", 20));
+        assert!(!tab.split_focused);
+    }
+
+    #[test]
+    fn export_lines() {
+        let mut state = file_view(&["a"]);
+        state.height = 2;
+        let file = &mut state.files[0].file;
+        file.total_lines = 100;
+        file.search("y".to_string(), SearchOptions::default());
+
+        let export = |state: &mut FileViewState, key| {
+            for c in [key, 'o', 'u', 't'] {
+                state.handle_key_event(&KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+            }
+            state.handle_key_event(&KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+
+            let export = state.export.take().unwrap();
+            assert_eq!(export.path, std::path::Path::new("out"));
+            (export.lines, export.matching.map(|(needle, _)| needle))
+        };
+
+        assert_eq!(export(&mut state, 'e'), (0..100, Some("y".to_string())));
+        assert_eq!(export(&mut state, 'E'), (0..2, None));
+
+        let file = &mut state.files[0].file;
+        file.filter("x".to_string(), SearchOptions::default());
+        file.filter.as_mut().unwrap().lines = [3, 30, 60].map(|index| (index, "x".into())).into();
+
+        assert_eq!(export(&mut state, 'e'), (0..100, Some("x".to_string())));
+        assert_eq!(export(&mut state, 'E'), (3..31, Some("x".to_string())));
+    }
+
+    #[test]
+    fn split_tab() {
+        let mut state = file_view(&["a"]);
+        state.open(info("b"), OpenMode::Split);
+
+        assert_eq!(names(&state), ["a"]);
+        assert_eq!(state.files[0].title(), "a | b");
+        assert!(state.files[0].split_focused);
+
+        let focus_left = KeyEvent::new(KeyCode::Left, KeyModifiers::CONTROL);
+        state.handle_key_event(&focus_left);
+        assert!(!state.files[0].split_focused);
+
+        // Closing the left pane leaves the right one.
+        close_tab(&mut state);
+        assert_eq!(state.files[0].title(), "b");
+
+        close_tab(&mut state);
+        assert!(state.is_empty());
+    }
+
+    #[test]
+    fn split_without_tabs() {
+        let mut state = file_view(&[]);
+        state.open(info("a"), OpenMode::Split);
+
+        assert_eq!(names(&state), ["a"]);
+        assert!(state.files[0].split.is_none());
+    }
+
+    #[test]
+    fn merge_by_timestamp() {
+        let repo = Repo(HashMap::from([
+            (
+                "a",
+                vec![
+                    "2024-05-01 10:00:01 a0",
+                    "    a1 has no timestamp",
+                    "2024-05-01 10:00:03 a2",
+                ],
+            ),
+            (
+                "b",
+                vec!["2024-05-01 10:00:02 b0", "2024-05-01 10:00:03 b1"],
+            ),
+        ]));
+
+        let mut merged = MergedFileState::new(
+            ["a".to_string(), "b".to_string()],
+            timestamp::iso8601,
+            UtcOffset::UTC,
+        );
+        merged.update(&repo);
+
+        assert_eq!(merged.lines, [(0, 0), (0, 1), (1, 0), (0, 2), (1, 1)]);
+    }
+
+    #[test]
+    fn merge_by_format() {
+        let repo = Repo(HashMap::from([
+            ("a", vec!["Jun  1 10:00:01 a0", "Jun  1 10:00:03 a1"]),
+            ("b", vec!["Jun  1 10:00:02 b0"]),
+            // 08:00 and 09:00 in UTC.
+            ("c", vec!["2024-05-01T10:00:00+02:00 c0"]),
+            ("d", vec!["2024-05-01 09:00:00Z d0"]),
+        ]));
+
+        let mut merged = MergedFileState::new(
+            ["a".to_string(), "b".to_string()],
+            timestamp::syslog,
+            UtcOffset::UTC,
+        );
+        merged.update(&repo);
+        assert_eq!(merged.lines, [(0, 0), (1, 0), (0, 1)]);
+
+        let mut merged = MergedFileState::new(
+            ["d".to_string(), "c".to_string()],
+            timestamp::any,
+            UtcOffset::UTC,
+        );
+        merged.update(&repo);
+        assert_eq!(merged.lines, [(1, 0), (0, 0)]);
+    }
+
+    #[test]
+    fn pending_lines() {
+        let mut state = file_view(&["a"]);
+        let file = &mut state.files[0].file;
+
+        let repo = Repo(HashMap::from([("a", vec!["0", "1", PENDING, "3"])]));
+        file.update(&repo, 4);
+        assert_eq!(file.pending_lines, 2);
+
+        let rows = file.text_lines(None, None, &Theme::default());
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[2].to_string(), super::PENDING_LINE);
+
+        file.update(&Repo(HashMap::from([("a", vec!["0", "1", "2", "3"])])), 4);
+        assert_eq!(file.pending_lines, 0);
+        assert_eq!(file.text_lines(None, None, &Theme::default()).len(), 4);
+    }
+
+    #[test]
+    fn reattach_recreated_file() {
+        let mut state = file_view(&["a"]);
+        let file = &mut state.files[0].file;
+        let lines = vec!["0", "1", "2", "3"];
+
+        file.update(&Repo(HashMap::from([("a", lines.clone())])), 2);
+        file.scroll_offset = 2;
+
+        file.update(&Repo(HashMap::new()), 2);
+        assert!(file.removed);
+        assert!(file.display_lines.is_empty());
+
+        file.update(&Repo(HashMap::from([("a", lines)])), 2);
+        assert!(!file.removed);
+        assert_eq!(file.scroll_offset, 0);
+        assert_eq!(&*file.display_lines[0], "0");
+    }
+
+    #[test]
+    fn status_bar() {
+        let mut state = file_view(&["app.log"]);
+        let file = &mut state.files[0].file;
+        file.total_lines = 98_765;
+        file.scroll_offset = 12_339;
+
+        assert_eq!(file.status(), "app.log  12,340/98,765  12%");
+
+        file.total_lines = 0;
+        file.scroll_offset = 0;
+        assert_eq!(file.status(), "app.log  0/0  0%");
+    }
+
+    #[test]
+    fn vim_keys() {
+        let mut state = file_view(&["a"]);
+        state.height = 10;
+        state.files[0].file.total_lines = 100;
+
+        for (code, modifiers, expected) in [
+            (KeyCode::Char('j'), KeyModifiers::NONE, 1),
+            (KeyCode::Char('d'), KeyModifiers::CONTROL, 6),
+            (KeyCode::Char('k'), KeyModifiers::NONE, 5),
+            (KeyCode::Char('u'), KeyModifiers::CONTROL, 0),
+        ] {
+            state.handle_key_event(&KeyEvent::new(code, modifiers));
+            assert_eq!(state.files[0].file.scroll_offset, expected, "{code:?}");
+        }
+    }
+
+    #[test]
+    fn highlight_regex() {
+        let mut state = file_view(&["a"]);
+        let file = &mut state.files[0].file;
+        file.display_lines = ["id=42 took 7ms"].map(Into::into).into();
+        file.search("took".to_string(), SearchOptions::default());
+
+        let re = regex::Regex::new(r"\d+").unwrap();
+        let spans = file.text_lines(None, Some(&re), &Theme::default())[0]
+            .spans
+            .iter()
+            .map(|span| span.content.to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(spans, ["id=", "42", " ", "took", " ", "7", "ms"]);
+    }
+
+    #[test]
+    fn invalid_regex() {
+        let mut state = file_view(&["a"]);
+        state.highlight("(unclosed");
+
+        assert!(state.highlight.is_none());
+        assert!(state
+            .message
+            .as_deref()
+            .is_some_and(|message| message.starts_with("Invalid regex:")));
+    }
+
+    #[test]
+    fn count_matches() {
+        let mut repo = Repo(HashMap::from([(
+            "a",
+            vec!["error 1", "ok", "error 2", "ok", "error 3"],
+        )]));
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+
+        let mut state = file_view(&["a"]);
+        state.height = 2;
+        state.update(&repo);
+        state.files[0]
+            .file
+            .search("error".into(), SearchOptions::default());
+
+        // Requested on one update, collected on the next.
+        state.update(&repo);
+        assert_eq!(state.files[0].file.status(), "a  1/5  20%  0 matches…");
+        state.update(&repo);
+        assert_eq!(state.files[0].file.status(), "a  1/5  20%  3 matches");
+
+        for code in [KeyCode::Char('#'), KeyCode::Char('3'), KeyCode::Enter] {
+            state.handle_key_event(&key(code));
+        }
+        state.update(&repo);
+        assert_eq!(state.files[0].file.status(), "a  4/5  80%  match 3 of 3");
+
+        // The appended lines are counted too.
+        repo.0.get_mut("a").unwrap().push("error 4");
+        state.update(&repo);
+        state.update(&repo);
+        assert_eq!(state.files[0].file.status(), "a  4/6  66%  match 3 of 4");
+
+        for code in [KeyCode::Char('#'), KeyCode::Char('5'), KeyCode::Enter] {
+            state.handle_key_event(&key(code));
+        }
+        assert_eq!(state.message.as_deref(), Some("No match 5, there are 4"));
+
+        // Replaced by a longer file before the next update.
+        repo.0.insert(
+            "a",
+            vec!["ok", "ok", "ok", "ok", "error 1", "ok", "error 2"],
+        );
+        state.update(&repo);
+        state.update(&repo);
+        assert_eq!(state.files[0].file.status(), "a  4/7  57%  match 1 of 2");
     }
 }
@@ -0,0 +1,119 @@
+use ratatui::{
+    layout::Margin,
+    prelude::{Buffer, Rect},
+    style::{Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph, Widget},
+};
+
+use crate::{theme::Theme, utils::RectExt};
+
+const TITLE: &str = "Help";
+
+/// Key bindings grouped by context.
+const KEY_BINDINGS: &[(&str, &[(&str, &str)])] = &[
+    (
+        "Global",
+        &[
+            ("o", "Open the file list"),
+            ("A", "Follow the file modified last, again to stop"),
+            ("q", "Quit"),
+            ("?", "Show or hide this help"),
+        ],
+    ),
+    (
+        "File list",
+        &[
+            ("Up/Down, k/j", "Select a file"),
+            ("Enter", "Open the file in a new tab"),
+            ("Shift+Enter", "Open the file next to the active tab"),
+            ("Ctrl+Enter", "Merge the file into the active tab"),
+            ("n/N", "Sort by name"),
+            ("l/L", "Sort by number of lines"),
+            ("a/A", "Sort by age"),
+            ("p", "Show the full paths"),
+            ("s", "Show the number of files, lines and bytes"),
+            ("/", "Filter the files by name, empty to show all"),
+            ("Esc", "Close the list"),
+        ],
+    ),
+    (
+        "File view",
+        &[
+            ("Up/Down, k/j", "Scroll by a line, by a page with Shift"),
+            ("PageUp/PageDown", "Scroll by a page"),
+            ("Ctrl+u/Ctrl+d", "Scroll by half a page"),
+            ("Home/g, End/G", "Go to the first or the last line"),
+            ("Left/Right, h/l", "Scroll horizontally"),
+            ("Tab/Shift+Tab", "Switch tabs"),
+            ("Ctrl+Left/Right", "Focus the left or the right pane"),
+            ("Ctrl+w", "Close the merged view, the pane or the tab"),
+            ("B", "Stick to the bottom"),
+            ("F", "Toggle follow mode"),
+            ("/", "Search"),
+            ("n/N", "Go to the next or the previous match"),
+            ("#", "Go to a match by its number"),
+            ("*", "Highlight a regex, empty to turn it off"),
+            ("&", "Filter the lines, again to turn it off"),
+            ("Alt+c, Alt+w", "Toggle ignore case, whole word in / and &"),
+            (":", "Go to a line"),
+            ("m", "Bookmark the top line, again to remove it"),
+            ("[/]", "Go to the previous or the next bookmark"),
+            ("M", "List the bookmarks"),
+            ("e", "Export the filtered, the found or all lines to a file"),
+            ("E", "Export the selected or the visible lines to a file"),
+            (
+                "V",
+                "Select lines from the top one, move to extend, again or Esc to stop",
+            ),
+            ("w", "Toggle line wrapping"),
+            ("c", "Toggle level colors"),
+            ("r", "Toggle relative line numbers"),
+            ("D", "Collapse repeated lines into one"),
+        ],
+    ),
+];
+
+/// Popup listing the key bindings.
+#[derive(Debug, Clone, Copy)]
+pub struct Help {
+    pub theme: Theme,
+}
+
+impl Widget for Help {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let key_width = KEY_BINDINGS
+            .iter()
+            .flat_map(|(_, bindings)| bindings.iter())
+            .map(|(key, _)| key.len())
+            .max()
+            .unwrap_or_default()
+            + 2;
+
+        let mut lines = vec![];
+        for (context, bindings) in KEY_BINDINGS {
+            if !lines.is_empty() {
+                lines.push(Line::default());
+            }
+            lines.push(Line::from(*context).bold());
+            lines.extend(bindings.iter().map(|(key, description)| {
+                Line::from(vec![
+                    Span::styled(
+                        format!("  {key:<key_width$}"),
+                        Style::default().fg(self.theme.highlight.fg),
+                    ),
+                    Span::raw(*description),
+                ])
+            }));
+        }
+
+        let centered = area.inner_centered(60, 80);
+
+        // Clear the area for popup.
+        Clear.render(centered.outer(Margin::new(2, 1)).clamp(area), buf);
+
+        Paragraph::new(lines)
+            .block(Block::default().title(TITLE).borders(Borders::ALL))
+            .render(centered, buf);
+    }
+}
@@ -0,0 +1,137 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::{
+    prelude::{Buffer, Rect},
+    style::Stylize,
+    text::{Line, Span},
+    widgets::{Paragraph, StatefulWidget, Widget},
+};
+
+use crate::repository::SearchOptions;
+
+use super::KeyEventHandler;
+
+/// Single-line input, e.g. `/query`.
+#[derive(Debug, Clone)]
+pub struct PromptState {
+    prefix: char,
+    input: String,
+    accept: fn(char) -> bool,
+    /// Toggled with Alt+c and Alt+w, shown after the input.
+    search_options: Option<SearchOptions>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PromptAction {
+    Submit(String),
+    Cancel,
+}
+
+impl PromptState {
+    pub fn new(prefix: char) -> Self {
+        Self {
+            prefix,
+            input: String::new(),
+            accept: |_| true,
+            search_options: None,
+        }
+    }
+
+    pub fn search(prefix: char, options: SearchOptions) -> Self {
+        Self {
+            search_options: Some(options),
+            ..Self::new(prefix)
+        }
+    }
+
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    pub fn set_input(&mut self, input: &str) {
+        input.clone_into(&mut self.input);
+    }
+
+    pub const fn search_options(&self) -> Option<SearchOptions> {
+        self.search_options
+    }
+
+    pub fn digits(prefix: char) -> Self {
+        Self {
+            accept: |c| c.is_ascii_digit(),
+            ..Self::new(prefix)
+        }
+    }
+}
+
+impl KeyEventHandler for PromptState {
+    type Action = PromptAction;
+
+    fn handle_key_event(&mut self, event: &KeyEvent) -> Option<Self::Action> {
+        if event.kind != KeyEventKind::Press {
+            return None;
+        }
+
+        match event.code {
+            KeyCode::Enter => return PromptAction::Submit(std::mem::take(&mut self.input)).into(),
+            KeyCode::Esc => return PromptAction::Cancel.into(),
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Char('c') if event.modifiers.contains(KeyModifiers::ALT) => {
+                if let Some(options) = self.search_options.as_mut() {
+                    options.case_insensitive = !options.case_insensitive;
+                }
+            }
+            KeyCode::Char('w') if event.modifiers.contains(KeyModifiers::ALT) => {
+                if let Some(options) = self.search_options.as_mut() {
+                    options.whole_word = !options.whole_word;
+                }
+            }
+            KeyCode::Char(c)
+                if (self.accept)(c)
+                    && !event
+                        .modifiers
+                        .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+            {
+                self.input.push(c);
+            }
+            _ => {}
+        }
+
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Prompt {}
+
+impl StatefulWidget for Prompt {
+    type State = PromptState;
+
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        let mut spans = vec![
+            Span::raw(state.prefix.to_string()),
+            Span::raw(state.input.as_str()),
+            // Cursor
+            Span::raw(" ").reversed(),
+        ];
+
+        if let Some(options) = state.search_options {
+            for (label, on) in [
+                ("ignore case", options.case_insensitive),
+                ("whole word", options.whole_word),
+            ] {
+                spans.push(Span::raw("  "));
+                spans.push(if on {
+                    Span::raw(label).reversed()
+                } else {
+                    Span::raw(label).dark_gray()
+                });
+            }
+        }
+
+        let line = Line::from(spans);
+
+        Paragraph::new(line).render(area, buf);
+    }
+}
@@ -1,4 +1,4 @@
-use crossterm::event::KeyEvent;
+use crossterm::event::{KeyEvent, MouseEvent};
 
 pub trait KeyEventHandler {
     type Action;
@@ -7,3 +7,11 @@ pub trait KeyEventHandler {
         None
     }
 }
+
+pub trait MouseEventHandler {
+    type Action;
+    fn handle_mouse_event(&mut self, event: MouseEvent) -> Option<Self::Action> {
+        let _ = event;
+        None
+    }
+}